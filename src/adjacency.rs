@@ -0,0 +1,189 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+
+use crate::sudoku::Cell;
+
+/// Which pairs of distinct cells can't hold the same digit. Every variant that forbids repeating a
+/// digit among some cells adds an implementation, so that eliminations follow it automatically.
+pub trait Adjacency {
+    /// Whether `a` and `b` are distinct cells that can't hold the same digit.
+    fn sees(&self, a: Cell, b: Cell) -> bool;
+
+    /// Every cell that `cell` sees.
+    fn peers(&self, cell: Cell) -> Vec<Cell> {
+        Cell::values()
+            .filter(|&other| self.sees(cell, other))
+            .collect()
+    }
+}
+
+/// Cells in a common row, column, or box.
+#[derive(Clone, Copy, Debug)]
+pub struct ClassicAdjacency;
+
+impl Adjacency for ClassicAdjacency {
+    fn sees(&self, a: Cell, b: Cell) -> bool {
+        a.sees_other(b)
+    }
+}
+
+/// Cells on a common long diagonal, for diagonal sudoku.
+#[derive(Clone, Copy, Debug)]
+pub struct DiagonalAdjacency;
+
+impl Adjacency for DiagonalAdjacency {
+    fn sees(&self, a: Cell, b: Cell) -> bool {
+        a != b
+            && ((a.on_main_diagonal() && b.on_main_diagonal())
+                || (a.on_anti_diagonal() && b.on_anti_diagonal()))
+    }
+}
+
+/// Cells a knight's move apart, for anti-knight sudoku.
+#[derive(Clone, Copy, Debug)]
+pub struct KnightAdjacency;
+
+impl Adjacency for KnightAdjacency {
+    fn sees(&self, a: Cell, b: Cell) -> bool {
+        a.knight_moves().any(|cell| cell == b)
+    }
+}
+
+/// Cells a king's move apart, for anti-king sudoku.
+#[derive(Clone, Copy, Debug)]
+pub struct KingAdjacency;
+
+impl Adjacency for KingAdjacency {
+    fn sees(&self, a: Cell, b: Cell) -> bool {
+        a.king_moves().any(|cell| cell == b)
+    }
+}
+
+/// Cells that see each other under any of several adjacencies, for combining variants.
+#[derive(Default)]
+pub struct AnyAdjacency(Vec<Box<dyn Adjacency>>);
+
+impl AnyAdjacency {
+    pub fn with(mut self, adjacency: impl Adjacency + 'static) -> Self {
+        self.0.push(Box::new(adjacency));
+        self
+    }
+}
+
+impl Adjacency for AnyAdjacency {
+    fn sees(&self, a: Cell, b: Cell) -> bool {
+        self.0.iter().any(|adjacency| adjacency.sees(a, b))
+    }
+}
+
+/// How `--dump_adjacency` lays out which cells see each other.
+#[derive(Clone, Copy, Debug)]
+pub enum AdjacencyFormat {
+    /// 81 lines of 81 `0`s and `1`s, with cells in row-major order.
+    Matrix,
+    /// One line per pair of cells that see each other, like `r1c1 r1c2`, each pair listed once.
+    Edges,
+}
+
+impl AdjacencyFormat {
+    pub fn parse(s: &str) -> Result<Self> {
+        match s {
+            "matrix" => Ok(AdjacencyFormat::Matrix),
+            "edges" => Ok(AdjacencyFormat::Edges),
+            x => Err(anyhow!(
+                "expected matrix or edges in --dump_adjacency {}",
+                x
+            )),
+        }
+    }
+}
+
+/// Renders `adjacency` over the whole board.
+pub fn format_adjacency(format: AdjacencyFormat, adjacency: &impl Adjacency) -> String {
+    let mut output = String::new();
+    match format {
+        AdjacencyFormat::Matrix => {
+            for a in Cell::values() {
+                for b in Cell::values() {
+                    output.push(if adjacency.sees(a, b) { '1' } else { '0' });
+                }
+                output.push('\n');
+            }
+        }
+        AdjacencyFormat::Edges => {
+            for a in Cell::values() {
+                for b in adjacency
+                    .peers(a)
+                    .into_iter()
+                    .filter(|b| b.index() > a.index())
+                {
+                    writeln!(&mut output, "{} {}", a, b).unwrap();
+                }
+            }
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        format_adjacency, Adjacency, AdjacencyFormat, AnyAdjacency, ClassicAdjacency,
+        DiagonalAdjacency, KingAdjacency, KnightAdjacency,
+    };
+    use crate::sudoku::Cell;
+
+    #[test]
+    fn every_cell_sees_twenty_others() {
+        let matrix = format_adjacency(AdjacencyFormat::Matrix, &ClassicAdjacency);
+        assert_eq!(81, matrix.lines().count());
+        for line in matrix.lines() {
+            assert_eq!(81, line.len());
+            assert_eq!(20, line.matches('1').count());
+        }
+
+        let edges = format_adjacency(AdjacencyFormat::Edges, &ClassicAdjacency);
+        assert_eq!(81 * 20 / 2, edges.lines().count());
+        assert_eq!(Some("r1c1 r1c2"), edges.lines().next());
+    }
+
+    #[test]
+    fn combined_adjacency_adds_diagonal_peers() {
+        let adjacency = AnyAdjacency::default()
+            .with(ClassicAdjacency)
+            .with(DiagonalAdjacency);
+        let cell = |name: &str| name.parse::<Cell>().unwrap();
+        assert_eq!(26, adjacency.peers(cell("r1c1")).len());
+        assert_eq!(32, adjacency.peers(cell("r5c5")).len());
+        assert_eq!(20, adjacency.peers(cell("r1c2")).len());
+        assert!(adjacency.sees(cell("r1c9"), cell("r9c1")));
+        assert!(!adjacency.sees(cell("r1c1"), cell("r1c1")));
+    }
+
+    #[test]
+    fn knight_adjacency_is_symmetric() {
+        for a in Cell::values() {
+            for b in Cell::values() {
+                assert_eq!(KnightAdjacency.sees(a, b), KnightAdjacency.sees(b, a));
+            }
+        }
+        let cell = |name: &str| name.parse::<Cell>().unwrap();
+        assert_eq!(8, KnightAdjacency.peers(cell("r5c5")).len());
+        assert_eq!(2, KnightAdjacency.peers(cell("r9c9")).len());
+    }
+
+    #[test]
+    fn king_adjacency_adds_diagonal_neighbors() {
+        let adjacency = AnyAdjacency::default()
+            .with(ClassicAdjacency)
+            .with(KingAdjacency);
+        let cell = |name: &str| name.parse::<Cell>().unwrap();
+        // Only the diagonal neighbors in other boxes are new.
+        assert_eq!(20, adjacency.peers(cell("r1c1")).len());
+        assert_eq!(21, adjacency.peers(cell("r1c3")).len());
+        assert_eq!(23, adjacency.peers(cell("r4c4")).len());
+        assert!(adjacency.sees(cell("r3c3"), cell("r4c4")));
+        assert!(!adjacency.sees(cell("r3c3"), cell("r5c5")));
+    }
+}
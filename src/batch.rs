@@ -0,0 +1,507 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use tokio::fs::{create_dir_all, read_dir, write};
+use tokio::io::{stdout, AsyncWrite, AsyncWriteExt};
+
+use crate::board_size::board_size;
+use crate::emit_problem::Parameters;
+use crate::formula_builder::Variable;
+use crate::puzzle_string::format_puzzle_string;
+use crate::regions::custom_regions;
+use crate::render_png::render_png;
+use crate::render_svg::render_svg;
+use crate::rule::Rule;
+use crate::sat_solver::Solution;
+use crate::solution_frames::{render_frames, solution_frames, warn_if_unsolved, Frame};
+use crate::solve::{solve_with_retries, SolveOptions};
+use crate::sudoku::VariableKind;
+use crate::symmetry::Symmetry;
+use crate::visualize_solution::{render_grid, render_oneline, render_tsv};
+use crate::OutputFormat;
+
+/// Where a batch of puzzles goes.
+pub enum BatchOutput<'a> {
+    /// Print every puzzle to stdout, one after another. Puzzles that span several lines are
+    /// separated by a blank line.
+    Stdout,
+    /// Write each puzzle to its own file in a directory, reporting progress on stdout.
+    Directory { path: &'a Path, overwrite: bool },
+}
+
+/// One puzzle of a `--format jsonl` batch, written as a single line of JSON.
+#[derive(Serialize)]
+struct PuzzleLine<'a> {
+    /// The puzzle's position in the batch, starting at 1. Each puzzle excludes every one before it,
+    /// so this and the parameters are enough to generate it again, as long as the solver honors
+    /// the seed and no attempt times out.
+    index: usize,
+    puzzle: String,
+    solution: String,
+    parameters: &'a ParameterLine,
+    frames: Vec<Frame>,
+}
+
+/// The resolved parameters of a batch, in a form that serializes to JSON. Everything that shapes the
+/// formula or the solver's search is here.
+#[derive(Serialize)]
+struct ParameterLine {
+    size: String,
+    /// The jigsaw layout, one region symbol per cell, or `None` for the classic boxes.
+    regions: Option<String>,
+    seed: Option<u64>,
+    distinct_solutions: bool,
+    givens: Option<usize>,
+    min_givens: Option<usize>,
+    max_givens: Option<usize>,
+    inference_levels: usize,
+    relax_last_level: bool,
+    no_at_least_one: bool,
+    sequential_at_most_one: bool,
+    dedup: bool,
+    max_chain_links: usize,
+    min_distinct_techniques: usize,
+    min_givens_per_digit: usize,
+    min_empty_cells: usize,
+    rules: Vec<Rule>,
+    fixed_givens: Option<String>,
+    propagate: bool,
+    pattern: String,
+    diagonals: bool,
+    anti_knight: bool,
+    anti_king: bool,
+    symmetry: Symmetry,
+    cages: Vec<String>,
+    sandwiches: Vec<String>,
+    thermos: Vec<String>,
+    kropki: Vec<String>,
+    kropki_negative: bool,
+    even: Vec<String>,
+    odd: Vec<String>,
+    base_only: bool,
+}
+
+impl ParameterLine {
+    fn new(params: &Parameters, seed: Option<u64>, distinct_solutions: bool) -> Self {
+        ParameterLine {
+            size: board_size().to_string(),
+            regions: custom_regions().map(ToString::to_string),
+            seed,
+            distinct_solutions,
+            givens: params.givens,
+            min_givens: params.min_givens,
+            max_givens: params.max_givens,
+            inference_levels: params.inference_levels,
+            relax_last_level: params.relax_last_level,
+            no_at_least_one: params.no_at_least_one,
+            sequential_at_most_one: params.sequential_at_most_one,
+            dedup: params.dedup_clauses,
+            max_chain_links: params.max_chain_links,
+            min_distinct_techniques: params.min_distinct_techniques,
+            min_givens_per_digit: params.min_givens_per_digit,
+            min_empty_cells: params.min_empty_cells,
+            rules: params.allowed_inferences.enabled_rules(),
+            fixed_givens: params.fixed_givens.as_ref().map(format_puzzle_string),
+            propagate: params.propagate,
+            pattern: format_puzzle_string(&params.pattern),
+            diagonals: params.diagonals,
+            anti_knight: params.anti_knight,
+            anti_king: params.anti_king,
+            symmetry: params.symmetry,
+            cages: params
+                .cages
+                .iter()
+                .map(|cage| {
+                    let cells: Vec<_> = cage.cells.iter().map(ToString::to_string).collect();
+                    match cage.sum {
+                        Some(sum) => format!("{}={}", cells.join("+"), sum),
+                        None => cells.join("+"),
+                    }
+                })
+                .collect(),
+            sandwiches: params.sandwiches.iter().map(ToString::to_string).collect(),
+            thermos: params.thermos.iter().map(ToString::to_string).collect(),
+            kropki: params.dots.iter().map(ToString::to_string).collect(),
+            kropki_negative: params.kropki_negative,
+            even: params.even_cells.iter().map(ToString::to_string).collect(),
+            odd: params.odd_cells.iter().map(ToString::to_string).collect(),
+            base_only: params.base_only,
+        }
+    }
+}
+
+/// Generates `count` distinct puzzles. Each solve excludes every puzzle found before it, so the
+/// batch stops early with an error if the parameters admit fewer than `count` puzzles. With
+/// `distinct_solutions`, each solve also excludes every solution grid found before it, and puzzles
+/// whose solution grid is equivalent under sudoku symmetry to an earlier one are skipped.
+pub async fn generate_batch(
+    params: &Parameters,
+    count: usize,
+    format: OutputFormat,
+    output: BatchOutput<'_>,
+    options: SolveOptions,
+    distinct_solutions: bool,
+) -> Result<()> {
+    run_batch(
+        params,
+        count,
+        format,
+        output,
+        ParameterLine::new(params, options.seed, distinct_solutions),
+        &mut stdout(),
+        |params| async move {
+            let (variables, solution, _, emission_stats) =
+                solve_with_retries(&params, options).await?;
+            if params.dedup_clauses {
+                eprintln!(
+                    "Removed {} duplicate clauses.",
+                    emission_stats.duplicates_removed
+                );
+            }
+            Ok((variables, solution))
+        },
+    )
+    .await
+}
+
+/// Runs the batch loop of `generate_batch`, getting each puzzle from `solve` and writing stdout
+/// output to `out`. The batch's `parameters` go into JSON output and say whether to skip
+/// equivalent solutions.
+async fn run_batch<W, S, F>(
+    params: &Parameters,
+    count: usize,
+    format: OutputFormat,
+    output: BatchOutput<'_>,
+    parameters: ParameterLine,
+    out: &mut W,
+    mut solve: S,
+) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    S: FnMut(Parameters) -> F,
+    F: Future<Output = Result<(HashMap<VariableKind, Variable>, Solution)>>,
+{
+    if let BatchOutput::Directory { path, overwrite } = output {
+        prepare_directory(path, overwrite).await?;
+    }
+
+    // Pad file names to at least three digits, and more if the batch needs them.
+    let width = count.to_string().len().max(3);
+    let mut params = params.clone();
+    let mut solution_hashes = HashSet::new();
+    let mut index = 1;
+    while index <= count {
+        let (variables, solution) = solve(params.clone()).await?;
+        let assignments = match &solution {
+            Solution::Satisfiable { assignments } => assignments,
+            Solution::Unsatisfiable => {
+                return Err(anyhow!(
+                    "found only {} distinct puzzles, but {} were requested",
+                    index - 1,
+                    count,
+                ))
+            }
+            Solution::Unknown => unreachable!("retries never return an unknown result"),
+        };
+        if params.relax_last_level {
+            warn_if_unsolved(&variables, assignments);
+        }
+        let grid = solution.to_grid(&variables)?;
+        let mask = solution.givens_mask(&variables)?;
+        params.excluded_puzzles.push(grid.givens(&mask));
+        if parameters.distinct_solutions {
+            // Other givens for the same grid would only be skipped in turn, so rule the grid out.
+            // Its symmetric equivalents are too many to exclude, so they're caught by hash instead.
+            params.excluded_solutions.push(grid.placements());
+            if !solution_hashes.insert(grid.canonical_hash()) {
+                continue;
+            }
+        }
+
+        let rendered = match format {
+            OutputFormat::Grid => render_grid(&grid, &mask, false).into_bytes(),
+            OutputFormat::Frames => (render_frames(&variables, assignments)? + "\n").into_bytes(),
+            OutputFormat::Tsv { solution } => render_tsv(&grid, &mask, solution).into_bytes(),
+            OutputFormat::Oneline { solution } => {
+                (render_oneline(&grid, &mask, solution) + "\n").into_bytes()
+            }
+            OutputFormat::Svg { solution } => render_svg(&grid, &mask, solution).into_bytes(),
+            OutputFormat::Png { solution, cell_px } => render_png(&grid, &mask, solution, cell_px)?,
+            OutputFormat::Jsonl => {
+                let line = PuzzleLine {
+                    index,
+                    puzzle: grid.to_puzzle_string(&mask),
+                    solution: grid.to_solution_string(),
+                    parameters: &parameters,
+                    frames: solution_frames(&variables, assignments),
+                };
+                (serde_json::to_string(&line)? + "\n").into_bytes()
+            }
+            OutputFormat::Opb | OutputFormat::FormulaStats => {
+                unreachable!("OPB and formula_stats output don't solve")
+            }
+        };
+
+        match output {
+            BatchOutput::Stdout => {
+                if index > 1 && is_multiline(format) {
+                    out.write_all(b"\n").await?;
+                }
+                // Flush every puzzle so a consumer can start on it before the batch finishes.
+                out.write_all(&rendered).await?;
+                out.flush().await?;
+            }
+            BatchOutput::Directory { path, .. } => {
+                let file_path = puzzle_path(path, index, width, format);
+                write(&file_path, rendered)
+                    .await
+                    .with_context(|| format!("Failed to write {}", file_path.display()))?;
+                println!("Wrote {} ({} of {})", file_path.display(), index, count);
+            }
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Whether a puzzle in `format` spans several lines, so that puzzles printed one after another
+/// need a blank line between them to tell them apart.
+fn is_multiline(format: OutputFormat) -> bool {
+    matches!(
+        format,
+        OutputFormat::Grid | OutputFormat::Tsv { .. } | OutputFormat::Svg { .. }
+    )
+}
+
+/// Creates the output directory if needed. Refuses to write into a directory that already has
+/// entries unless `overwrite` is set.
+async fn prepare_directory(path: &Path, overwrite: bool) -> Result<()> {
+    create_dir_all(path)
+        .await
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    if !overwrite && read_dir(path).await?.next_entry().await?.is_some() {
+        return Err(anyhow!(
+            "{} is not empty; pass --overwrite to write into it anyway",
+            path.display(),
+        ));
+    }
+    Ok(())
+}
+
+fn puzzle_path(directory: &Path, index: usize, width: usize, format: OutputFormat) -> PathBuf {
+    let extension = match format {
+        OutputFormat::Grid => "txt",
+        OutputFormat::Frames => "json",
+        OutputFormat::Tsv { .. } => "tsv",
+        OutputFormat::Oneline { .. } => "txt",
+        OutputFormat::Svg { .. } => "svg",
+        OutputFormat::Png { .. } => "png",
+        OutputFormat::Jsonl => "jsonl",
+        OutputFormat::Opb | OutputFormat::FormulaStats => {
+            unreachable!("OPB and formula_stats output don't solve")
+        }
+    };
+    directory.join(format!(
+        "puzzle_{:0width$}.{}",
+        index,
+        extension,
+        width = width
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+    use std::path::Path;
+
+    use super::{puzzle_path, run_batch, BatchOutput, ParameterLine};
+    use crate::emit_problem::Parameters;
+    use crate::formula_builder::{TaggedVariableFormulaBuilder, Variable};
+    use crate::puzzle_string::parse_puzzle_string;
+    use crate::sat_solver::Solution;
+    use crate::sudoku::{Cell, Digit, VariableKind};
+    use crate::OutputFormat;
+
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    /// A valid grid that isn't equivalent to `SOLUTION`.
+    const OTHER_SOLUTION: &str =
+        "123456789456789123789123456214365897365897214897214365531642978642978531978531642";
+
+    /// A stand-in for the solver that knows `puzzles` puzzles for each of the solution `grids`, and
+    /// returns the first one that `params` doesn't exclude.
+    fn fake_solve(
+        params: &Parameters,
+        grids: &[&str],
+        puzzles: usize,
+    ) -> (HashMap<VariableKind, Variable>, Solution) {
+        let mut formula = TaggedVariableFormulaBuilder::<VariableKind>::default();
+        for cell in Cell::values() {
+            formula.get_variable(VariableKind::Given {
+                row: cell.row,
+                col: cell.col,
+            });
+            for digit in Digit::values() {
+                formula.get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                });
+            }
+        }
+        let variables = formula.into_tagged_variables();
+
+        // Puzzle `k` leaves empty the cells whose index is `k` modulo `puzzles`.
+        let is_given = |k: usize, cell: Cell| cell.index() % puzzles != k;
+        let found = grids
+            .iter()
+            .map(|grid| parse_puzzle_string(grid).unwrap())
+            .filter(|digits| !params.excluded_solutions.contains(digits))
+            .find_map(|digits| {
+                let k = (0..puzzles).find(|&k| {
+                    let givens: HashMap<_, _> = Cell::values()
+                        .filter(|&cell| is_given(k, cell))
+                        .map(|cell| (cell, digits[&cell]))
+                        .collect();
+                    !params.excluded_puzzles.contains(&givens)
+                })?;
+                Some((digits, k))
+            });
+        let (digits, k) = match found {
+            Some(found) => found,
+            None => return (variables, Solution::Unsatisfiable),
+        };
+
+        let mut assignments = HashMap::new();
+        for cell in Cell::values() {
+            let given = VariableKind::Given {
+                row: cell.row,
+                col: cell.col,
+            };
+            assignments.insert(variables[&given], is_given(k, cell));
+            for digit in Digit::values() {
+                let placed = VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                };
+                assignments.insert(variables[&placed], digits[&cell] == digit);
+            }
+        }
+        (variables, Solution::Satisfiable { assignments })
+    }
+
+    async fn batch(count: usize, format: OutputFormat) -> anyhow::Result<String> {
+        let mut out = Vec::new();
+        run_batch(
+            &Parameters::default(),
+            count,
+            format,
+            BatchOutput::Stdout,
+            ParameterLine::new(&Parameters::default(), None, false),
+            &mut out,
+            |params| async move { Ok(fake_solve(&params, &[SOLUTION], 5)) },
+        )
+        .await?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[tokio::test]
+    async fn batch_emits_the_requested_count_of_distinct_puzzles() {
+        let output = batch(4, OutputFormat::Oneline { solution: false })
+            .await
+            .unwrap();
+        let puzzles: Vec<_> = output.lines().collect();
+        assert_eq!(4, puzzles.len());
+        assert_eq!(4, puzzles.iter().collect::<HashSet<_>>().len());
+
+        // Multi-line puzzles are separated by one blank line each.
+        let output = batch(3, OutputFormat::Grid).await.unwrap();
+        let puzzles: Vec<_> = output.split("\n\n").collect();
+        assert_eq!(3, puzzles.len());
+        assert_eq!(3, puzzles.iter().collect::<HashSet<_>>().len());
+
+        let error = batch(6, OutputFormat::Oneline { solution: false })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "found only 5 distinct puzzles, but 6 were requested",
+            error.to_string(),
+        );
+    }
+
+    #[tokio::test]
+    async fn distinct_solutions_skip_equivalent_grids_without_revisiting_them() {
+        // Swapping two digits gives an equivalent grid.
+        let relabeled: String = SOLUTION
+            .chars()
+            .map(|c| match c {
+                '1' => '2',
+                '2' => '1',
+                c => c,
+            })
+            .collect();
+        let grids = [SOLUTION, relabeled.as_str(), OTHER_SOLUTION];
+
+        let mut out = Vec::new();
+        let mut solves = 0;
+        let params = Parameters::default();
+        run_batch(
+            &params,
+            2,
+            OutputFormat::Oneline { solution: true },
+            BatchOutput::Stdout,
+            ParameterLine::new(&params, None, true),
+            &mut out,
+            |params| {
+                solves += 1;
+                let result = fake_solve(&params, &grids, 3);
+                async move { Ok(result) }
+            },
+        )
+        .await
+        .unwrap();
+
+        // The relabeled grid is skipped once, and no other puzzle for a seen grid is tried.
+        assert_eq!(3, solves);
+        let output = String::from_utf8(out).unwrap();
+        assert_eq!(
+            vec![SOLUTION, OTHER_SOLUTION],
+            output.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn jsonl_lines_carry_the_global_settings() {
+        let output = batch(1, OutputFormat::Jsonl).await.unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        let parameters = &line["parameters"];
+        assert_eq!("9", parameters["size"]);
+        assert!(parameters["regions"].is_null());
+        assert!(parameters["seed"].is_null());
+        assert!(parameters["fixed_givens"].is_null());
+        assert_eq!(false, parameters["sequential_at_most_one"]);
+    }
+
+    #[test]
+    fn puzzle_paths_are_zero_padded() {
+        let directory = Path::new("out");
+        assert_eq!(
+            Path::new("out/puzzle_007.txt"),
+            puzzle_path(directory, 7, 3, OutputFormat::Grid)
+        );
+        assert_eq!(
+            Path::new("out/puzzle_0042.json"),
+            puzzle_path(directory, 42, 4, OutputFormat::Frames)
+        );
+        assert_eq!(
+            Path::new("out/puzzle_001.jsonl"),
+            puzzle_path(directory, 1, 3, OutputFormat::Jsonl)
+        );
+    }
+}
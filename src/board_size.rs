@@ -0,0 +1,188 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use thiserror::Error;
+
+/// The dimensions of the board. Boxes are `box_height` rows by `box_width` columns, and the board
+/// is `box_height` boxes across and `box_width` boxes down, so each row, column, and box holds
+/// `box_height * box_width` cells and every digit up to that.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoardSize {
+    box_height: u8,
+    box_width: u8,
+}
+
+impl BoardSize {
+    /// The classic 9x9 board of 3x3 boxes.
+    pub const CLASSIC: BoardSize = BoardSize::new(3, 3);
+
+    /// Hexadoku: a 16x16 board of 4x4 boxes.
+    pub const HEXADOKU: BoardSize = BoardSize::new(4, 4);
+
+    /// Every supported size, from smallest to largest.
+    pub const ALL: [BoardSize; 4] = [
+        BoardSize::new(2, 2),
+        BoardSize::new(2, 3),
+        BoardSize::CLASSIC,
+        BoardSize::HEXADOKU,
+    ];
+
+    pub const fn new(box_height: u8, box_width: u8) -> Self {
+        BoardSize {
+            box_height,
+            box_width,
+        }
+    }
+
+    pub const fn box_height(self) -> u8 {
+        self.box_height
+    }
+
+    pub const fn box_width(self) -> u8 {
+        self.box_width
+    }
+
+    /// How many cells are in each row, column, and box, which is also the highest digit.
+    pub const fn side(self) -> u8 {
+        self.box_height * self.box_width
+    }
+
+    pub const fn cell_count(self) -> usize {
+        self.side() as usize * self.side() as usize
+    }
+
+    /// No puzzle of this size with fewer givens than this has a unique solution. The true minimum
+    /// for hexadoku is unknown, so it falls back on the digits other than one needing a given each.
+    pub const fn min_unique_givens(self) -> usize {
+        match self.side() {
+            4 => 4,
+            6 => 8,
+            9 => 17,
+            side => side as usize - 1,
+        }
+    }
+
+    /// Whether digits are written as one hexadecimal character each, `0` through `F` for 1 through
+    /// 16, because the board has more digits than `1` through `9` can show.
+    pub const fn uses_hex_symbols(self) -> bool {
+        self.side() > 9
+    }
+}
+
+impl Default for BoardSize {
+    fn default() -> Self {
+        BoardSize::CLASSIC
+    }
+}
+
+/// Formats a size by its side length, like `6`, as `--size` takes it.
+impl Display for BoardSize {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.side())
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("expected a board size of 4, 6, 9, or 16, but found {0:?}")]
+pub struct UnknownSizeError(String);
+
+/// Parses a size by its side length, like `6`, or as `6x6`.
+impl FromStr for BoardSize {
+    type Err = UnknownSizeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let side = match s.split_once('x') {
+            Some((rows, cols)) if rows == cols => rows,
+            Some(_) => return Err(UnknownSizeError(s.to_string())),
+            None => s,
+        };
+        BoardSize::ALL
+            .iter()
+            .copied()
+            .find(|size| size.side().to_string() == side)
+            .ok_or_else(|| UnknownSizeError(s.to_string()))
+    }
+}
+
+static BOARD_SIZE: OnceLock<BoardSize> = OnceLock::new();
+
+#[cfg(test)]
+thread_local! {
+    static TEST_BOARD_SIZE: std::cell::Cell<Option<BoardSize>> = const { std::cell::Cell::new(None) };
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("the board size is already {current}, so it can't be changed to {requested}")]
+pub struct BoardSizeAlreadySet {
+    pub current: BoardSize,
+    pub requested: BoardSize,
+}
+
+/// Chooses the board size in place of the classic 9x9. The size is fixed once it's first chosen or
+/// looked up, so asking for a different size after that fails; asking for the same one again is
+/// fine.
+pub fn set_board_size(size: BoardSize) -> Result<(), BoardSizeAlreadySet> {
+    let current = *BOARD_SIZE.get_or_init(|| size);
+    if current == size {
+        Ok(())
+    } else {
+        Err(BoardSizeAlreadySet {
+            current,
+            requested: size,
+        })
+    }
+}
+
+/// The board size that every row, column, digit, and box consults.
+pub fn board_size() -> BoardSize {
+    #[cfg(test)]
+    if let Some(size) = TEST_BOARD_SIZE.with(|size| size.get()) {
+        return size;
+    }
+    *BOARD_SIZE.get_or_init(BoardSize::default)
+}
+
+/// Runs `f` with `size` in effect on this thread only, so tests of other sizes can run alongside
+/// the classic ones.
+#[cfg(test)]
+pub(crate) fn with_board_size<R>(size: BoardSize, f: impl FnOnce() -> R) -> R {
+    let previous = TEST_BOARD_SIZE.with(|current| current.replace(Some(size)));
+    let result = f();
+    TEST_BOARD_SIZE.with(|current| current.set(previous));
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{set_board_size, BoardSize, BoardSizeAlreadySet};
+
+    #[test]
+    fn parses_sizes() {
+        assert_eq!(Ok(BoardSize::new(2, 2)), "4".parse());
+        assert_eq!(Ok(BoardSize::new(2, 3)), "6x6".parse());
+        assert_eq!(Ok(BoardSize::CLASSIC), "9".parse());
+        assert_eq!(Ok(BoardSize::HEXADOKU), "16x16".parse());
+        assert!("8".parse::<BoardSize>().is_err());
+        assert!("6x4".parse::<BoardSize>().is_err());
+        for size in BoardSize::ALL {
+            assert_eq!(Ok(size), size.to_string().parse());
+            assert_eq!(0, size.side() % size.box_width());
+        }
+    }
+
+    #[test]
+    fn a_different_size_cant_replace_the_one_in_effect() {
+        // Every other test either overrides the size per thread or uses the classic size, so fixing
+        // the process-wide size to the classic one is harmless.
+        assert_eq!(Ok(()), set_board_size(BoardSize::CLASSIC));
+        assert_eq!(Ok(()), set_board_size(BoardSize::CLASSIC));
+        assert_eq!(
+            Err(BoardSizeAlreadySet {
+                current: BoardSize::CLASSIC,
+                requested: BoardSize::new(2, 3),
+            }),
+            set_board_size(BoardSize::new(2, 3))
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+
+use thiserror::Error;
+
+use crate::board_size::board_size;
+use crate::sudoku::{Cell, CellNameError};
+
+/// A killer cage: a group of cells that must all hold different digits and, if `sum` is set, must
+/// add up to it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cage {
+    pub cells: Vec<Cell>,
+    pub sum: Option<u32>,
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum CageError {
+    #[error("in cage {cage:?}: {source}")]
+    BadCell { cage: String, source: CellNameError },
+
+    #[error("in cage {0:?}: expected a sum like =10")]
+    BadSum(String),
+
+    #[error("cage {cage:?} has {cells} cells, but a cage can hold at most {max}")]
+    TooManyCells {
+        cage: String,
+        cells: usize,
+        max: usize,
+    },
+
+    #[error("{0} appears more than once among the cages")]
+    RepeatedCell(Cell),
+
+    #[error(
+        "cage {cage:?} can't add up to {sum}; {cells} different digits sum to {min} through {max}"
+    )]
+    ImpossibleSum {
+        cage: String,
+        sum: u32,
+        cells: usize,
+        min: u32,
+        max: u32,
+    },
+}
+
+/// Parses a semicolon-separated list of cages, each a `+`-separated list of cells with an optional
+/// `=sum`, like `r1c1+r1c2=10;r5c5+r5c6+r6c5`. Cages may not overlap, hold more than nine cells, or
+/// have a sum that their number of distinct digits can't reach.
+pub fn parse_cages(s: &str) -> Result<Vec<Cage>, CageError> {
+    let mut seen = HashSet::new();
+    let mut cages = Vec::new();
+    for text in s.split(';').map(str::trim).filter(|text| !text.is_empty()) {
+        let (cells_text, sum) = match text.split_once('=') {
+            Some((cells_text, sum)) => (
+                cells_text,
+                Some(
+                    sum.trim()
+                        .parse::<u32>()
+                        .map_err(|_| CageError::BadSum(text.to_string()))?,
+                ),
+            ),
+            None => (text, None),
+        };
+
+        let mut cells = Vec::new();
+        for name in cells_text.split('+').map(str::trim) {
+            let cell = name.parse::<Cell>().map_err(|source| CageError::BadCell {
+                cage: text.to_string(),
+                source,
+            })?;
+            if !seen.insert(cell) {
+                return Err(CageError::RepeatedCell(cell));
+            }
+            cells.push(cell);
+        }
+        let side = board_size().side() as u32;
+        if cells.len() > side as usize {
+            return Err(CageError::TooManyCells {
+                cage: text.to_string(),
+                cells: cells.len(),
+                max: side as usize,
+            });
+        }
+
+        if let Some(sum) = sum {
+            // The smallest and largest totals come from the lowest and highest distinct digits.
+            let n = cells.len() as u32;
+            let min = n * (n + 1) / 2;
+            let max = side * (side + 1) / 2 - (side - n) * (side + 1 - n) / 2;
+            if sum < min || sum > max {
+                return Err(CageError::ImpossibleSum {
+                    cage: text.to_string(),
+                    sum,
+                    cells: cells.len(),
+                    min,
+                    max,
+                });
+            }
+        }
+
+        cages.push(Cage { cells, sum });
+    }
+    Ok(cages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_cages, CageError};
+
+    #[test]
+    fn parses_cages_with_and_without_sums() {
+        let cages = parse_cages("r1c1+r1c2=10; r5c5 + r5c6 + r6c5").unwrap();
+        assert_eq!(2, cages.len());
+        assert_eq!(Some(10), cages[0].sum);
+        assert_eq!(3, cages[1].cells.len());
+        assert_eq!(None, cages[1].sum);
+    }
+
+    #[test]
+    fn rejects_invalid_cages() {
+        assert!(matches!(
+            parse_cages("r1c1+r1c2;r1c2+r1c3"),
+            Err(CageError::RepeatedCell(_))
+        ));
+        assert!(matches!(
+            parse_cages("r1c1+r1c2+r1c3+r1c4+r1c5+r1c6+r1c7+r1c8+r1c9+r2c1"),
+            Err(CageError::TooManyCells { cells: 10, .. })
+        ));
+        assert!(matches!(
+            parse_cages("r1c1+r1c2=18"),
+            Err(CageError::ImpossibleSum { max: 17, .. })
+        ));
+        assert!(matches!(
+            parse_cages("r1c1+r1c2=x"),
+            Err(CageError::BadSum(_))
+        ));
+        assert!(matches!(
+            parse_cages("r1c1+x"),
+            Err(CageError::BadCell { .. })
+        ));
+    }
+}
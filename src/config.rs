@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use tokio::fs::read_to_string;
+
+use crate::rule::Rule;
+
+/// Parameters loaded from a `--config` TOML file. Keys match the command-line flags, and anything
+/// left out falls back to the flag's default. Flags given on the command line override the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub givens: Option<usize>,
+    pub no_givens_count: Option<bool>,
+    pub min_givens: Option<usize>,
+    pub max_givens: Option<usize>,
+    pub minimize_givens: Option<bool>,
+    pub max_inference_levels: Option<usize>,
+    pub relax_last_level: Option<bool>,
+    pub max_clause_count: Option<usize>,
+    pub sequential_at_most_one: Option<bool>,
+    pub dedup: Option<bool>,
+    pub base_only: Option<bool>,
+    pub max_chain_links: Option<usize>,
+    pub min_distinct_techniques: Option<usize>,
+    pub min_givens_per_digit: Option<usize>,
+    pub min_empty_cells: Option<usize>,
+    pub assume_unique: Option<bool>,
+    pub propagate: Option<bool>,
+    pub pattern: Option<String>,
+    pub diagonals: Option<bool>,
+    pub anti_knight: Option<bool>,
+    pub anti_king: Option<bool>,
+    pub symmetry: Option<String>,
+    pub size: Option<String>,
+    pub regions: Option<String>,
+    pub cages: Option<String>,
+    pub sandwiches: Option<String>,
+    pub thermos: Option<String>,
+    pub kropki: Option<String>,
+    pub kropki_negative: Option<bool>,
+    pub even: Option<String>,
+    pub odd: Option<String>,
+    /// The `[rules]` table, with one key per inference rule flag.
+    #[serde(default)]
+    pub rules: BTreeMap<Rule, bool>,
+}
+
+impl Config {
+    pub fn parse(s: &str) -> Result<Self> {
+        let config: Config = toml::from_str(s)?;
+        if config.givens.is_some() && config.no_givens_count == Some(true) {
+            return Err(anyhow!("givens and no_givens_count cannot both be set"));
+        }
+        if config.givens.is_some() && config.minimize_givens == Some(true) {
+            return Err(anyhow!("givens and minimize_givens cannot both be set"));
+        }
+        if config.givens.is_some() && (config.min_givens.is_some() || config.max_givens.is_some()) {
+            return Err(anyhow!(
+                "givens cannot be set along with min_givens or max_givens"
+            ));
+        }
+        Ok(config)
+    }
+}
+
+pub async fn load_config(path: &Path) -> Result<Config> {
+    let contents = read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    Config::parse(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::rule::Rule;
+
+    #[test]
+    fn parses_flags_and_rules() {
+        let config = Config::parse(
+            "givens = 28\n\
+             pattern = \"r5c5=1\"\n\
+             [rules]\n\
+             w_wing = true\n",
+        )
+        .unwrap();
+        assert_eq!(Some(28), config.givens);
+        assert_eq!(Some("r5c5=1"), config.pattern.as_deref());
+        assert_eq!(Some(&true), config.rules.get(&Rule::WWing));
+        assert_eq!(None, config.rules.get(&Rule::Jellyfish));
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        assert!(Config::parse("givns = 28\n").is_err());
+        assert!(Config::parse("[rules]\ny_wing = true\n").is_err());
+        assert!(Config::parse("givens = 28\nno_givens_count = true\n").is_err());
+        assert!(Config::parse("givens = 28\nminimize_givens = true\n").is_err());
+    }
+}
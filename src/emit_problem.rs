@@ -1,119 +1,1378 @@
-use std::collections::HashMap;
+#[cfg(test)]
+use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap};
+use std::iter::once;
+use std::sync::{Mutex, OnceLock};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde::Serialize;
 use tokio::io::AsyncWrite;
 
+use crate::adjacency::{
+    Adjacency, AnyAdjacency, ClassicAdjacency, DiagonalAdjacency, KingAdjacency, KnightAdjacency,
+};
+use crate::board_size::{board_size, BoardSize};
+use crate::cages::Cage;
 use crate::formula_builder::{
-    BitVector, CardinalityFormulaBuilder, FormulaBuilder, GateFormulaBuilder,
+    BitVector, CardinalityFormulaBuilder, FormulaBuilder, GateFormulaBuilder, Literal, Relation,
     TaggedVariableFormulaBuilder, Variable,
 };
-use crate::sudoku::{Box, Cell, Col, Digit, Row, VariableKind};
+use crate::kropki::{orthogonally_adjacent, Dot, DotColor};
+use crate::parity::Parity;
+use crate::propagate::CandidateGrid;
+use crate::rule::Rule;
+use crate::sandwich::Sandwich;
+use crate::sudoku::{Cell, Col, Digit, DigitSet, House, Row, VariableKind};
+use crate::symmetry::Symmetry;
+use crate::thermo::Thermo;
+
+use chains::emit_remote_pairs;
+use fish::emit_fish;
+use intersections::{emit_claiming, emit_pointing};
+use naked_subsets::emit_naked_subsets;
+use wings::{emit_w_wing, emit_xy_wing};
+
+mod chains;
+mod fish;
+mod intersections;
+mod naked_subsets;
+mod wings;
 
+type Formula = TaggedVariableFormulaBuilder<VariableKind>;
+
+/// Justifications for eliminating placements, keyed by the cell and digit they eliminate.
+type Eliminations = BTreeMap<(Cell, Digit), Vec<Literal>>;
+
+#[derive(Clone, Debug)]
 pub struct Parameters {
-    pub givens: usize,
+    /// Require exactly this many givens, or let the solver choose any number if `None`.
+    pub givens: Option<usize>,
+    /// When `givens` is `None`, require at least this many givens.
+    pub min_givens: Option<usize>,
+    /// When `givens` is `None`, require at most this many givens.
+    pub max_givens: Option<usize>,
     pub inference_levels: usize,
+    /// Let the last inference level fall short of solving the board instead of requiring it to,
+    /// so that running out of levels shows up as an unfinished solve rather than UNSAT.
+    pub relax_last_level: bool,
+    /// Diagnostic only: leave out the at-least-one halves of the rules of sudoku, so cells and
+    /// houses may go without a digit. The resulting "puzzles" are not valid sudoku.
+    pub no_at_least_one: bool,
+    /// Abandon the formula with an error once it has more than this many clauses.
+    pub max_clause_count: Option<usize>,
+    /// Encode the rules of sudoku's at-most-one constraints with a linear ladder of auxiliary
+    /// variables instead of pairwise clauses, for a smaller formula.
+    pub sequential_at_most_one: bool,
+    /// Drop clauses that repeat an earlier one before writing the formula.
+    pub dedup_clauses: bool,
+    pub max_chain_links: usize,
+    pub min_distinct_techniques: usize,
+    /// Require every digit to appear at least this many times among the givens.
+    pub min_givens_per_digit: usize,
+    /// Require at least this many cells to be left empty rather than given.
+    pub min_empty_cells: usize,
     pub allowed_inferences: Inferences,
+    /// Pin the givens to exactly these placements instead of letting the solver choose them.
+    pub fixed_givens: Option<HashMap<Cell, Digit>>,
+    /// Whether to propagate singles from `fixed_givens` before emitting the formula.
+    pub propagate: bool,
+    /// Placements the solution must contain, whether or not they are givens.
+    pub pattern: HashMap<Cell, Digit>,
+    /// Also require each digit once on both long diagonals, as in diagonal (X) sudoku.
+    pub diagonals: bool,
+    /// Also forbid cells a knight's move apart from sharing a digit, as in anti-knight sudoku.
+    pub anti_knight: bool,
+    /// Also forbid cells a king's move apart from sharing a digit, as in anti-king sudoku.
+    pub anti_king: bool,
+    /// A symmetry the pattern of givens must have.
+    pub symmetry: Symmetry,
+    /// Killer cages the solution must respect.
+    pub cages: Vec<Cage>,
+    /// Sandwich clues the solution must respect.
+    pub sandwiches: Vec<Sandwich>,
+    /// Thermometers the solution must respect.
+    pub thermos: Vec<Thermo>,
+    /// Kropki dots the solution must respect.
+    pub dots: Vec<Dot>,
+    /// Forbid consecutive digits and digits in a 1:2 ratio across edges without a Kropki dot.
+    pub kropki_negative: bool,
+    /// Cells the solution must fill with even digits.
+    pub even_cells: Vec<Cell>,
+    /// Cells the solution must fill with odd digits.
+    pub odd_cells: Vec<Cell>,
+    /// Puzzles, as their given placements, that must not be generated again.
+    pub excluded_puzzles: Vec<HashMap<Cell, Digit>>,
+    /// Solution grids, as every cell's placement, that must not be generated again with any givens.
+    pub excluded_solutions: Vec<HashMap<Cell, Digit>>,
+    /// Leave out the inference circuit and emit only the rules of sudoku and the constraints on
+    /// the givens, which is all it takes to check that `fixed_givens` can be solved at all. The
+    /// inference rules and levels are ignored.
+    pub base_only: bool,
 }
 
+/// The command line's defaults: 40 givens, 25 inference levels, and only singles allowed.
+impl Default for Parameters {
+    fn default() -> Self {
+        Parameters {
+            givens: Some(40),
+            min_givens: None,
+            max_givens: None,
+            inference_levels: 25,
+            relax_last_level: false,
+            no_at_least_one: false,
+            max_clause_count: None,
+            sequential_at_most_one: false,
+            dedup_clauses: false,
+            max_chain_links: 4,
+            min_distinct_techniques: 0,
+            min_givens_per_digit: 0,
+            min_empty_cells: 0,
+            allowed_inferences: Inferences::default(),
+            fixed_givens: None,
+            propagate: true,
+            pattern: HashMap::new(),
+            diagonals: false,
+            anti_knight: false,
+            anti_king: false,
+            symmetry: Symmetry::None,
+            cages: Vec::new(),
+            sandwiches: Vec::new(),
+            thermos: Vec::new(),
+            dots: Vec::new(),
+            kropki_negative: false,
+            even_cells: Vec::new(),
+            odd_cells: Vec::new(),
+            excluded_puzzles: Vec::new(),
+            excluded_solutions: Vec::new(),
+            base_only: false,
+        }
+    }
+}
+
+impl Parameters {
+    /// Which cells can't share a digit, given the active variants.
+    pub fn adjacency(&self) -> AnyAdjacency {
+        let mut adjacency = AnyAdjacency::default().with(ClassicAdjacency);
+        if self.diagonals {
+            adjacency = adjacency.with(DiagonalAdjacency);
+        }
+        if self.anti_knight {
+            adjacency = adjacency.with(KnightAdjacency);
+        }
+        if self.anti_king {
+            adjacency = adjacency.with(KingAdjacency);
+        }
+        adjacency
+    }
+
+    /// Checks that the given counts fit on the board and leave room for the per-digit and
+    /// empty-cell minimums.
+    pub fn check_given_counts(&self) -> Result<()> {
+        let size = board_size();
+        let cell_count = size.cell_count();
+        if let (Some(min), Some(max)) = (self.min_givens, self.max_givens) {
+            if min > max {
+                return Err(anyhow!(
+                    "--min_givens is {}, which is more than --max_givens {}",
+                    min,
+                    max
+                ));
+            }
+        }
+        for (flag, count) in [
+            ("givens", self.givens),
+            ("min_givens", self.min_givens),
+            ("max_givens", self.max_givens),
+        ] {
+            if count.is_some_and(|count| count > cell_count) {
+                return Err(anyhow!("--{} can be at most {}", flag, cell_count));
+            }
+        }
+        let max_givens_per_digit = self
+            .givens
+            .or(self.max_givens)
+            .unwrap_or(cell_count)
+            .min(cell_count)
+            / size.side() as usize;
+        if self.min_givens_per_digit > max_givens_per_digit {
+            return Err(anyhow!(
+                "--min_givens_per_digit is {}, but the givens leave room for at most {} of each digit",
+                self.min_givens_per_digit,
+                max_givens_per_digit,
+            ));
+        }
+        let max_empty_cells = cell_count.saturating_sub(
+            self.givens
+                .or(self.min_givens)
+                .unwrap_or(size.min_unique_givens())
+                .max(size.min_unique_givens()),
+        );
+        if self.min_empty_cells > max_empty_cells {
+            return Err(anyhow!(
+                "--min_empty_cells is {}, but a uniquely solvable puzzle with these givens leaves at most {} empty",
+                self.min_empty_cells,
+                max_empty_cells,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Rules that are only sound when the puzzle has a unique solution, such as unique rectangles or
+/// BUG. None are implemented yet; a rule added here is rejected unless `--assume_unique` is on.
+pub const UNIQUENESS_RULES: &[Rule] = &[];
+
+#[derive(Clone, Debug)]
 pub struct Inferences {
     pub naked_single: bool,
     pub hidden_single: bool,
+    pub pointing: bool,
+    pub claiming: bool,
+    pub naked_pair: bool,
+    pub naked_triple: bool,
+    pub naked_quad: bool,
+    pub x_wing: bool,
+    pub xy_wing: bool,
+    pub w_wing: bool,
+    pub remote_pairs: bool,
+    pub jellyfish: bool,
 }
 
-pub async fn build_formula<W: AsyncWrite + Unpin>(
-    w: &mut W,
-    params: &Parameters,
-) -> Result<HashMap<VariableKind, Variable>> {
-    let mut formula = TaggedVariableFormulaBuilder::default();
+/// Naked and hidden singles, as on the command line.
+impl Default for Inferences {
+    fn default() -> Self {
+        Inferences {
+            naked_single: true,
+            hidden_single: true,
+            pointing: false,
+            claiming: false,
+            naked_pair: false,
+            naked_triple: false,
+            naked_quad: false,
+            x_wing: false,
+            xy_wing: false,
+            w_wing: false,
+            remote_pairs: false,
+            jellyfish: false,
+        }
+    }
+}
+
+impl Inferences {
+    fn field_mut(&mut self, rule: Rule) -> &mut bool {
+        match rule {
+            Rule::NakedSingle => &mut self.naked_single,
+            Rule::HiddenSingle => &mut self.hidden_single,
+            Rule::Pointing => &mut self.pointing,
+            Rule::Claiming => &mut self.claiming,
+            Rule::NakedPair => &mut self.naked_pair,
+            Rule::NakedTriple => &mut self.naked_triple,
+            Rule::NakedQuad => &mut self.naked_quad,
+            Rule::XWing => &mut self.x_wing,
+            Rule::XYWing => &mut self.xy_wing,
+            Rule::WWing => &mut self.w_wing,
+            Rule::RemotePairs => &mut self.remote_pairs,
+            Rule::Jellyfish => &mut self.jellyfish,
+        }
+    }
+
+    fn field(&self, rule: Rule) -> bool {
+        match rule {
+            Rule::NakedSingle => self.naked_single,
+            Rule::HiddenSingle => self.hidden_single,
+            Rule::Pointing => self.pointing,
+            Rule::Claiming => self.claiming,
+            Rule::NakedPair => self.naked_pair,
+            Rule::NakedTriple => self.naked_triple,
+            Rule::NakedQuad => self.naked_quad,
+            Rule::XWing => self.x_wing,
+            Rule::XYWing => self.xy_wing,
+            Rule::WWing => self.w_wing,
+            Rule::RemotePairs => self.remote_pairs,
+            Rule::Jellyfish => self.jellyfish,
+        }
+    }
+
+    pub fn is_allowed(&self, rule: Rule) -> bool {
+        self.field(rule)
+    }
+
+    pub fn set(&mut self, rule: Rule, allowed: bool) {
+        *self.field_mut(rule) = allowed;
+    }
+
+    /// The allowed rules, in the order of `Rule::ALL`.
+    pub fn enabled_rules(&self) -> Vec<Rule> {
+        Rule::ALL
+            .iter()
+            .copied()
+            .filter(|&rule| self.is_allowed(rule))
+            .collect()
+    }
 
+    /// Checks that no allowed rule relies on the puzzle having a unique solution, unless
+    /// `assume_unique` is on.
+    pub fn check_assume_unique(&self, assume_unique: bool) -> Result<()> {
+        self.check_uniqueness_rules(UNIQUENESS_RULES, assume_unique)
+    }
+
+    fn check_uniqueness_rules(&self, uniqueness_rules: &[Rule], assume_unique: bool) -> Result<()> {
+        let enabled: Vec<_> = self
+            .enabled_rules()
+            .into_iter()
+            .filter(|rule| uniqueness_rules.contains(rule))
+            .map(Rule::name)
+            .collect();
+        if !assume_unique && !enabled.is_empty() {
+            return Err(anyhow!(
+                "--assume_unique is off, but these enabled rules are only sound for puzzles with a unique solution: {}",
+                enabled.join(", "),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Collects, for each rule, literals that are true wherever the rule derives a placement that was
+/// not already known on the previous level. Nothing is collected unless `enabled` is set, since
+/// the extra gates are only needed to constrain which techniques a puzzle requires.
+struct RuleFirings {
+    enabled: bool,
+    firings: BTreeMap<Rule, Vec<Literal>>,
+}
+
+impl RuleFirings {
+    fn record(
+        &mut self,
+        formula: &mut Formula,
+        rule: Rule,
+        justifications: &[Literal],
+        already_known: Literal,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let justified = formula.new_variable().as_positive();
+        formula.add_logical_or_constraint(justified, justifications);
+        let fired = formula.new_variable().as_positive();
+        formula.add_logical_and_constraint(fired, &[justified, -already_known]);
+        self.firings.entry(rule).or_default().push(fired);
+    }
+
+    /// Returns one literal per rule that is true if the rule fired anywhere.
+    fn into_fired(self, formula: &mut Formula) -> Vec<Literal> {
+        self.firings
+            .into_values()
+            .map(|firings| {
+                let fired = formula.new_variable().as_positive();
+                formula.add_logical_or_constraint(fired, &firings);
+                fired
+            })
+            .collect()
+    }
+}
+
+fn eliminated(formula: &mut Formula, cell: Cell, digit: Digit, level: usize) -> Literal {
+    formula
+        .get_variable(VariableKind::Eliminated {
+            row: cell.row,
+            col: cell.col,
+            digit,
+            level,
+        })
+        .as_positive()
+}
+
+/// A literal that is true if every placement in `cell` outside of `digits` is eliminated at
+/// `level`. The indicator is shared by every rule that asks for the same cell and digit set.
+fn confined(formula: &mut Formula, cell: Cell, digits: DigitSet, level: usize) -> Literal {
+    formula
+        .get_defined_variable(
+            VariableKind::Confined {
+                row: cell.row,
+                col: cell.col,
+                digits,
+                level,
+            },
+            |formula, variable| {
+                let literals: Vec<_> = Digit::values()
+                    .filter(|digit| !digits.contains(*digit))
+                    .map(|digit| eliminated(formula, cell, digit, level))
+                    .collect();
+                formula.add_logical_and_constraint(variable.as_positive(), &literals);
+            },
+        )
+        .as_positive()
+}
+
+/// Assumes that `is_eliminated` are the only eliminated placements at level 0 and returns the
+/// placements that `eliminations` eliminates, for testing a rule's pattern.
+#[cfg(test)]
+fn fired(
+    formula: &mut Formula,
+    eliminations: &Eliminations,
+    is_eliminated: impl Fn(Cell, Digit) -> bool,
+) -> HashSet<(Cell, Digit)> {
+    let mut assumptions = Vec::new();
+    for cell in Cell::values() {
+        for digit in Digit::values() {
+            let literal = eliminated(formula, cell, digit, 0);
+            assumptions.push(if is_eliminated(cell, digit) {
+                literal
+            } else {
+                -literal
+            });
+        }
+    }
+    let solution = formula.solve(&assumptions).unwrap();
+    eliminations
+        .iter()
+        .filter(|(_, justifications)| {
+            justifications
+                .iter()
+                .any(|literal| solution[&literal.variable()] == literal.is_positive())
+        })
+        .map(|(key, _)| *key)
+        .collect()
+}
+
+/// The file format a formula is written in, which decides how cardinality constraints are
+/// encoded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FormulaFormat {
+    /// DIMACS CNF, where every constraint is broken down into clauses.
+    Dimacs,
+    /// OPB, where cardinality constraints are kept as linear constraints.
+    Opb,
+}
+
+fn add_at_most_one_constraint(formula: &mut Formula, literals: &[Literal], sequential: bool) {
+    if sequential {
+        formula.add_at_most_one_sequential(literals);
+    } else {
+        formula.add_at_most_one_of_constraint(literals);
+    }
+}
+
+fn add_exactly_one_constraint(
+    formula: &mut Formula,
+    literals: Vec<Literal>,
+    format: FormulaFormat,
+    sequential: bool,
+) {
+    match format {
+        FormulaFormat::Dimacs if sequential => formula.add_exactly_k_of_constraint(&literals, 1),
+        FormulaFormat::Dimacs => {
+            formula.add_at_most_one_of_constraint(&literals);
+            formula.add_clause(literals);
+        }
+        FormulaFormat::Opb => formula.add_linear_constraint(&literals, Relation::Equal, 1),
+    }
+}
+
+/// Adds the rules of sudoku: every cell holds one digit, and every digit appears once in each row,
+/// column, and box, and on both diagonals if `diagonals` is set. Without `at_least_one`, only the
+/// at-most-one halves are emitted, for `--no_at_least_one`. With `sequential_at_most_one`, the
+/// constraints go through the sequential counter, as exactly one or at most one of each set.
+fn emit_base_constraints(
+    formula: &mut Formula,
+    format: FormulaFormat,
+    at_least_one: bool,
+    diagonals: bool,
+    sequential_at_most_one: bool,
+) {
     // One digit per cell.
     for row in Row::values() {
         for col in Col::values() {
             let literals: Vec<_> = Digit::values()
                 .map(|digit| {
                     formula
-                        .get_variable(VariableKind::Placed { row, col, digit })
-                        .as_positive()
-                })
-                .collect();
-            formula.add_at_most_one_of_constraint(&literals);
-            formula.add_clause(literals);
+                        .get_variable(VariableKind::Placed { row, col, digit })
+                        .as_positive()
+                })
+                .collect();
+            if at_least_one {
+                add_exactly_one_constraint(formula, literals, format, sequential_at_most_one);
+            } else {
+                add_at_most_one_constraint(formula, &literals, sequential_at_most_one);
+            }
+        }
+    }
+
+    // Each digit appears once in every row, column, and box, and in diagonal sudoku on each diagonal.
+    for cells in house_cells(diagonals) {
+        for digit in Digit::values() {
+            let literals: Vec<_> = cells
+                .iter()
+                .map(|cell| {
+                    formula
+                        .get_variable(VariableKind::Placed {
+                            row: cell.row,
+                            col: cell.col,
+                            digit,
+                        })
+                        .as_positive()
+                })
+                .collect();
+            if at_least_one {
+                add_exactly_one_constraint(formula, literals, format, sequential_at_most_one);
+            } else {
+                add_at_most_one_constraint(formula, &literals, sequential_at_most_one);
+            }
+        }
+    }
+}
+
+/// The parameters that shape the rules of sudoku's clauses. The region layout is chosen once per
+/// run, so it needn't be part of the key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct BaseKey {
+    size: BoardSize,
+    format: FormulaFormat,
+    at_least_one: bool,
+    diagonals: bool,
+    sequential_at_most_one: bool,
+}
+
+impl BaseKey {
+    fn new(params: &Parameters, format: FormulaFormat) -> Self {
+        BaseKey {
+            size: board_size(),
+            format,
+            at_least_one: !params.no_at_least_one,
+            diagonals: params.diagonals,
+            sequential_at_most_one: params.sequential_at_most_one,
+        }
+    }
+}
+
+/// Formulas holding only the rules of sudoku, each built the first time its parameters come up and
+/// cloned as the start of every later formula, so a batch of puzzles or a run of uniqueness checks
+/// doesn't emit the same clauses over and over.
+#[derive(Default)]
+struct BaseCache {
+    formulas: HashMap<BaseKey, Formula>,
+    /// How many base formulas have been built rather than cloned.
+    builds: usize,
+}
+
+impl BaseCache {
+    fn base_formula(&mut self, key: BaseKey) -> Formula {
+        if let Some(formula) = self.formulas.get(&key) {
+            return formula.clone();
+        }
+        let mut formula = Formula::default();
+        emit_base_constraints(
+            &mut formula,
+            key.format,
+            key.at_least_one,
+            key.diagonals,
+            key.sequential_at_most_one,
+        );
+        self.builds += 1;
+        self.formulas.insert(key, formula.clone());
+        formula
+    }
+}
+
+static BASE_CACHE: OnceLock<Mutex<BaseCache>> = OnceLock::new();
+
+/// A new formula holding the rules of sudoku shaped by `key`, cloned from the cache.
+fn base_formula(key: BaseKey) -> Formula {
+    BASE_CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .base_formula(key)
+}
+
+/// The cells of every group that must hold each digit once: the rows, columns, and boxes, then in
+/// diagonal sudoku the main and anti-diagonals.
+fn house_cells(diagonals: bool) -> Vec<Vec<Cell>> {
+    let mut groups: Vec<_> = House::values().map(House::cells).collect();
+    if diagonals {
+        groups.push(
+            Cell::values()
+                .filter(|cell| cell.on_main_diagonal())
+                .collect(),
+        );
+        groups.push(
+            Cell::values()
+                .filter(|cell| cell.on_anti_diagonal())
+                .collect(),
+        );
+    }
+    groups
+}
+
+/// Requires the digits in each cage to differ and, for cages with a sum, to add up to it.
+fn emit_cage_constraints(formula: &mut Formula, cages: &[Cage]) {
+    for cage in cages {
+        for digit in Digit::values() {
+            let literals: Vec<_> = cage
+                .cells
+                .iter()
+                .map(|cell| {
+                    formula
+                        .get_variable(VariableKind::Placed {
+                            row: cell.row,
+                            col: cell.col,
+                            digit,
+                        })
+                        .as_positive()
+                })
+                .collect();
+            formula.add_at_most_one_of_constraint(&literals);
+        }
+
+        let sum = match cage.sum {
+            Some(sum) => sum,
+            None => continue,
+        };
+
+        let values = cage
+            .cells
+            .iter()
+            .map(|&cell| digit_value_bitvector(formula, cell))
+            .collect();
+        BitVector::add_tree(formula, values).add_equal_constraint(formula, sum);
+    }
+}
+
+/// The digit placed in `cell` as a bit vector over the board's digits. Each bit is set if the cell
+/// holds any digit with that bit set, so the value is only meaningful when exactly one digit is
+/// placed.
+fn digit_value_bitvector(formula: &mut Formula, cell: Cell) -> BitVector {
+    let range = 1..board_size().side() as u32 + 1;
+    let bits = (0..range.end.next_power_of_two().trailing_zeros())
+        .map(|bit| {
+            let literals: Vec<_> = Digit::values()
+                .filter(|digit| (digit.as_u8() >> bit) & 1 == 1)
+                .map(|digit| {
+                    formula
+                        .get_variable(VariableKind::Placed {
+                            row: cell.row,
+                            col: cell.col,
+                            digit,
+                        })
+                        .as_positive()
+                })
+                .collect();
+            let output = formula.new_variable().as_positive();
+            formula.add_logical_or_constraint(output, &literals);
+            output
+        })
+        .collect();
+    BitVector::from_bits(range, bits)
+}
+
+/// Requires the digits strictly between the 1 and the highest digit of each clued row or column to
+/// add up to its clue. A cell lies between them when one is placed on either side of it, and only
+/// then does its value count toward the sum.
+fn emit_sandwich_constraints(formula: &mut Formula, sandwiches: &[Sandwich]) {
+    let highest = Digit::new(board_size().side()).unwrap();
+    for sandwich in sandwiches {
+        let cells = sandwich.house.cells();
+        let mut placed = |cell: Cell, digit| {
+            formula
+                .get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                })
+                .as_positive()
+        };
+        let ones: Vec<_> = cells.iter().map(|&cell| placed(cell, Digit::K1)).collect();
+        let highs: Vec<_> = cells.iter().map(|&cell| placed(cell, highest)).collect();
+
+        // The cells at either end have nothing beyond them, so they're never between.
+        let values = (1..cells.len() - 1)
+            .map(|i| {
+                let one_before = any_of(formula, &ones[..i]);
+                let one_after = any_of(formula, &ones[i + 1..]);
+                let high_before = any_of(formula, &highs[..i]);
+                let high_after = any_of(formula, &highs[i + 1..]);
+                let ascending = formula.new_variable().as_positive();
+                formula.add_logical_and_constraint(ascending, &[one_before, high_after]);
+                let descending = formula.new_variable().as_positive();
+                formula.add_logical_and_constraint(descending, &[high_before, one_after]);
+                let between = any_of(formula, &[ascending, descending]);
+                digit_value_bitvector(formula, cells[i]).masked(formula, between)
+            })
+            .collect();
+        BitVector::add_tree(formula, values).add_equal_constraint(formula, sandwich.sum);
+    }
+}
+
+/// Requires the digits along each thermometer to strictly increase from its bulb.
+fn emit_thermo_constraints(formula: &mut Formula, thermos: &[Thermo]) {
+    for thermo in thermos {
+        let values: Vec<_> = thermo
+            .cells
+            .iter()
+            .map(|&cell| digit_value_bitvector(formula, cell))
+            .collect();
+        for pair in values.windows(2) {
+            let less = BitVector::less_than(formula, &pair[0], &pair[1]);
+            formula.add_unit_clause(less);
+        }
+    }
+}
+
+/// Requires the digits on either side of each Kropki dot to have its relationship and, if
+/// `negative` is set, forbids both relationships across every orthogonal edge without a dot.
+fn emit_kropki_constraints(formula: &mut Formula, dots: &[Dot], negative: bool) {
+    for dot in dots {
+        forbid_digit_pairs(formula, dot.cells, |a, b| !dot.color.allows(a, b));
+    }
+    if negative {
+        for a in Cell::values() {
+            for b in Cell::values().filter(|&b| b.index() > a.index()) {
+                if orthogonally_adjacent(a, b)
+                    && !dots
+                        .iter()
+                        .any(|dot| dot.cells == [a, b] || dot.cells == [b, a])
+                {
+                    forbid_digit_pairs(formula, [a, b], |a, b| {
+                        DotColor::ALL.iter().any(|color| color.allows(a, b))
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Rules out the digits of the wrong parity in each shaded cell.
+fn emit_parity_constraints(formula: &mut Formula, cells: &[Cell], parity: Parity) {
+    for cell in cells {
+        for digit in Digit::values().filter(|&digit| !parity.allows(digit)) {
+            let literal = formula
+                .get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                })
+                .as_positive();
+            formula.add_unit_clause(-literal);
+        }
+    }
+}
+
+/// Forbids `cells` from holding each pair of digits for which `forbidden` holds.
+fn forbid_digit_pairs(formula: &mut Formula, cells: [Cell; 2], forbidden: impl Fn(u8, u8) -> bool) {
+    for a in Digit::values() {
+        for b in Digit::values().filter(|b| forbidden(a.as_u8(), b.as_u8())) {
+            let a = formula
+                .get_variable(VariableKind::Placed {
+                    row: cells[0].row,
+                    col: cells[0].col,
+                    digit: a,
+                })
+                .as_positive();
+            let b = formula
+                .get_variable(VariableKind::Placed {
+                    row: cells[1].row,
+                    col: cells[1].col,
+                    digit: b,
+                })
+                .as_positive();
+            formula.add_clause(vec![-a, -b]);
+        }
+    }
+}
+
+/// A fresh literal that holds exactly when any of `literals` does.
+fn any_of(formula: &mut Formula, literals: &[Literal]) -> Literal {
+    let output = formula.new_variable().as_positive();
+    formula.add_logical_or_constraint(output, literals);
+    output
+}
+
+/// Forbids every pair of cells a knight's move apart from holding the same digit.
+fn emit_anti_knight_constraints(formula: &mut Formula) {
+    for cell in Cell::values() {
+        for other in cell
+            .knight_moves()
+            .filter(|other| other.index() > cell.index())
+        {
+            for digit in Digit::values() {
+                let a = formula
+                    .get_variable(VariableKind::Placed {
+                        row: cell.row,
+                        col: cell.col,
+                        digit,
+                    })
+                    .as_positive();
+                let b = formula
+                    .get_variable(VariableKind::Placed {
+                        row: other.row,
+                        col: other.col,
+                        digit,
+                    })
+                    .as_positive();
+                formula.add_clause(vec![-a, -b]);
+            }
+        }
+    }
+}
+
+/// Forbids every pair of cells a king's move apart from holding the same digit. Orthogonal moves
+/// stay within a row or column, which already forbids it, so only the diagonal ones need clauses.
+fn emit_anti_king_constraints(formula: &mut Formula) {
+    for cell in Cell::values() {
+        for other in cell.king_moves().filter(|other| {
+            other.index() > cell.index() && other.row != cell.row && other.col != cell.col
+        }) {
+            for digit in Digit::values() {
+                let a = formula
+                    .get_variable(VariableKind::Placed {
+                        row: cell.row,
+                        col: cell.col,
+                        digit,
+                    })
+                    .as_positive();
+                let b = formula
+                    .get_variable(VariableKind::Placed {
+                        row: other.row,
+                        col: other.col,
+                        digit,
+                    })
+                    .as_positive();
+                formula.add_clause(vec![-a, -b]);
+            }
+        }
+    }
+}
+
+/// Makes each cell given exactly when its mirror under `symmetry` is.
+fn emit_symmetry_constraints(formula: &mut Formula, symmetry: Symmetry) {
+    for cell in Cell::values() {
+        let mirror = match symmetry.mirror(cell) {
+            Some(mirror) if mirror.index() > cell.index() => mirror,
+            _ => continue,
+        };
+        let a = formula
+            .get_variable(VariableKind::Given {
+                row: cell.row,
+                col: cell.col,
+            })
+            .as_positive();
+        let b = formula
+            .get_variable(VariableKind::Given {
+                row: mirror.row,
+                col: mirror.col,
+            })
+            .as_positive();
+        formula.add_logical_equivalence_constraint(a, b);
+    }
+}
+
+/// Runs singles propagation from the givens and adds the resulting placements and eliminations as
+/// unit clauses. These follow from the rules of sudoku alone, so they never change which
+/// solutions exist, but they spare the solver from rediscovering them.
+fn emit_propagated_placements(formula: &mut Formula, givens: &HashMap<Cell, Digit>) {
+    let mut grid = CandidateGrid::from_givens(givens);
+    grid.propagate();
+    for cell in Cell::values() {
+        let candidates = grid.candidates(cell);
+        for digit in Digit::values() {
+            let placed = formula
+                .get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                })
+                .as_positive();
+            if !candidates.contains(digit) {
+                formula.add_unit_clause(-placed);
+            } else if candidates.len() == 1 {
+                formula.add_unit_clause(placed);
+            }
+        }
+    }
+}
+
+/// Constrains the solution with the rules of every variant in `params` beyond the houses, such as
+/// cages, thermometers, and anti-knight. Diagonals belong to the base formula instead.
+fn emit_variant_constraints(formula: &mut Formula, params: &Parameters) {
+    emit_cage_constraints(formula, &params.cages);
+    emit_sandwich_constraints(formula, &params.sandwiches);
+    emit_thermo_constraints(formula, &params.thermos);
+    emit_kropki_constraints(formula, &params.dots, params.kropki_negative);
+    emit_parity_constraints(formula, &params.even_cells, Parity::Even);
+    emit_parity_constraints(formula, &params.odd_cells, Parity::Odd);
+    if params.anti_knight {
+        emit_anti_knight_constraints(formula);
+    }
+    if params.anti_king {
+        emit_anti_king_constraints(formula);
+    }
+}
+
+/// The rules of sudoku and of the variants in `params` with the given placements fixed, for solving
+/// a puzzle rather than generating one. Callers that solve it more than once can add clauses
+/// between solves.
+pub fn solving_formula(
+    params: &Parameters,
+    givens: &HashMap<Cell, Digit>,
+    propagate: bool,
+) -> Formula {
+    let mut formula = base_formula(BaseKey {
+        size: board_size(),
+        format: FormulaFormat::Dimacs,
+        at_least_one: true,
+        diagonals: params.diagonals,
+        sequential_at_most_one: false,
+    });
+    emit_variant_constraints(&mut formula, params);
+    if propagate {
+        emit_propagated_placements(&mut formula, givens);
+    }
+    for cell in Cell::values() {
+        if let Some(&digit) = givens.get(&cell) {
+            let placed = formula
+                .get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                })
+                .as_positive();
+            formula.add_unit_clause(placed);
+        }
+    }
+    formula
+}
+
+/// Builds a formula that solves the puzzle with the given placements using only the rules of
+/// sudoku and the variants in `params`, without modeling any inference.
+pub async fn build_solving_formula<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    params: &Parameters,
+    givens: &HashMap<Cell, Digit>,
+    propagate: bool,
+) -> Result<HashMap<VariableKind, Variable>> {
+    let formula = solving_formula(params, givens, propagate);
+    formula.write_dimacs(w).await?;
+    Ok(formula.into_tagged_variables())
+}
+
+/// Writes the formula for `params` as DIMACS, returning its tagged variables and the statistics
+/// from emitting it.
+pub async fn build_formula<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    params: &Parameters,
+) -> Result<(HashMap<VariableKind, Variable>, EmissionStats)> {
+    let (formula, stats) = emit_formula(params, FormulaFormat::Dimacs)?;
+    formula.write_dimacs(w).await?;
+    Ok((formula.into_tagged_variables(), stats))
+}
+
+/// Like `build_formula`, but starts with a comment naming each tagged variable, so the output
+/// describes itself.
+pub async fn build_tagged_formula<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    params: &Parameters,
+) -> Result<HashMap<VariableKind, Variable>> {
+    let (formula, _) = emit_formula(params, FormulaFormat::Dimacs)?;
+    formula.write_dimacs_with_tags(w).await?;
+    Ok(formula.into_tagged_variables())
+}
+
+/// Like `build_formula`, but writes WCNF for a MaxSAT solver, with a soft clause preferring each
+/// cell not to be a given. The optimal puzzle then has the fewest givens the other parameters allow.
+pub async fn build_maxsat_formula<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    params: &Parameters,
+) -> Result<(HashMap<VariableKind, Variable>, EmissionStats)> {
+    let (mut formula, stats) = emit_formula(params, FormulaFormat::Dimacs)?;
+    let soft_literals: Vec<_> = Cell::values()
+        .map(|cell| {
+            -formula
+                .get_variable(VariableKind::Given {
+                    row: cell.row,
+                    col: cell.col,
+                })
+                .as_positive()
+        })
+        .collect();
+    formula.write_wcnf(w, &soft_literals).await?;
+    Ok((formula.into_tagged_variables(), stats))
+}
+
+/// Like `build_formula`, but writes OPB. The rules of sudoku and the given count become linear
+/// constraints, so no adder tree is needed to count the givens.
+pub async fn build_opb_formula<W: AsyncWrite + Unpin>(
+    w: &mut W,
+    params: &Parameters,
+) -> Result<HashMap<VariableKind, Variable>> {
+    let (formula, _) = emit_formula(params, FormulaFormat::Opb)?;
+    formula.write_opb(w).await?;
+    Ok(formula.into_tagged_variables())
+}
+
+/// The running clause count after each stage of emission. Recording a stage also enforces
+/// `--max_clause_count`, so an oversized formula is abandoned before it finishes growing or is
+/// serialized.
+#[derive(Debug)]
+pub struct EmissionStats {
+    max_clause_count: Option<usize>,
+    stages: Vec<(String, usize)>,
+    /// How many repeated clauses were dropped after the last stage, with `dedup_clauses`.
+    pub duplicates_removed: usize,
+}
+
+impl EmissionStats {
+    fn new(params: &Parameters) -> Self {
+        EmissionStats {
+            max_clause_count: params.max_clause_count,
+            stages: Vec::new(),
+            duplicates_removed: 0,
+        }
+    }
+
+    fn record(&mut self, formula: &Formula, stage: impl Into<String>) -> Result<()> {
+        let stage = stage.into();
+        let total = formula.clause_count();
+        if let Some(max) = self.max_clause_count {
+            if total > max {
+                return Err(anyhow!(
+                    "the formula reached {} clauses while emitting {}, exceeding --max_clause_count {}",
+                    total,
+                    stage,
+                    max,
+                ));
+            }
+        }
+        self.stages.push((stage, total));
+        Ok(())
+    }
+
+    /// A table of how many clauses each stage added, with a running total.
+    pub fn format_table(&self) -> String {
+        let width = self
+            .stages
+            .iter()
+            .map(|(stage, _)| stage.len())
+            .chain(once("stage".len()))
+            .max()
+            .unwrap();
+        let mut output = format!(
+            "{:<width$} {:>10} {:>10}\n",
+            "stage",
+            "clauses",
+            "total",
+            width = width
+        );
+        let mut prev_total = 0;
+        for (stage, total) in &self.stages {
+            output += &format!(
+                "{:<width$} {:>10} {:>10}\n",
+                stage,
+                total - prev_total,
+                total,
+                width = width,
+            );
+            prev_total = *total;
+        }
+        output
+    }
+}
+
+/// Re-emits the formula for `params` and checks that `assignments` satisfies every clause, to catch
+/// a model that doesn't match what was sent to the solver.
+pub fn verify_model(params: &Parameters, assignments: &HashMap<Variable, bool>) -> Result<()> {
+    let (formula, _) = emit_formula(params, FormulaFormat::Dimacs)?;
+    formula.check_model(assignments)
+}
+
+/// Emits the formula for `params` only to measure it, returning the clauses added by each stage.
+pub fn formula_stats(params: &Parameters) -> Result<EmissionStats> {
+    Ok(emit_formula(params, FormulaFormat::Dimacs)?.1)
+}
+
+/// A machine-readable summary of the formula for `params`, for `--format formula_stats`.
+#[derive(Debug, Serialize)]
+pub struct FormulaReport {
+    variables: usize,
+    clauses: usize,
+    /// Tagged variables by kind. Variables introduced by gates and counters are `auxiliary`.
+    variables_by_kind: BTreeMap<&'static str, usize>,
+    /// How many clauses there are of each width.
+    clause_widths: BTreeMap<usize, usize>,
+    stages: Vec<StageLine>,
+}
+
+/// The clauses one stage of emission added, as in the `--stats` table.
+#[derive(Debug, Serialize)]
+struct StageLine {
+    stage: String,
+    clauses: usize,
+    total: usize,
+}
+
+/// Emits the formula for `params` only to measure it, returning a summary of its variables and
+/// clauses.
+pub fn formula_report(params: &Parameters) -> Result<FormulaReport> {
+    let (formula, stats) = emit_formula(params, FormulaFormat::Dimacs)?;
+    let mut variables_by_kind = BTreeMap::new();
+    for kind in formula.tagged_variables().keys() {
+        *variables_by_kind.entry(kind.name()).or_insert(0) += 1;
+    }
+    let auxiliary = formula.variable_count() - formula.tagged_variables().len();
+    if auxiliary > 0 {
+        variables_by_kind.insert("auxiliary", auxiliary);
+    }
+    let mut prev_total = 0;
+    let stages = stats
+        .stages
+        .into_iter()
+        .map(|(stage, total)| {
+            let line = StageLine {
+                stage,
+                clauses: total - prev_total,
+                total,
+            };
+            prev_total = total;
+            line
+        })
+        .collect();
+    Ok(FormulaReport {
+        variables: formula.variable_count(),
+        clauses: formula.clause_count(),
+        variables_by_kind,
+        clause_widths: formula.clause_width_histogram(),
+        stages,
+    })
+}
+
+pub(crate) fn emit_formula(
+    params: &Parameters,
+    format: FormulaFormat,
+) -> Result<(Formula, EmissionStats)> {
+    let mut formula = base_formula(BaseKey::new(params, format));
+    let mut stats = EmissionStats::new(params);
+    let adjacency = params.adjacency();
+    let houses = house_cells(params.diagonals);
+    stats.record(&formula, "rules of sudoku")?;
+
+    if let (Some(givens), FormulaFormat::Opb) = (params.givens, format) {
+        let literals: Vec<_> = Cell::values()
+            .map(|cell| {
+                formula
+                    .get_variable(VariableKind::Given {
+                        row: cell.row,
+                        col: cell.col,
+                    })
+                    .as_positive()
+            })
+            .collect();
+        formula.add_linear_constraint(&literals, Relation::Equal, givens as u32);
+    } else if let FormulaFormat::Opb = format {
+        let literals: Vec<_> = Cell::values()
+            .map(|cell| {
+                formula
+                    .get_variable(VariableKind::Given {
+                        row: cell.row,
+                        col: cell.col,
+                    })
+                    .as_positive()
+            })
+            .collect();
+        if let Some(min) = params.min_givens {
+            formula.add_linear_constraint(&literals, Relation::AtLeast, min as u32);
+        }
+        if let Some(max) = params.max_givens {
+            // At most `max` givens means at least `cell_count - max` empty cells.
+            let empty: Vec<_> = literals.iter().map(|&literal| -literal).collect();
+            let cell_count = board_size().cell_count() as u32;
+            formula.add_linear_constraint(&empty, Relation::AtLeast, cell_count - max as u32);
+        }
+    } else if params.givens.is_some() || params.min_givens.is_some() || params.max_givens.is_some()
+    {
+        // Count the given digits.
+        let given_bits = Cell::values()
+            .map(|cell| {
+                BitVector::from_literal(
+                    formula
+                        .get_variable(VariableKind::Given {
+                            row: cell.row,
+                            col: cell.col,
+                        })
+                        .as_positive(),
+                )
+            })
+            .collect();
+        let given_count = BitVector::add_tree(&mut formula, given_bits);
+
+        if let Some(givens) = params.givens {
+            // Fix the number of given digits.
+            given_count.add_equal_constraint(&mut formula, givens as u32);
+        } else {
+            // Bound the number of given digits.
+            if let Some(min) = params.min_givens {
+                given_count.add_at_least_constraint(&mut formula, min as u32);
+            }
+            if let Some(max) = params.max_givens {
+                given_count.add_at_most_constraint(&mut formula, max as u32);
+            }
         }
     }
 
-    // Each digit appears once in a row.
-    for row in Row::values() {
+    // Require every digit to appear among the givens at least so many times.
+    if params.min_givens_per_digit > 0 {
         for digit in Digit::values() {
-            let literals: Vec<_> = Col::values()
-                .map(|col| {
-                    formula
-                        .get_variable(VariableKind::Placed { row, col, digit })
-                        .as_positive()
+            let given_placements: Vec<_> = Cell::values()
+                .map(|cell| {
+                    let given = formula
+                        .get_variable(VariableKind::Given {
+                            row: cell.row,
+                            col: cell.col,
+                        })
+                        .as_positive();
+                    let placed = formula
+                        .get_variable(VariableKind::Placed {
+                            row: cell.row,
+                            col: cell.col,
+                            digit,
+                        })
+                        .as_positive();
+                    let given_placement = formula.new_variable().as_positive();
+                    formula.add_logical_and_constraint(given_placement, &[given, placed]);
+                    given_placement
                 })
                 .collect();
-            formula.add_at_most_one_of_constraint(&literals);
-            formula.add_clause(literals);
+            let min = params.min_givens_per_digit as u32;
+            if let FormulaFormat::Opb = format {
+                formula.add_linear_constraint(&given_placements, Relation::AtLeast, min);
+            } else {
+                let bits = given_placements
+                    .into_iter()
+                    .map(BitVector::from_literal)
+                    .collect();
+                BitVector::add_tree(&mut formula, bits).add_at_least_constraint(&mut formula, min);
+            }
         }
     }
 
-    // Each digit appears once in a column.
-    for col in Col::values() {
-        for digit in Digit::values() {
-            let literals: Vec<_> = Row::values()
-                .map(|row| {
-                    formula
-                        .get_variable(VariableKind::Placed { row, col, digit })
-                        .as_positive()
-                })
+    // Require at least so many cells to be left for the solver to fill.
+    if params.min_empty_cells > 0 {
+        let empty_cells: Vec<_> = Cell::values()
+            .map(|cell| {
+                -formula
+                    .get_variable(VariableKind::Given {
+                        row: cell.row,
+                        col: cell.col,
+                    })
+                    .as_positive()
+            })
+            .collect();
+        let min = params.min_empty_cells as u32;
+        if let FormulaFormat::Opb = format {
+            formula.add_linear_constraint(&empty_cells, Relation::AtLeast, min);
+        } else {
+            let bits = empty_cells
+                .into_iter()
+                .map(BitVector::from_literal)
                 .collect();
-            formula.add_at_most_one_of_constraint(&literals);
-            formula.add_clause(literals);
+            BitVector::add_tree(&mut formula, bits).add_at_least_constraint(&mut formula, min);
         }
     }
 
-    // Each digit appears once in a box.
-    for box_ in Box::values() {
-        for digit in Digit::values() {
-            let literals: Vec<_> = box_
-                .cells()
-                .map(|cell| {
-                    formula
+    // Pin the givens of a known puzzle.
+    if let Some(fixed_givens) = &params.fixed_givens {
+        for cell in Cell::values() {
+            let given = formula
+                .get_variable(VariableKind::Given {
+                    row: cell.row,
+                    col: cell.col,
+                })
+                .as_positive();
+            match fixed_givens.get(&cell) {
+                Some(&digit) => {
+                    let placed = formula
                         .get_variable(VariableKind::Placed {
                             row: cell.row,
                             col: cell.col,
                             digit,
                         })
-                        .as_positive()
+                        .as_positive();
+                    formula.add_unit_clause(given);
+                    formula.add_unit_clause(placed);
+                }
+                None => formula.add_unit_clause(-given),
+            }
+        }
+        if params.propagate {
+            emit_propagated_placements(&mut formula, fixed_givens);
+        }
+    }
+
+    emit_symmetry_constraints(&mut formula, params.symmetry);
+    emit_variant_constraints(&mut formula, params);
+
+    // Constrain the solution to contain the pattern.
+    for (&cell, &digit) in &params.pattern {
+        let placed = formula
+            .get_variable(VariableKind::Placed {
+                row: cell.row,
+                col: cell.col,
+                digit,
+            })
+            .as_positive();
+        formula.add_unit_clause(placed);
+    }
+
+    // Rule out previously generated puzzles. A puzzle is repeated only if exactly the same cells
+    // are given and each holds the same digit.
+    for puzzle in &params.excluded_puzzles {
+        let mut clause = Vec::new();
+        for cell in Cell::values() {
+            let given = formula
+                .get_variable(VariableKind::Given {
+                    row: cell.row,
+                    col: cell.col,
                 })
-                .collect();
-            formula.add_at_most_one_of_constraint(&literals);
-            formula.add_clause(literals);
+                .as_positive();
+            match puzzle.get(&cell) {
+                Some(&digit) => {
+                    let placed = formula
+                        .get_variable(VariableKind::Placed {
+                            row: cell.row,
+                            col: cell.col,
+                            digit,
+                        })
+                        .as_positive();
+                    clause.push(-given);
+                    clause.push(-placed);
+                }
+                None => clause.push(given),
+            }
         }
+        formula.add_clause(clause);
     }
 
-    // Count the given digits.
-    let given_bits = Cell::values()
-        .map(|cell| {
-            BitVector::from_literal(
-                formula
-                    .get_variable(VariableKind::Given {
+    // Rule out previously generated solution grids, which repeat if every cell holds the same digit.
+    for grid in &params.excluded_solutions {
+        let clause = Cell::values()
+            .map(|cell| {
+                -formula
+                    .get_variable(VariableKind::Placed {
                         row: cell.row,
                         col: cell.col,
+                        digit: grid[&cell],
                     })
-                    .as_positive(),
-            )
-        })
-        .collect();
-    let given_count = BitVector::add_tree(&mut formula, given_bits);
+                    .as_positive()
+            })
+            .collect();
+        formula.add_clause(clause);
+    }
 
-    // Fix the number of given digits.
-    assert_eq!(7, given_count.len());
-    for bit in 0..7 {
-        let mut literal = given_count.bits()[bit];
-        if (params.givens >> bit) & 1 == 0 {
-            literal = -literal;
+    if params.base_only {
+        stats.record(&formula, "givens")?;
+        if params.dedup_clauses {
+            stats.duplicates_removed = formula.dedup_clauses();
         }
-        formula.add_unit_clause(literal);
+        return Ok((formula, stats));
     }
 
     // At level 0, the given placements are forced and nothing is eliminated.
@@ -154,12 +1413,92 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
         }
     }
 
+    stats.record(&formula, "givens and level 0")?;
+
     // Model bounded iteration of forced and eliminated placements in accordance with a rule set.
-    for cell in Cell::values() {
-        for digit in Digit::values() {
-            for level in 1..params.inference_levels {
-                let prev_level = level - 1;
+    let mut rule_firings = RuleFirings {
+        enabled: params.min_distinct_techniques > 0,
+        firings: BTreeMap::new(),
+    };
+    for level in 1..params.inference_levels {
+        let prev_level = level - 1;
+
+        // Rules that reason about patterns spanning several cells contribute eliminations to many
+        // placements at once, so they are emitted up front for the whole level.
+        let mut rule_eliminations = Vec::new();
+        if params.allowed_inferences.pointing {
+            let mut eliminations = Eliminations::new();
+            emit_pointing(&mut formula, prev_level, &mut eliminations);
+            rule_eliminations.push((Rule::Pointing, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::Pointing))?;
+        }
+        if params.allowed_inferences.claiming {
+            let mut eliminations = Eliminations::new();
+            emit_claiming(&mut formula, prev_level, &mut eliminations);
+            rule_eliminations.push((Rule::Claiming, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::Claiming))?;
+        }
+        for (rule, size) in [
+            (Rule::NakedPair, 2),
+            (Rule::NakedTriple, 3),
+            (Rule::NakedQuad, 4),
+        ] {
+            if params.allowed_inferences.is_allowed(rule) {
+                let mut eliminations = Eliminations::new();
+                emit_naked_subsets(&mut formula, prev_level, size, &mut eliminations);
+                rule_eliminations.push((rule, eliminations));
+                stats.record(&formula, format!("level {}: {}", level, rule))?;
+            }
+        }
+        if params.allowed_inferences.x_wing {
+            let mut eliminations = Eliminations::new();
+            emit_fish(&mut formula, prev_level, 2, &mut eliminations);
+            rule_eliminations.push((Rule::XWing, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::XWing))?;
+        }
+        if params.allowed_inferences.xy_wing {
+            let mut eliminations = Eliminations::new();
+            emit_xy_wing(&mut formula, prev_level, &mut eliminations);
+            rule_eliminations.push((Rule::XYWing, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::XYWing))?;
+        }
+        if params.allowed_inferences.w_wing {
+            let mut eliminations = Eliminations::new();
+            emit_w_wing(&mut formula, prev_level, &mut eliminations);
+            rule_eliminations.push((Rule::WWing, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::WWing))?;
+        }
+        if params.allowed_inferences.remote_pairs {
+            let mut eliminations = Eliminations::new();
+            emit_remote_pairs(
+                &mut formula,
+                prev_level,
+                params.max_chain_links,
+                &mut eliminations,
+            );
+            rule_eliminations.push((Rule::RemotePairs, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::RemotePairs))?;
+        }
+        if params.allowed_inferences.jellyfish {
+            let mut eliminations = Eliminations::new();
+            emit_fish(&mut formula, prev_level, 4, &mut eliminations);
+            rule_eliminations.push((Rule::Jellyfish, eliminations));
+            stats.record(&formula, format!("level {}: {}", level, Rule::Jellyfish))?;
+        }
+        let mut pattern_eliminations = Eliminations::new();
+        for (rule, eliminations) in rule_eliminations {
+            for ((cell, digit), justifications) in eliminations {
+                let already_known = eliminated(&mut formula, cell, digit, prev_level);
+                rule_firings.record(&mut formula, rule, &justifications, already_known);
+                pattern_eliminations
+                    .entry((cell, digit))
+                    .or_default()
+                    .extend(justifications);
+            }
+        }
 
+        for cell in Cell::values() {
+            for digit in Digit::values() {
                 // Build up lists of justifications for forcing or eliminating this placement. The
                 // variables for forcing and eliminating this placement will be equated to the
                 // logical OR of these justifications.
@@ -167,16 +1506,15 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                 let mut eliminating_justifications = Vec::new();
 
                 // Forced or eliminated placements propagate from the previous level.
-                forcing_justifications.push(
-                    formula
-                        .get_variable(VariableKind::Forced {
-                            row: cell.row,
-                            col: cell.col,
-                            digit,
-                            level: prev_level,
-                        })
-                        .as_positive(),
-                );
+                let already_forced = formula
+                    .get_variable(VariableKind::Forced {
+                        row: cell.row,
+                        col: cell.col,
+                        digit,
+                        level: prev_level,
+                    })
+                    .as_positive();
+                forcing_justifications.push(already_forced);
                 eliminating_justifications.push(
                     formula
                         .get_variable(VariableKind::Eliminated {
@@ -193,7 +1531,7 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                 // This placement is forced if all other placements in its cell are eliminated on
                 // the previous level.
                 if params.allowed_inferences.naked_single {
-                    forcing_justifications.push({
+                    let justification = {
                         let mut literals = Vec::new();
                         for other_digit in Digit::values() {
                             if digit != other_digit {
@@ -212,7 +1550,14 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                         let justification = formula.new_variable().as_positive();
                         formula.add_logical_and_constraint(justification, &literals);
                         justification
-                    });
+                    };
+                    rule_firings.record(
+                        &mut formula,
+                        Rule::NakedSingle,
+                        &[justification],
+                        already_forced,
+                    );
+                    forcing_justifications.push(justification);
                 }
 
                 // RULE: HIDDEN SINGLE
@@ -220,66 +1565,34 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                 // This placement is forced if, within one of its houses, all other placements for
                 // this digit are eliminated.
                 if params.allowed_inferences.hidden_single {
-                    forcing_justifications.push({
-                        let mut literals = Vec::new();
-                        for other_col in Col::values() {
-                            if cell.col != other_col {
-                                literals.push(
-                                    formula
-                                        .get_variable(VariableKind::Eliminated {
-                                            row: cell.row,
-                                            col: other_col,
-                                            digit,
-                                            level: prev_level,
-                                        })
-                                        .as_positive(),
-                                );
-                            }
-                        }
-                        let justification = formula.new_variable().as_positive();
-                        formula.add_logical_and_constraint(justification, &literals);
-                        justification
-                    });
-                    forcing_justifications.push({
-                        let mut literals = Vec::new();
-                        for other_row in Row::values() {
-                            if cell.row != other_row {
-                                literals.push(
-                                    formula
-                                        .get_variable(VariableKind::Eliminated {
-                                            row: other_row,
-                                            col: cell.col,
-                                            digit,
-                                            level: prev_level,
-                                        })
-                                        .as_positive(),
-                                );
-                            }
-                        }
-                        let justification = formula.new_variable().as_positive();
-                        formula.add_logical_and_constraint(justification, &literals);
-                        justification
-                    });
-                    forcing_justifications.push({
-                        let mut literals = Vec::new();
-                        for other_cell in cell.box_().cells() {
-                            if cell != other_cell {
-                                literals.push(
-                                    formula
-                                        .get_variable(VariableKind::Eliminated {
-                                            row: other_cell.row,
-                                            col: other_cell.col,
-                                            digit,
-                                            level: prev_level,
-                                        })
-                                        .as_positive(),
-                                );
-                            }
-                        }
+                    // In diagonal sudoku, each diagonal this cell is on acts as one more house.
+                    let first_justification = forcing_justifications.len();
+                    for house in houses.iter().filter(|house| house.contains(&cell)) {
+                        let literals: Vec<_> = house
+                            .iter()
+                            .copied()
+                            .filter(|&other_cell| other_cell != cell)
+                            .map(|other_cell| {
+                                formula
+                                    .get_variable(VariableKind::Eliminated {
+                                        row: other_cell.row,
+                                        col: other_cell.col,
+                                        digit,
+                                        level: prev_level,
+                                    })
+                                    .as_positive()
+                            })
+                            .collect();
                         let justification = formula.new_variable().as_positive();
                         formula.add_logical_and_constraint(justification, &literals);
-                        justification
-                    });
+                        forcing_justifications.push(justification);
+                    }
+                    rule_firings.record(
+                        &mut formula,
+                        Rule::HiddenSingle,
+                        &forcing_justifications[first_justification..],
+                        already_forced,
+                    );
                 }
 
                 // This placement is eliminated by any other forced placement in its cell on the
@@ -302,7 +1615,7 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                 // This placement is eliminated by any other forced placement it sees for the same
                 // digit on the previous level.
                 for other_cell in Cell::values() {
-                    if cell.sees_other(other_cell) {
+                    if adjacency.sees(cell, other_cell) {
                         eliminating_justifications.push(
                             formula
                                 .get_variable(VariableKind::Forced {
@@ -316,6 +1629,11 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                     }
                 }
 
+                // This placement is eliminated by any pattern rule that targets it.
+                if let Some(justifications) = pattern_eliminations.remove(&(cell, digit)) {
+                    eliminating_justifications.extend(justifications);
+                }
+
                 // Tie whether this placement is forced to the logical OR of the justifications.
                 let forced = formula
                     .get_variable(VariableKind::Forced {
@@ -339,7 +1657,20 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                 formula.add_logical_or_constraint(eliminated, &eliminating_justifications);
             }
         }
+        stats.record(&formula, format!("level {}: singles and carry-over", level))?;
+    }
+
+    // Require a variety of techniques to fire somewhere in the derivation.
+    let fired = rule_firings.into_fired(&mut formula);
+    if fired.len() < params.min_distinct_techniques {
+        return Err(anyhow!(
+            "--min_distinct_techniques is {}, but only {} inference rules can fire in {} levels",
+            params.min_distinct_techniques,
+            fired.len(),
+            params.inference_levels,
+        ));
     }
+    formula.add_at_least_k_of_constraint(&fired, params.min_distinct_techniques);
 
     // The last iteration of forced and eliminated placements must match the board.
     for cell in Cell::values() {
@@ -367,12 +1698,578 @@ pub async fn build_formula<W: AsyncWrite + Unpin>(
                     digit,
                 })
                 .as_positive();
-            formula.add_logical_equivalence_constraint(forced, placed);
-            formula.add_logical_equivalence_constraint(eliminated, -placed);
+            if params.relax_last_level {
+                // Only require the last level to agree with the board, so a circuit that runs out of
+                // levels before its fixpoint still yields a puzzle to inspect.
+                formula.add_binary_clause(-forced, placed);
+                formula.add_binary_clause(-eliminated, -placed);
+            } else {
+                formula.add_logical_equivalence_constraint(forced, placed);
+                formula.add_logical_equivalence_constraint(eliminated, -placed);
+            }
         }
     }
+    stats.record(&formula, "technique count and final level")?;
+    if params.dedup_clauses {
+        stats.duplicates_removed = formula.dedup_clauses();
+    }
 
-    formula.write_dimacs(w).await?;
+    Ok((formula, stats))
+}
 
-    Ok(formula.into_tagged_variables())
+#[cfg(test)]
+mod tests {
+    use tokio::io::sink;
+
+    use super::{
+        build_formula, digit_value_bitvector, emit_anti_king_constraints, emit_cage_constraints,
+        emit_formula, emit_kropki_constraints, emit_parity_constraints, emit_sandwich_constraints,
+        emit_symmetry_constraints, emit_thermo_constraints, formula_stats, house_cells, BaseCache,
+        BaseKey, Formula, FormulaFormat, Inferences, Parameters,
+    };
+    use crate::board_size::{with_board_size, BoardSize};
+    use crate::cages::parse_cages;
+    use crate::formula_builder::{CardinalityFormulaBuilder, FormulaBuilder, Literal};
+    use crate::kropki::parse_dots;
+    use crate::parity::{parse_parity_cells, Parity};
+    use crate::puzzle_string::parse_puzzle_string;
+    use crate::rule::Rule;
+    use crate::sandwich::parse_sandwiches;
+    use crate::sudoku::{Cell, Digit, VariableKind};
+    use crate::symmetry::Symmetry;
+    use crate::thermo::parse_thermos;
+
+    fn placed(formula: &mut Formula, cell: &str, digit: u8) -> Literal {
+        let cell: Cell = cell.parse().unwrap();
+        formula
+            .get_variable(VariableKind::Placed {
+                row: cell.row,
+                col: cell.col,
+                digit: Digit::new(digit).unwrap(),
+            })
+            .as_positive()
+    }
+
+    /// A formula where each of `cells` holds exactly one digit, with nothing else constrained.
+    fn one_digit_per_cell(cells: &[&str]) -> Formula {
+        let mut formula = Formula::default();
+        for cell in cells {
+            let literals: Vec<_> = (1..=9)
+                .map(|digit| placed(&mut formula, cell, digit))
+                .collect();
+            formula.add_at_most_one_of_constraint(&literals);
+            formula.add_clause(literals);
+        }
+        formula
+    }
+
+    #[test]
+    fn diagonal_cells_have_extra_houses() {
+        let houses_of = |diagonals, cell: &str| {
+            let cell: Cell = cell.parse().unwrap();
+            house_cells(diagonals)
+                .into_iter()
+                .filter(|house| house.contains(&cell))
+                .count()
+        };
+        assert_eq!(27, house_cells(false).len());
+        assert_eq!(29, house_cells(true).len());
+        assert!(house_cells(true).iter().all(|house| house.len() == 9));
+        assert_eq!(3, houses_of(false, "r5c5"));
+        assert_eq!(5, houses_of(true, "r5c5"));
+        assert_eq!(4, houses_of(true, "r1c1"));
+        assert_eq!(3, houses_of(true, "r1c2"));
+    }
+
+    #[test]
+    fn small_boards_have_smaller_houses() {
+        with_board_size(BoardSize::new(2, 3), || {
+            let houses = house_cells(true);
+            assert_eq!(20, houses.len());
+            assert!(houses.iter().all(|house| house.len() == 6));
+            let r1c1: Cell = "r1c1".parse().unwrap();
+            let box_ = houses
+                .iter()
+                .find(|house| house.contains(&r1c1) && house.contains(&"r2c3".parse().unwrap()))
+                .unwrap();
+            assert!(!box_.contains(&"r3c1".parse().unwrap()));
+        });
+        with_board_size(BoardSize::new(2, 2), || {
+            assert_eq!(12, house_cells(false).len());
+            assert!(house_cells(false).iter().all(|house| house.len() == 4));
+        });
+    }
+
+    #[test]
+    fn hexadoku_formula_solves_a_nearly_full_grid() {
+        with_board_size(BoardSize::HEXADOKU, || {
+            // A solved grid with the long diagonal emptied, so every empty cell is a naked single.
+            let digit = |cell: Cell| {
+                let (row, col) = (cell.row.index(), cell.col.index());
+                Digit::new(((4 * (row % 4) + row / 4 + col) % 16 + 1) as u8).unwrap()
+            };
+            let givens = Cell::values()
+                .filter(|cell| !cell.on_main_diagonal())
+                .map(|cell| (cell, digit(cell)))
+                .collect();
+            let params = Parameters {
+                givens: None,
+                inference_levels: 3,
+                fixed_givens: Some(givens),
+                propagate: false,
+                ..Parameters::default()
+            };
+            let (mut formula, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+            let solution = formula.solve(&[]).unwrap();
+            for cell in Cell::values().filter(|cell| cell.on_main_diagonal()) {
+                let placed = placed(&mut formula, &cell.to_string(), digit(cell).as_u8());
+                assert!(solution[&placed.variable()], "{}", cell);
+            }
+        });
+    }
+
+    #[test]
+    fn base_only_leaves_out_the_circuit() {
+        let givens = parse_puzzle_string(
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let params = Parameters {
+            givens: None,
+            fixed_givens: Some(givens),
+            ..Parameters::default()
+        };
+        let (full, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+        let (mut base, _) = emit_formula(
+            &Parameters {
+                base_only: true,
+                ..params
+            },
+            FormulaFormat::Dimacs,
+        )
+        .unwrap();
+
+        assert!(base.variable_count() * 10 < full.variable_count());
+        assert!(base.clause_count() * 10 < full.clause_count());
+        assert!(base.tagged_variables().keys().all(|kind| matches!(
+            kind,
+            VariableKind::Placed { .. } | VariableKind::Given { .. }
+        )));
+        let solution = base.solve(&[]).unwrap();
+        let r1c3 = placed(&mut base, "r1c3", 4);
+        assert!(solution[&r1c3.variable()]);
+    }
+
+    #[test]
+    fn excluded_solutions_rule_out_their_grid() {
+        let givens = parse_puzzle_string(
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let solution = parse_puzzle_string(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        )
+        .unwrap();
+        let mut params = Parameters {
+            givens: None,
+            fixed_givens: Some(givens),
+            base_only: true,
+            ..Parameters::default()
+        };
+        let (formula, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+        assert!(formula.solve(&[]).is_some());
+
+        // The givens have only the one solution, so excluding it leaves nothing.
+        params.excluded_solutions.push(solution);
+        let (formula, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+        assert!(formula.solve(&[]).is_none());
+    }
+
+    #[test]
+    fn sequential_base_has_the_same_solution() {
+        let givens = parse_puzzle_string(
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let params = Parameters {
+            givens: None,
+            fixed_givens: Some(givens),
+            base_only: true,
+            sequential_at_most_one: true,
+            ..Parameters::default()
+        };
+        let (mut formula, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+        let solution = formula.solve(&[]).unwrap();
+        let r1c3 = placed(&mut formula, "r1c3", 4);
+        assert!(solution[&r1c3.variable()]);
+        assert!(formula.solve(&[-r1c3]).is_none());
+    }
+
+    #[test]
+    fn base_cache_builds_each_base_once() {
+        let mut cache = BaseCache::default();
+        let params = Parameters::default();
+        let key = BaseKey::new(&params, FormulaFormat::Dimacs);
+        let mut first = cache.base_formula(key);
+        let second = cache.base_formula(key);
+        assert_eq!(1, cache.builds);
+        assert_eq!(first.variable_count(), second.variable_count());
+        assert_eq!(first.clause_count(), second.clause_count());
+
+        // Extending a clone leaves the cached base alone.
+        let r1c1 = placed(&mut first, "r1c1", 1);
+        first.add_unit_clause(r1c1);
+        assert_eq!(
+            second.clause_count(),
+            cache.base_formula(key).clause_count()
+        );
+        assert_eq!(1, cache.builds);
+
+        let diagonals = Parameters {
+            diagonals: true,
+            ..params
+        };
+        cache.base_formula(BaseKey::new(&diagonals, FormulaFormat::Dimacs));
+        assert_eq!(2, cache.builds);
+    }
+
+    #[test]
+    fn symmetry_pairs_givens_with_their_mirrors() {
+        for symmetry in Symmetry::ALL {
+            let mut formula = Formula::default();
+            let given = |formula: &mut Formula, cell: Cell| {
+                formula
+                    .get_variable(VariableKind::Given {
+                        row: cell.row,
+                        col: cell.col,
+                    })
+                    .as_positive()
+            };
+            for cell in Cell::values() {
+                given(&mut formula, cell);
+            }
+            emit_symmetry_constraints(&mut formula, symmetry);
+
+            // One equivalence, as two binary clauses, per pair of distinct mirrored cells.
+            let pairs = Cell::values()
+                .filter(
+                    |&cell| matches!(symmetry.mirror(cell), Some(m) if m.index() > cell.index()),
+                )
+                .count();
+            assert_eq!(
+                match symmetry {
+                    Symmetry::None => 0,
+                    Symmetry::Rotational => 40,
+                    Symmetry::Horizontal | Symmetry::Vertical | Symmetry::Diagonal => 36,
+                },
+                pairs,
+            );
+            assert_eq!(2 * pairs, formula.clause_count());
+
+            // Giving a cell without its mirror is unsatisfiable, and every other cell is free.
+            let r1c2 = given(&mut formula, "r1c2".parse().unwrap());
+            for other in Cell::values().filter(|&other| other != "r1c2".parse().unwrap()) {
+                let other_literal = given(&mut formula, other);
+                assert_eq!(
+                    symmetry.mirror("r1c2".parse().unwrap()) != Some(other),
+                    formula.solve(&[r1c2, -other_literal]).is_some(),
+                    "{} {}",
+                    symmetry,
+                    other,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cages_constrain_sums_and_distinctness() {
+        let cages = parse_cages("r1c1+r2c2=3;r5c5+r6c6+r5c6=24;r8c8+r9c2").unwrap();
+        let mut formula =
+            one_digit_per_cell(&["r1c1", "r2c2", "r5c5", "r6c6", "r5c6", "r8c8", "r9c2"]);
+        emit_cage_constraints(&mut formula, &cages);
+
+        // Only {1, 2} sums to 3, and only {7, 8, 9} sums to 24.
+        let a = placed(&mut formula, "r1c1", 2);
+        assert!(formula.solve(&[a]).is_some());
+        let a = placed(&mut formula, "r1c1", 3);
+        assert!(formula.solve(&[a]).is_none());
+        let a = placed(&mut formula, "r6c6", 9);
+        assert!(formula.solve(&[a]).is_some());
+        let a = placed(&mut formula, "r6c6", 6);
+        assert!(formula.solve(&[a]).is_none());
+
+        // A cage without a sum only keeps its digits distinct, even across houses.
+        let a = placed(&mut formula, "r8c8", 4);
+        let b = placed(&mut formula, "r9c2", 5);
+        assert!(formula.solve(&[a, b]).is_some());
+        let b = placed(&mut formula, "r9c2", 4);
+        assert!(formula.solve(&[a, b]).is_none());
+    }
+
+    #[test]
+    fn sandwich_clue_sums_the_cells_between_one_and_nine() {
+        let sandwiches = parse_sandwiches("r1=10").unwrap();
+        let row = |col| format!("r1c{}", col);
+        let mut formula = one_digit_per_cell(&[
+            "r1c1", "r1c2", "r1c3", "r1c4", "r1c5", "r1c6", "r1c7", "r1c8", "r1c9",
+        ]);
+        for digit in 1..=9 {
+            let literals: Vec<_> = (1..=9)
+                .map(|col| placed(&mut formula, &row(col), digit))
+                .collect();
+            formula.add_at_most_one_of_constraint(&literals);
+        }
+        emit_sandwich_constraints(&mut formula, &sandwiches);
+
+        let one = placed(&mut formula, "r1c1", 1);
+        let nine = placed(&mut formula, "r1c4", 9);
+        let four = placed(&mut formula, "r1c2", 4);
+        let five = placed(&mut formula, "r1c3", 5);
+        assert!(formula.solve(&[one, nine, four]).is_some());
+        assert!(formula.solve(&[one, nine, four, five]).is_none());
+        let nine = placed(&mut formula, "r1c2", 9);
+        assert!(formula.solve(&[one, nine]).is_none());
+
+        // The 9 may come first.
+        let nine = placed(&mut formula, "r1c5", 9);
+        let one = placed(&mut formula, "r1c8", 1);
+        let three = placed(&mut formula, "r1c6", 3);
+        let seven = placed(&mut formula, "r1c7", 7);
+        let six = placed(&mut formula, "r1c7", 6);
+        assert!(formula.solve(&[nine, one, three, seven]).is_some());
+        assert!(formula.solve(&[nine, one, three, six]).is_none());
+    }
+
+    #[test]
+    fn anti_king_forbids_repeats_across_a_corner() {
+        let mut formula = one_digit_per_cell(&["r1c3", "r2c4", "r3c5"]);
+        emit_anti_king_constraints(&mut formula);
+
+        let corner = placed(&mut formula, "r1c3", 5);
+        let diagonal = placed(&mut formula, "r2c4", 5);
+        let two_away = placed(&mut formula, "r3c5", 5);
+        assert!(formula.solve(&[corner, diagonal]).is_none());
+        assert!(formula.solve(&[corner, two_away]).is_some());
+    }
+
+    #[test]
+    fn parity_cells_rule_out_the_other_digits() {
+        let mut formula = one_digit_per_cell(&["r1c1", "r1c2"]);
+        let even = parse_parity_cells("r1c1", Parity::Even).unwrap();
+        let odd = parse_parity_cells("r1c2", Parity::Odd).unwrap();
+        emit_parity_constraints(&mut formula, &even, Parity::Even);
+        emit_parity_constraints(&mut formula, &odd, Parity::Odd);
+
+        let three = placed(&mut formula, "r1c1", 3);
+        let four = placed(&mut formula, "r1c1", 4);
+        let odd_three = placed(&mut formula, "r1c2", 3);
+        let odd_four = placed(&mut formula, "r1c2", 4);
+        assert!(formula.solve(&[three]).is_none());
+        assert!(formula.solve(&[four, odd_three]).is_some());
+        assert!(formula.solve(&[odd_four]).is_none());
+    }
+
+    #[test]
+    fn white_dot_requires_consecutive_digits() {
+        let mut formula = one_digit_per_cell(&["r1c1", "r1c2"]);
+        emit_kropki_constraints(&mut formula, &parse_dots("r1c1+r1c2=w").unwrap(), false);
+
+        for (a, b, expected) in [(4, 5, true), (5, 4, true), (4, 6, false), (1, 9, false)] {
+            let a_literal = placed(&mut formula, "r1c1", a);
+            let b_literal = placed(&mut formula, "r1c2", b);
+            let solution = formula.solve(&[a_literal, b_literal]);
+            assert_eq!(expected, solution.is_some(), "{} and {}", a, b);
+        }
+    }
+
+    #[test]
+    fn black_dot_requires_a_double() {
+        let mut formula = one_digit_per_cell(&["r1c1", "r2c1"]);
+        emit_kropki_constraints(&mut formula, &parse_dots("r1c1+r2c1=b").unwrap(), false);
+
+        for (a, b, expected) in [(3, 6, true), (8, 4, true), (3, 4, false), (5, 9, false)] {
+            let a_literal = placed(&mut formula, "r1c1", a);
+            let b_literal = placed(&mut formula, "r2c1", b);
+            let solution = formula.solve(&[a_literal, b_literal]);
+            assert_eq!(expected, solution.is_some(), "{} and {}", a, b);
+        }
+        // 5 has neither a double on the board nor a whole half.
+        let five = placed(&mut formula, "r1c1", 5);
+        assert!(formula.solve(&[five]).is_none());
+    }
+
+    #[test]
+    fn kropki_negative_constrains_undotted_edges() {
+        let dots = parse_dots("r1c1+r1c2=w").unwrap();
+        let mut formula = one_digit_per_cell(&["r1c1", "r1c2", "r2c1"]);
+        emit_kropki_constraints(&mut formula, &dots, true);
+
+        let four = placed(&mut formula, "r1c1", 4);
+        let below_five = placed(&mut formula, "r2c1", 5);
+        let below_eight = placed(&mut formula, "r2c1", 8);
+        let below_seven = placed(&mut formula, "r2c1", 7);
+        assert!(formula.solve(&[four, below_five]).is_none());
+        assert!(formula.solve(&[four, below_eight]).is_none());
+        assert!(formula.solve(&[four, below_seven]).is_some());
+        // The dotted edge still allows its own relationship.
+        let five = placed(&mut formula, "r1c2", 5);
+        assert!(formula.solve(&[four, five, below_seven]).is_some());
+    }
+
+    #[test]
+    fn thermo_digits_increase_from_the_bulb() {
+        let thermos = parse_thermos("r1c1+r1c2+r2c3").unwrap();
+        let mut formula = one_digit_per_cell(&["r1c1", "r1c2", "r2c3"]);
+        emit_thermo_constraints(&mut formula, &thermos);
+
+        let bulb_two = placed(&mut formula, "r1c1", 2);
+        let next_five = placed(&mut formula, "r1c2", 5);
+        let next_one = placed(&mut formula, "r1c2", 1);
+        let next_two = placed(&mut formula, "r1c2", 2);
+        let tip_nine = placed(&mut formula, "r2c3", 9);
+        let tip_five = placed(&mut formula, "r2c3", 5);
+        assert!(formula.solve(&[bulb_two, next_five, tip_nine]).is_some());
+        assert!(formula.solve(&[bulb_two, next_one]).is_none());
+        assert!(formula.solve(&[bulb_two, next_two]).is_none());
+        assert!(formula.solve(&[next_five, tip_five]).is_none());
+
+        // A 9 in the middle leaves nothing higher for the tip.
+        let next_nine = placed(&mut formula, "r1c2", 9);
+        assert!(formula.solve(&[next_nine]).is_none());
+    }
+
+    #[test]
+    fn two_cell_cage_sums_digit_values() {
+        let cages = parse_cages("r1c1+r1c2=4").unwrap();
+        let mut formula = one_digit_per_cell(&["r1c1", "r1c2"]);
+        let value = digit_value_bitvector(&mut formula, "r1c1".parse().unwrap());
+        emit_cage_constraints(&mut formula, &cages);
+
+        // Only {1, 3} sums to 4 without repeating a digit, so 2 + 2 is out.
+        for (a, b, expected) in [(1, 3, true), (3, 1, true), (2, 2, false), (1, 2, false)] {
+            let a_literal = placed(&mut formula, "r1c1", a);
+            let b_literal = placed(&mut formula, "r1c2", b);
+            let solution = formula.solve(&[a_literal, b_literal]);
+            assert_eq!(expected, solution.is_some(), "{} + {}", a, b);
+            if let Some(solution) = solution {
+                let decoded: u32 = value
+                    .bits()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, bit)| ((solution[&bit.variable()] == bit.is_positive()) as u32) << i)
+                    .sum();
+                assert_eq!(a as u32, decoded);
+            }
+        }
+    }
+
+    #[test]
+    fn given_counts_must_fit_on_the_board() {
+        for params in [
+            Parameters {
+                givens: Some(82),
+                ..Parameters::default()
+            },
+            Parameters {
+                givens: None,
+                min_givens: Some(100),
+                ..Parameters::default()
+            },
+            Parameters {
+                givens: None,
+                max_givens: Some(82),
+                ..Parameters::default()
+            },
+        ] {
+            let error = params.check_given_counts().unwrap_err();
+            assert!(error.to_string().contains("can be at most 81"), "{}", error);
+        }
+        let params = Parameters {
+            givens: Some(81),
+            ..Parameters::default()
+        };
+        assert!(params.check_given_counts().is_ok());
+    }
+
+    #[test]
+    fn too_few_levels_for_distinct_techniques_is_an_error() {
+        // Level 0 only holds the givens, so a single level has no rules to fire.
+        let params = Parameters {
+            inference_levels: 1,
+            min_distinct_techniques: 1,
+            ..Parameters::default()
+        };
+        let error = emit_formula(&params, FormulaFormat::Dimacs)
+            .err()
+            .expect("emitting should fail");
+        assert!(error.to_string().contains("--min_distinct_techniques is 1"));
+    }
+
+    #[test]
+    fn assume_unique_off_rejects_uniqueness_rules() {
+        // No implemented rule relies on a unique solution yet, so W-wing stands in for one.
+        let inferences = Inferences {
+            w_wing: true,
+            ..Inferences::default()
+        };
+        assert!(inferences
+            .check_uniqueness_rules(&[Rule::WWing], true)
+            .is_ok());
+        let error = inferences
+            .check_uniqueness_rules(&[Rule::WWing], false)
+            .unwrap_err();
+        assert!(error.to_string().contains("w_wing"));
+        assert!(Inferences::default()
+            .check_uniqueness_rules(&[Rule::WWing], false)
+            .is_ok());
+
+        assert!(inferences.check_assume_unique(false).is_ok());
+    }
+
+    #[tokio::test]
+    async fn max_clause_count_stops_emission() {
+        let mut params = Parameters {
+            givens: Some(30),
+            inference_levels: 3,
+            max_clause_count: Some(10_000),
+            allowed_inferences: Inferences {
+                naked_single: true,
+                hidden_single: true,
+                ..Inferences::default()
+            },
+            ..Parameters::default()
+        };
+        let error = build_formula(&mut sink(), &params).await.unwrap_err();
+        assert!(error.to_string().contains("--max_clause_count 10000"));
+
+        params.max_clause_count = None;
+        assert!(build_formula(&mut sink(), &params).await.is_ok());
+
+        let stats = formula_stats(&params).unwrap();
+        let (formula, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+        assert_eq!(
+            vec![
+                "rules of sudoku",
+                "givens and level 0",
+                "level 1: singles and carry-over",
+                "level 2: singles and carry-over",
+                "technique count and final level",
+            ],
+            stats
+                .stages
+                .iter()
+                .map(|(stage, _)| stage.as_str())
+                .collect::<Vec<_>>(),
+        );
+        assert_eq!(formula.clause_count(), stats.stages.last().unwrap().1);
+
+        // With dedup, the stats report how many repeated clauses were dropped.
+        params.dedup_clauses = true;
+        let (_, stats) = build_formula(&mut sink(), &params).await.unwrap();
+        let (deduped, _) = emit_formula(&params, FormulaFormat::Dimacs).unwrap();
+        assert_eq!(
+            formula.clause_count() - deduped.clause_count(),
+            stats.duplicates_removed
+        );
+    }
 }
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use crate::emit_problem::{confined, Eliminations, Formula};
+use crate::formula_builder::{FormulaBuilder, GateFormulaBuilder, Literal};
+use crate::sudoku::{Cell, Digit, DigitSet, VariableKind};
+
+/// RULE: REMOTE PAIRS
+///
+/// Cells confined to the same two candidates `{x, y}` that see each other must hold different
+/// digits, so a chain of them alternates between `x` and `y`. Any cell that sees one chain cell an
+/// even number of links from some origin and another an odd number of links away sees both `x`
+/// and `y`, so both are eliminated from it.
+///
+/// Chains are explored from every cell as an origin out to `max_links` links. Each step of that
+/// exploration is a `VariableKind::RemotePair` variable, so the cost of the rule grows linearly
+/// with the bound and is substantial even for small bounds.
+pub fn emit_remote_pairs(
+    formula: &mut Formula,
+    prev_level: usize,
+    max_links: usize,
+    eliminations: &mut Eliminations,
+) {
+    for (i, x) in Digit::values().enumerate() {
+        for y in Digit::values().skip(i + 1) {
+            let digits = [x, y].iter().copied().collect();
+            emit_remote_pairs_for_digits(formula, prev_level, max_links, digits, eliminations);
+        }
+    }
+}
+
+pub fn emit_remote_pairs_for_digits(
+    formula: &mut Formula,
+    prev_level: usize,
+    max_links: usize,
+    digits: DigitSet,
+    eliminations: &mut Eliminations,
+) {
+    let cells: Vec<_> = Cell::values().collect();
+    let confined: HashMap<_, _> = cells
+        .iter()
+        .copied()
+        .map(|cell| (cell, confined(formula, cell, digits, prev_level)))
+        .collect();
+
+    for origin in cells.iter().copied() {
+        // Cells reachable from the origin, keyed by cell and whether they are an odd number of
+        // links away.
+        let mut reach: HashMap<(Cell, bool), Literal> = HashMap::new();
+        reach.insert(
+            (origin, false),
+            remote_pair_variable(formula, origin, origin, digits, false, 0, prev_level),
+        );
+        formula.add_logical_equivalence_constraint(reach[&(origin, false)], confined[&origin]);
+
+        for links in 1..=max_links {
+            let mut next_reach = HashMap::new();
+            for cell in cells.iter().copied() {
+                for odd in [false, true].iter().copied() {
+                    let mut justifications = Vec::new();
+                    if let Some(literal) = reach.get(&(cell, odd)) {
+                        justifications.push(*literal);
+                    }
+                    let neighbors: Vec<_> = cells
+                        .iter()
+                        .copied()
+                        .filter(|neighbor| neighbor.sees_other(cell))
+                        .filter_map(|neighbor| reach.get(&(neighbor, !odd)).copied())
+                        .collect();
+                    if !neighbors.is_empty() {
+                        let any_neighbor = formula.new_variable().as_positive();
+                        formula.add_logical_or_constraint(any_neighbor, &neighbors);
+                        let extended = formula.new_variable().as_positive();
+                        formula
+                            .add_logical_and_constraint(extended, &[any_neighbor, confined[&cell]]);
+                        justifications.push(extended);
+                    }
+                    if justifications.is_empty() {
+                        continue;
+                    }
+
+                    let literal =
+                        remote_pair_variable(formula, origin, cell, digits, odd, links, prev_level);
+                    formula.add_logical_or_constraint(literal, &justifications);
+                    next_reach.insert((cell, odd), literal);
+                }
+            }
+            reach = next_reach;
+        }
+
+        for target in cells.iter().copied() {
+            let mut sees_parity = |odd: bool| -> Option<Literal> {
+                let literals: Vec<_> = cells
+                    .iter()
+                    .copied()
+                    .filter(|cell| target.sees_other(*cell))
+                    .filter_map(|cell| reach.get(&(cell, odd)).copied())
+                    .collect();
+                if literals.is_empty() {
+                    return None;
+                }
+                let literal = formula.new_variable().as_positive();
+                formula.add_logical_or_constraint(literal, &literals);
+                Some(literal)
+            };
+            if let (Some(sees_even), Some(sees_odd)) = (sees_parity(false), sees_parity(true)) {
+                let justification = formula.new_variable().as_positive();
+                formula.add_logical_and_constraint(justification, &[sees_even, sees_odd]);
+                for digit in digits.iter() {
+                    eliminations
+                        .entry((target, digit))
+                        .or_default()
+                        .push(justification);
+                }
+            }
+        }
+    }
+}
+
+fn remote_pair_variable(
+    formula: &mut Formula,
+    origin: Cell,
+    cell: Cell,
+    digits: DigitSet,
+    odd: bool,
+    links: usize,
+    level: usize,
+) -> Literal {
+    formula
+        .get_variable(VariableKind::RemotePair {
+            origin_row: origin.row,
+            origin_col: origin.col,
+            row: cell.row,
+            col: cell.col,
+            digits,
+            odd,
+            links,
+            level,
+        })
+        .as_positive()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::emit_remote_pairs_for_digits;
+    use crate::emit_problem::{fired, Eliminations, Formula};
+    use crate::sudoku::{Cell, Col, Digit, Row};
+
+    fn cell(row: u8, col: u8) -> Cell {
+        Cell {
+            row: Row::new(row).unwrap(),
+            col: Col::new(col).unwrap(),
+        }
+    }
+
+    #[test]
+    fn remote_pairs_eliminate_from_cells_seeing_both_colors() {
+        let mut formula = Formula::default();
+        let mut eliminations = Eliminations::new();
+        let digits = [Digit::K1, Digit::K2].iter().copied().collect();
+        emit_remote_pairs_for_digits(&mut formula, 0, 3, digits, &mut eliminations);
+
+        // A chain of {1, 2} cells: r1c1 -(row)- r1c5 -(box)- r3c6 -(column)- r9c6.
+        let even = [cell(1, 1), cell(3, 6)];
+        let odd = [cell(1, 5), cell(9, 6)];
+        let actual = fired(&mut formula, &eliminations, |cell, digit| {
+            (even.contains(&cell) || odd.contains(&cell)) && digit.as_u8() > 2
+        });
+        let expected: HashSet<_> = Cell::values()
+            .filter(|target| {
+                even.iter().any(|cell| target.sees_other(*cell))
+                    && odd.iter().any(|cell| target.sees_other(*cell))
+            })
+            .flat_map(|target| vec![(target, Digit::K1), (target, Digit::K2)])
+            .collect();
+        assert_eq!(expected, actual);
+
+        // r9c1 sees only the two ends of the chain, so no shorter pattern explains it.
+        assert!(actual.contains(&(cell(9, 1), Digit::K1)));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::emit_problem::{eliminated, Eliminations, Formula};
+use crate::formula_builder::{FormulaBuilder, GateFormulaBuilder};
+use crate::subsets::subsets;
+use crate::sudoku::{Cell, Col, Digit, Row};
+
+/// RULE: FISH
+///
+/// Some `size` rows confine a digit to the same `size` columns between them. Each of those rows
+/// holds the digit once, so together they fill every one of the columns with it, and the digit is
+/// eliminated from those columns in all other rows. The same holds with rows and columns swapped.
+/// A size of two is an X-wing, and a size of four is a jellyfish.
+pub fn emit_fish(
+    formula: &mut Formula,
+    prev_level: usize,
+    size: usize,
+    eliminations: &mut Eliminations,
+) {
+    for digit in Digit::values() {
+        emit_fish_for_digit(formula, prev_level, size, digit, eliminations);
+    }
+}
+
+fn emit_fish_for_digit(
+    formula: &mut Formula,
+    prev_level: usize,
+    size: usize,
+    digit: Digit,
+    eliminations: &mut Eliminations,
+) {
+    let indices: Vec<u8> = Row::values().map(Row::as_u8).collect();
+    let index_subsets = subsets(&indices, size);
+
+    for &base_is_row in &[true, false] {
+        let cell = |base: u8, cover: u8| {
+            let (row, col) = if base_is_row {
+                (base, cover)
+            } else {
+                (cover, base)
+            };
+            Cell {
+                row: Row::new(row).unwrap(),
+                col: Col::new(col).unwrap(),
+            }
+        };
+
+        // Whether a base line confines the digit to a set of cover lines is shared by every fish
+        // using that line, so cache it by base line and cover set.
+        let mut confinements = HashMap::new();
+
+        for base_lines in &index_subsets {
+            // Many fish share the same base lines and cover a given line, so first collect, for
+            // each cover line, whether any fish on these base lines covers it.
+            let mut covers = vec![Vec::new(); indices.len()];
+            for cover_lines in &index_subsets {
+                let literals: Vec<_> = base_lines
+                    .iter()
+                    .map(|&base| {
+                        *confinements
+                            .entry((base, cover_lines.clone()))
+                            .or_insert_with(|| {
+                                let literals: Vec<_> = indices
+                                    .iter()
+                                    .filter(|cover| !cover_lines.contains(cover))
+                                    .map(|&cover| {
+                                        eliminated(formula, cell(base, cover), digit, prev_level)
+                                    })
+                                    .collect();
+                                let confined = formula.new_variable().as_positive();
+                                formula.add_logical_and_constraint(confined, &literals);
+                                confined
+                            })
+                    })
+                    .collect();
+                let fish = formula.new_variable().as_positive();
+                formula.add_logical_and_constraint(fish, &literals);
+                for &cover in cover_lines {
+                    covers[cover as usize - 1].push(fish);
+                }
+            }
+
+            for &cover in &indices {
+                let covered = formula.new_variable().as_positive();
+                formula.add_logical_or_constraint(covered, &covers[cover as usize - 1]);
+                for base in indices.iter().filter(|base| !base_lines.contains(base)) {
+                    eliminations
+                        .entry((cell(*base, cover), digit))
+                        .or_default()
+                        .push(covered);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::emit_fish_for_digit;
+    use crate::emit_problem::{fired, Eliminations, Formula};
+    use crate::sudoku::{Cell, Digit};
+
+    #[test]
+    fn fish_eliminate_from_cover_columns() {
+        for &size in &[2, 4] {
+            let mut formula = Formula::default();
+            let mut eliminations = Eliminations::new();
+            emit_fish_for_digit(&mut formula, 0, size, Digit::K1, &mut eliminations);
+
+            // The first `size` rows confine 1 to the first `size` columns. Every other candidate
+            // is still open.
+            let size = size as u8;
+            let actual = fired(&mut formula, &eliminations, |cell, digit| {
+                digit == Digit::K1 && cell.row.as_u8() <= size && cell.col.as_u8() > size
+            });
+            let expected: HashSet<_> = Cell::values()
+                .filter(|cell| cell.row.as_u8() > size && cell.col.as_u8() <= size)
+                .map(|cell| (cell, Digit::K1))
+                .collect();
+            assert_eq!(expected, actual, "size {}", size);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::emit_problem::{eliminated, Eliminations, Formula};
+use crate::formula_builder::{FormulaBuilder, GateFormulaBuilder};
+use crate::sudoku::{Box, Cell, Digit, House};
+
+/// RULE: POINTING
+///
+/// Within a box, a digit is confined to the cells it shares with one row or column. The digit must
+/// go in one of those cells, so it is eliminated from the rest of that row or column.
+pub fn emit_pointing(formula: &mut Formula, prev_level: usize, eliminations: &mut Eliminations) {
+    for (box_, line) in box_lines() {
+        emit_intersection(formula, prev_level, &box_, &line, eliminations);
+    }
+}
+
+/// RULE: CLAIMING
+///
+/// Within a row or column, a digit is confined to the cells it shares with one box. The digit must
+/// go in one of those cells, so it is eliminated from the rest of that box.
+pub fn emit_claiming(formula: &mut Formula, prev_level: usize, eliminations: &mut Eliminations) {
+    for (box_, line) in box_lines() {
+        emit_intersection(formula, prev_level, &line, &box_, eliminations);
+    }
+}
+
+/// The cells of every box paired with the cells of each row and column crossing it.
+fn box_lines() -> Vec<(Vec<Cell>, Vec<Cell>)> {
+    Box::values()
+        .flat_map(|box_| {
+            box_.rows()
+                .map(House::Row)
+                .chain(box_.cols().map(House::Col))
+                .map(move |line| (House::Box(box_).cells(), line.cells()))
+        })
+        .collect()
+}
+
+/// Eliminates each digit from `target` outside of `source` when `source` confines it to their
+/// intersection.
+fn emit_intersection(
+    formula: &mut Formula,
+    prev_level: usize,
+    source: &[Cell],
+    target: &[Cell],
+    eliminations: &mut Eliminations,
+) {
+    for digit in Digit::values() {
+        let literals: Vec<_> = source
+            .iter()
+            .filter(|cell| !target.contains(cell))
+            .map(|&cell| eliminated(formula, cell, digit, prev_level))
+            .collect();
+        let confined = formula.new_variable().as_positive();
+        formula.add_logical_and_constraint(confined, &literals);
+        for cell in target.iter().filter(|cell| !source.contains(cell)) {
+            eliminations
+                .entry((*cell, digit))
+                .or_default()
+                .push(confined);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{emit_claiming, emit_pointing};
+    use crate::emit_problem::{fired, Eliminations, Formula};
+    use crate::sudoku::{Box, Cell, Digit, Row};
+
+    #[test]
+    fn pointing_eliminates_along_the_row() {
+        let mut formula = Formula::default();
+        let mut eliminations = Eliminations::new();
+        emit_pointing(&mut formula, 0, &mut eliminations);
+
+        // Box 1 confines 1 to row 1.
+        let actual = fired(&mut formula, &eliminations, |cell, digit| {
+            digit == Digit::K1 && cell.box_() == Box::K1 && cell.row != Row::K1
+        });
+        let expected: HashSet<_> = Cell::values()
+            .filter(|cell| cell.row == Row::K1 && cell.box_() != Box::K1)
+            .map(|cell| (cell, Digit::K1))
+            .collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn claiming_eliminates_within_the_box() {
+        let mut formula = Formula::default();
+        let mut eliminations = Eliminations::new();
+        emit_claiming(&mut formula, 0, &mut eliminations);
+
+        // Row 1 confines 1 to box 1.
+        let actual = fired(&mut formula, &eliminations, |cell, digit| {
+            digit == Digit::K1 && cell.row == Row::K1 && cell.box_() != Box::K1
+        });
+        let expected: HashSet<_> = Cell::values()
+            .filter(|cell| cell.box_() == Box::K1 && cell.row != Row::K1)
+            .map(|cell| (cell, Digit::K1))
+            .collect();
+        assert_eq!(expected, actual);
+    }
+}
@@ -0,0 +1,126 @@
+use crate::emit_problem::{confined, Eliminations, Formula};
+use crate::formula_builder::{FormulaBuilder, GateFormulaBuilder};
+use crate::subsets::subsets;
+use crate::sudoku::{houses, Cell, Digit, DigitSet};
+
+/// RULE: NAKED SUBSET
+///
+/// Some `size` cells of a house are confined to the same `size` candidates between them. Those
+/// cells must hold all of those digits, so the digits are eliminated from every other cell of the
+/// house. Sizes two, three, and four are naked pairs, triples, and quads.
+pub fn emit_naked_subsets(
+    formula: &mut Formula,
+    prev_level: usize,
+    size: usize,
+    eliminations: &mut Eliminations,
+) {
+    for house in houses() {
+        emit_naked_subset(formula, &house, size, prev_level, eliminations);
+    }
+}
+
+/// Emits naked subsets of `size` cells within the one house `house`. Subsets are only formed from
+/// cells sharing a house, which keeps the number of gates bounded.
+fn emit_naked_subset(
+    formula: &mut Formula,
+    house: &[Cell],
+    size: usize,
+    prev_level: usize,
+    eliminations: &mut Eliminations,
+) {
+    let digits: Vec<_> = Digit::values().collect();
+    let digit_subsets: Vec<DigitSet> = subsets(&digits, size)
+        .into_iter()
+        .map(|digits| digits.into_iter().collect())
+        .collect();
+
+    for cells in subsets(house, size) {
+        for digits in digit_subsets.iter().copied() {
+            let literals: Vec<_> = cells
+                .iter()
+                .map(|&cell| confined(formula, cell, digits, prev_level))
+                .collect();
+            let subset = formula.new_variable().as_positive();
+            formula.add_logical_and_constraint(subset, &literals);
+            for other_cell in house.iter().filter(|cell| !cells.contains(cell)) {
+                for digit in digits.iter() {
+                    eliminations
+                        .entry((*other_cell, digit))
+                        .or_default()
+                        .push(subset);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{emit_naked_subset, emit_naked_subsets};
+    use crate::emit_problem::{fired, Eliminations, Formula};
+    use crate::sudoku::{Cell, Col, Digit, House, Row};
+
+    fn cell(row: u8, col: u8) -> Cell {
+        Cell {
+            row: Row::new(row).unwrap(),
+            col: Col::new(col).unwrap(),
+        }
+    }
+
+    /// Assumes the first `confined` cells of row 1 are confined to digits 1 through `confined`, with
+    /// every other candidate open, and returns the placements that `eliminations` eliminates.
+    fn fired_in_row_1(
+        formula: &mut Formula,
+        eliminations: &Eliminations,
+        confined: u8,
+    ) -> HashSet<(Cell, Digit)> {
+        fired(formula, eliminations, |cell, digit| {
+            cell.row == Row::K1 && cell.col.as_u8() <= confined && digit.as_u8() > confined
+        })
+    }
+
+    #[test]
+    fn naked_pair_eliminates_from_shared_houses() {
+        let mut formula = Formula::default();
+        let mut eliminations = Eliminations::new();
+        emit_naked_subsets(&mut formula, 0, 2, &mut eliminations);
+
+        // The pair r1c1 and r1c2 shares row 1 and box 1, so 1 and 2 leave the rest of both.
+        let actual = fired_in_row_1(&mut formula, &eliminations, 2);
+        let expected: HashSet<_> = Cell::values()
+            .filter(|other| other.sees_other(cell(1, 1)) && other.sees_other(cell(1, 2)))
+            .flat_map(|other| vec![(other, Digit::K1), (other, Digit::K2)])
+            .collect();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn each_size_fires_only_on_its_own_subsets() {
+        let row = House::Row(Row::K1).cells();
+        for size in 2..=4 {
+            for confined in 2..=4 {
+                let mut formula = Formula::default();
+                let mut eliminations = Eliminations::new();
+                emit_naked_subset(&mut formula, &row, size, 0, &mut eliminations);
+
+                let actual = fired_in_row_1(&mut formula, &eliminations, confined as u8);
+                let expected: HashSet<_> = if size == confined {
+                    row.iter()
+                        .copied()
+                        .filter(|cell| cell.col.as_u8() > confined as u8)
+                        .flat_map(|cell| {
+                            Digit::values()
+                                .filter(|digit| digit.as_u8() <= confined as u8)
+                                .map(move |digit| (cell, digit))
+                        })
+                        .collect()
+                } else {
+                    HashSet::new()
+                };
+                assert_eq!(expected, actual, "size {}, confined {}", size, confined);
+            }
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::emit_problem::Parameters;
+use crate::formula_builder::Variable;
+use crate::rule::Rule;
+use crate::sat_solver::Solution;
+use crate::solve::solve;
+use crate::sudoku::{Cell, Digit, VariableKind};
+
+/// Finds a minimal set of the allowed inference rules that still solves the puzzle with the given
+/// placements. Rules are greedily disabled one at a time in a fixed order, keeping each one
+/// disabled if the puzzle stays solvable without it, so this takes one solve per enabled rule.
+/// The result is minimal, but not necessarily the smallest such set.
+pub async fn minimal_rules(
+    params: &Parameters,
+    givens: HashMap<Cell, Digit>,
+    timeout_duration: Option<Duration>,
+) -> Result<Vec<Rule>> {
+    minimal_rules_with(params, givens, |params| async move {
+        let (_, solution) = solve(&params, timeout_duration).await?;
+        Ok(!matches!(solution, Solution::Unsatisfiable))
+    })
+    .await
+}
+
+/// Runs the search of `minimal_rules`, asking `solvable` whether each candidate set of rules still
+/// solves the puzzle.
+async fn minimal_rules_with<S, F>(
+    params: &Parameters,
+    givens: HashMap<Cell, Digit>,
+    mut solvable: S,
+) -> Result<Vec<Rule>>
+where
+    S: FnMut(Parameters) -> F,
+    F: Future<Output = Result<bool>>,
+{
+    // The fixed givens already pin every Given variable, so there's no need to count them.
+    let mut params = Parameters {
+        givens: None,
+        min_givens: None,
+        max_givens: None,
+        min_distinct_techniques: 0,
+        fixed_givens: Some(givens),
+        ..params.clone()
+    };
+    for rule in params.allowed_inferences.enabled_rules() {
+        params.allowed_inferences.set(rule, false);
+        if !solvable(params.clone()).await? {
+            params.allowed_inferences.set(rule, true);
+        }
+    }
+    Ok(params.allowed_inferences.enabled_rules())
+}
+
+fn join_rules(rules: &[Rule]) -> String {
+    let names: Vec<_> = rules.iter().map(|rule| rule.name()).collect();
+    names.join(", ")
+}
+
+/// Prints the techniques from a minimal set of the enabled rules that still solves the generated
+/// puzzle, for `--classify`.
+pub async fn classify(
+    params: &Parameters,
+    variables: &HashMap<VariableKind, Variable>,
+    solution: &Solution,
+    timeout_duration: Option<Duration>,
+) -> Result<()> {
+    if let Solution::Unsatisfiable = solution {
+        return Ok(());
+    }
+
+    let givens = solution
+        .to_grid(variables)?
+        .givens(&solution.givens_mask(variables)?);
+    let needed = minimal_rules(params, givens, timeout_duration).await?;
+    println!("Techniques: {}", join_rules(&needed));
+    Ok(())
+}
+
+pub async fn explain_rules(
+    params: &Parameters,
+    variables: &HashMap<VariableKind, Variable>,
+    solution: &Solution,
+    timeout_duration: Option<Duration>,
+) -> Result<()> {
+    if let Solution::Unsatisfiable = solution {
+        return Ok(());
+    }
+
+    let givens = solution
+        .to_grid(variables)?
+        .givens(&solution.givens_mask(variables)?);
+    let needed = minimal_rules(params, givens, timeout_duration).await?;
+    let unnecessary: Vec<_> = params
+        .allowed_inferences
+        .enabled_rules()
+        .into_iter()
+        .filter(|rule| !needed.contains(rule))
+        .collect();
+
+    let mut explanation = format!("Solvable with {{{}}}", join_rules(&needed));
+    match unnecessary.len() {
+        0 => (),
+        1 => explanation += &format!("; {} was enabled but unnecessary", unnecessary[0]),
+        _ => {
+            explanation += &format!(
+                "; {} were enabled but unnecessary",
+                join_rules(&unnecessary)
+            )
+        }
+    }
+    println!("{}.", explanation);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::minimal_rules_with;
+    use crate::emit_problem::{emit_formula, FormulaFormat, Inferences, Parameters};
+    use crate::puzzle_string::parse_puzzle_string;
+    use crate::rule::Rule;
+
+    #[tokio::test]
+    async fn naked_singles_puzzle_needs_only_naked_singles() {
+        // A solved grid with one cell emptied in each row, column, and box, so every empty cell is
+        // a naked single from the start.
+        let givens = parse_puzzle_string(
+            ".34678912672.95348198342.678.97614234268.37917139248.696.53728428741.63534528617.",
+        )
+        .unwrap();
+        let mut allowed_inferences = Inferences::default();
+        allowed_inferences.set(Rule::Pointing, true);
+        allowed_inferences.set(Rule::XWing, true);
+        allowed_inferences.set(Rule::HiddenSingle, false);
+        let params = Parameters {
+            inference_levels: 3,
+            allowed_inferences,
+            propagate: false,
+            ..Parameters::default()
+        };
+
+        let rules = minimal_rules_with(&params, givens, |params| async move {
+            let (formula, _) = emit_formula(&params, FormulaFormat::Dimacs)?;
+            Ok(formula.solve(&[]).is_some())
+        })
+        .await
+        .unwrap();
+        assert_eq!(vec![Rule::NakedSingle], rules);
+    }
+}
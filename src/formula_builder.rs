@@ -1,8 +1,8 @@
-use std::collections::HashMap;
-use std::fmt::Write;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Write};
 use std::hash::Hash;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 use crate::positive_i32::PositiveI32;
@@ -11,6 +11,7 @@ pub use arithmetic::ArithmeticFormulaBuilder;
 pub use bit_vector::BitVector;
 pub use cardinality::CardinalityFormulaBuilder;
 pub use gate::GateFormulaBuilder;
+pub use linear::{LinearConstraint, Relation};
 pub use literal::Literal;
 pub use variable::Variable;
 
@@ -18,7 +19,10 @@ mod arithmetic;
 mod bit_vector;
 mod cardinality;
 mod gate;
+mod linear;
 mod literal;
+#[cfg(any(test, feature = "incremental"))]
+mod testing;
 mod variable;
 
 pub trait FormulaBuilder {
@@ -31,6 +35,7 @@ pub trait FormulaBuilder {
     fn clause_count(&self) -> usize;
 }
 
+#[derive(Clone)]
 struct UnitClause(Literal);
 
 impl UnitClause {
@@ -42,6 +47,7 @@ impl UnitClause {
     }
 }
 
+#[derive(Clone)]
 struct BinaryClause([Literal; 2]);
 
 impl BinaryClause {
@@ -53,6 +59,7 @@ impl BinaryClause {
     }
 }
 
+#[derive(Clone)]
 struct WideClause(Vec<Literal>);
 
 impl WideClause {
@@ -70,6 +77,7 @@ impl WideClause {
     }
 }
 
+#[derive(Clone)]
 struct VariableCounter {
     highest_variable_index: u32,
 }
@@ -81,12 +89,16 @@ impl VariableCounter {
     }
 }
 
+/// Cloning a builder snapshots its clauses and variables, so a shared prefix of a formula can be
+/// built once and extended in several directions.
+#[derive(Clone)]
 pub struct TaggedVariableFormulaBuilder<T> {
     variable_counter: VariableCounter,
     tagged_variables: HashMap<T, Variable>,
     unit: Vec<UnitClause>,
     binary: Vec<BinaryClause>,
     wide: Vec<WideClause>,
+    linear: Vec<LinearConstraint>,
 }
 
 impl<T> TaggedVariableFormulaBuilder<T> {
@@ -99,6 +111,7 @@ impl<T> TaggedVariableFormulaBuilder<T> {
             unit: Default::default(),
             binary: Default::default(),
             wide: Default::default(),
+            linear: Default::default(),
         }
     }
 
@@ -110,7 +123,133 @@ impl<T> TaggedVariableFormulaBuilder<T> {
         self.tagged_variables
     }
 
+    /// How many clauses there are of each width, keyed by the number of literals.
+    pub fn clause_width_histogram(&self) -> BTreeMap<usize, usize> {
+        let mut histogram = BTreeMap::new();
+        if !self.unit.is_empty() {
+            histogram.insert(1, self.unit.len());
+        }
+        if !self.binary.is_empty() {
+            histogram.insert(2, self.binary.len());
+        }
+        for clause in &self.wide {
+            *histogram.entry(clause.0.len()).or_insert(0) += 1;
+        }
+        histogram
+    }
+
+    /// The literal of each unit clause.
+    pub fn units(&self) -> impl Iterator<Item = Literal> + '_ {
+        self.unit.iter().map(|clause| clause.0)
+    }
+
+    /// The two literals of each binary clause.
+    pub fn binaries(&self) -> impl Iterator<Item = [Literal; 2]> + '_ {
+        self.binary.iter().map(|clause| clause.0)
+    }
+
+    /// The literals of each clause wider than two.
+    pub fn wides(&self) -> impl Iterator<Item = &[Literal]> {
+        self.wide.iter().map(|clause| &clause.0[..])
+    }
+
+    /// Every clause, whatever its width, in the order `write_dimacs` writes them: unit clauses,
+    /// then binary clauses, then wider ones. Linear constraints aren't clauses and are left out.
+    pub fn clauses(&self) -> impl Iterator<Item = &[Literal]> {
+        self.unit
+            .iter()
+            .map(|clause| std::slice::from_ref(&clause.0))
+            .chain(self.binary.iter().map(|clause| &clause.0[..]))
+            .chain(self.wides())
+    }
+
+    /// Adds a constraint on how many of `literals` are true. Only OPB output can represent these.
+    pub fn add_linear_constraint(&mut self, literals: &[Literal], relation: Relation, rhs: u32) {
+        self.linear.push(LinearConstraint {
+            literals: literals.to_vec(),
+            relation,
+            rhs,
+        });
+    }
+
+    /// Checks that `assignments` satisfies every clause, returning an error naming the first clause
+    /// it violates. A variable missing from `assignments` counts as a violation, since a complete
+    /// model assigns every variable.
+    pub fn check_model(&self, assignments: &HashMap<Variable, bool>) -> Result<()> {
+        for clause in self.clauses() {
+            let mut satisfied = false;
+            for literal in clause {
+                match assignments.get(&literal.variable()) {
+                    Some(&value) => satisfied |= value == literal.is_positive(),
+                    None => {
+                        return Err(anyhow!(
+                            "the model doesn't assign variable {}",
+                            literal.variable().index().as_i32(),
+                        ))
+                    }
+                }
+            }
+            if !satisfied {
+                let indices: Vec<_> = clause.iter().map(|literal| literal.index()).collect();
+                return Err(anyhow!("the model violates the clause {:?}", indices));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the formula is consistent with its DIMACS header: every literal's variable is
+    /// within the variable count, and no clause is empty.
+    pub fn validate(&self) -> Result<()> {
+        for clause in self.clauses() {
+            if clause.is_empty() {
+                return Err(anyhow!("the formula has an empty clause"));
+            }
+            for literal in clause {
+                let index = literal.variable().index().as_i32() as u32;
+                if index > self.variable_counter.highest_variable_index {
+                    let indices: Vec<_> = clause.iter().map(|literal| literal.index()).collect();
+                    return Err(anyhow!(
+                        "the clause {:?} uses variable {}, but the formula has only {}",
+                        indices,
+                        index,
+                        self.variable_counter.highest_variable_index,
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops every clause that repeats an earlier one, ignoring the order of literals, and returns
+    /// how many were dropped.
+    pub fn dedup_clauses(&mut self) -> usize {
+        let before = self.clause_count();
+        let mut seen = HashSet::new();
+        let mut first = |literals: &[Literal]| {
+            let mut key: Vec<_> = literals
+                .iter()
+                .map(|literal| literal.index().get())
+                .collect();
+            key.sort_unstable();
+            seen.insert(key)
+        };
+        self.unit
+            .retain(|clause| first(std::slice::from_ref(&clause.0)));
+        self.binary.retain(|clause| first(&clause.0));
+        self.wide.retain(|clause| first(&clause.0));
+        before - self.clause_count()
+    }
+
     pub async fn write_dimacs<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<()> {
+        if !self.linear.is_empty() {
+            return Err(anyhow!(
+                "the formula has linear constraints, which DIMACS can't represent"
+            ));
+        }
+        if cfg!(debug_assertions) {
+            self.validate()?;
+        }
+
         let mut buf = String::new();
         writeln!(
             &mut buf,
@@ -131,6 +270,99 @@ impl<T> TaggedVariableFormulaBuilder<T> {
         }
         Ok(())
     }
+
+    /// Like `write_dimacs`, but first writes a `c tag <index> <tag>` comment line for each tagged
+    /// variable, in order of index, so the output describes what its variables mean.
+    pub async fn write_dimacs_with_tags<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<()>
+    where
+        T: Display,
+    {
+        let mut tags: Vec<_> = self
+            .tagged_variables
+            .iter()
+            .map(|(tag, variable)| (variable.index(), tag))
+            .collect();
+        tags.sort_by_key(|&(index, _)| index);
+        let mut buf = String::new();
+        for (index, tag) in tags {
+            writeln!(&mut buf, "c tag {} {}", index.as_i32(), tag)?;
+        }
+        w.write_all(buf.as_bytes()).await?;
+        self.write_dimacs(w).await
+    }
+
+    /// Writes the formula in the weighted DIMACS format read by MaxSAT solvers. Every clause is
+    /// hard, and each of `soft_literals` becomes a unit soft clause of weight one, so the solver
+    /// maximizes how many of them hold.
+    pub async fn write_wcnf<W: AsyncWrite + Unpin>(
+        &self,
+        w: &mut W,
+        soft_literals: &[Literal],
+    ) -> Result<()> {
+        if !self.linear.is_empty() {
+            return Err(anyhow!(
+                "the formula has linear constraints, which WCNF can't represent"
+            ));
+        }
+
+        // Hard clauses are marked by a weight above the total of all soft clause weights.
+        let top = soft_literals.len() + 1;
+        let mut buf = String::new();
+        writeln!(
+            &mut buf,
+            "p wcnf {} {} {}",
+            self.variable_counter.highest_variable_index,
+            self.clause_count() + soft_literals.len(),
+            top,
+        )?;
+        w.write_all(buf.as_bytes()).await?;
+
+        let prefix = format!("{} ", top);
+        for clause in &self.unit {
+            w.write_all(prefix.as_bytes()).await?;
+            clause.write_dimacs_fragment(w).await?;
+        }
+        for clause in &self.binary {
+            w.write_all(prefix.as_bytes()).await?;
+            clause.write_dimacs_fragment(w).await?;
+        }
+        for clause in &self.wide {
+            w.write_all(prefix.as_bytes()).await?;
+            clause.write_dimacs_fragment(w).await?;
+        }
+        for &literal in soft_literals {
+            w.write_all(b"1 ").await?;
+            UnitClause(literal).write_dimacs_fragment(w).await?;
+        }
+        Ok(())
+    }
+
+    /// Writes the formula in the OPB pseudo-Boolean format, with each clause as an at-least-one
+    /// constraint alongside the linear constraints.
+    pub async fn write_opb<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<()> {
+        let mut buf = String::new();
+        writeln!(
+            &mut buf,
+            "* #variable= {} #constraint= {}",
+            self.variable_counter.highest_variable_index,
+            self.clause_count() + self.linear.len()
+        )?;
+        w.write_all(buf.as_bytes()).await?;
+
+        for clause in self.clauses() {
+            LinearConstraint {
+                literals: clause.to_vec(),
+                relation: Relation::AtLeast,
+                rhs: 1,
+            }
+            .write_opb_fragment(w)
+            .await?;
+        }
+        for constraint in &self.linear {
+            constraint.write_opb_fragment(w).await?;
+        }
+        Ok(())
+    }
 }
 
 impl<T> TaggedVariableFormulaBuilder<T>
@@ -144,6 +376,21 @@ where
             .entry(tag)
             .or_insert_with(move || variable_counter.new_variable())
     }
+
+    /// Like `get_variable`, but calls `define` to add the variable's defining constraints the
+    /// first time the tag is seen. Later calls with an equal tag reuse the variable as-is.
+    pub fn get_defined_variable(
+        &mut self,
+        tag: T,
+        define: impl FnOnce(&mut Self, Variable),
+    ) -> Variable {
+        if let Some(variable) = self.tagged_variables.get(&tag) {
+            return *variable;
+        }
+        let variable = self.get_variable(tag);
+        define(self, variable);
+        variable
+    }
 }
 
 impl<T> FormulaBuilder for TaggedVariableFormulaBuilder<T> {
@@ -151,7 +398,11 @@ impl<T> FormulaBuilder for TaggedVariableFormulaBuilder<T> {
         self.variable_counter.new_variable()
     }
 
+    /// Adds the clause unless it holds a literal and its negation, since that is always satisfied.
     fn add_clause(&mut self, literals: Vec<Literal>) {
+        if is_tautology(&literals) {
+            return;
+        }
         match &*literals {
             [] => panic!(),
             &[a] => self.add_unit_clause(a),
@@ -165,6 +416,9 @@ impl<T> FormulaBuilder for TaggedVariableFormulaBuilder<T> {
     }
 
     fn add_binary_clause(&mut self, a: Literal, b: Literal) {
+        if is_tautology(&[a, b]) {
+            return;
+        }
         self.binary.push(BinaryClause([a, b]));
     }
 
@@ -177,8 +431,230 @@ impl<T> FormulaBuilder for TaggedVariableFormulaBuilder<T> {
     }
 }
 
+/// Whether the clause holds some literal and its negation.
+fn is_tautology(literals: &[Literal]) -> bool {
+    literals.iter().enumerate().any(|(i, a)| {
+        literals[i + 1..]
+            .iter()
+            .any(|b| a.index().get() == -b.index().get())
+    })
+}
+
 impl<T> Default for TaggedVariableFormulaBuilder<T> {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{FormulaBuilder, Literal, TaggedVariableFormulaBuilder};
+
+    #[test]
+    fn check_model_finds_violated_clauses() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        let c = formula.get_variable(2).as_positive();
+        formula.add_unit_clause(a);
+        formula.add_binary_clause(-a, b);
+        formula.add_clause(vec![-b, c, -a]);
+
+        let mut model = formula.solve(&[]).unwrap();
+        assert!(formula.check_model(&model).is_ok());
+        model.insert(c.variable(), false);
+        assert!(formula.check_model(&model).is_err());
+        model.remove(&c.variable());
+        assert!(formula.check_model(&model).is_err());
+    }
+
+    #[tokio::test]
+    async fn validate_catches_literals_past_the_variable_count() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        formula.add_unit_clause(a);
+        assert!(formula.validate().is_ok());
+
+        // A literal from a bigger formula names a variable this one never allocated.
+        let mut other = TaggedVariableFormulaBuilder::<usize>::default();
+        other.get_variable(0);
+        let stray = other.get_variable(1).as_positive();
+        formula.add_binary_clause(a, stray);
+        let error = formula.validate().unwrap_err();
+        assert!(error.to_string().contains("uses variable 2"), "{}", error);
+        if cfg!(debug_assertions) {
+            assert!(formula.write_dimacs(&mut Vec::new()).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn tautologies_are_not_written() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        formula.add_clause(vec![a, -a, b]);
+        formula.add_binary_clause(-b, b);
+        formula.add_clause(vec![a, b, -b]);
+        assert_eq!(0, formula.clause_count());
+
+        // Clauses repeating a literal without its negation are kept, as are units and binaries.
+        formula.add_clause(vec![a, b, a]);
+        formula.add_clause(vec![-a]);
+        formula.add_binary_clause(a, b);
+        assert_eq!(3, formula.clause_count());
+
+        let mut dimacs = Vec::new();
+        formula.write_dimacs(&mut dimacs).await.unwrap();
+        assert_eq!(
+            "p cnf 2 3\n-1 0\n1 2 0\n1 2 1 0\n",
+            String::from_utf8(dimacs).unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn clauses_match_the_dimacs_output() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        let c = formula.get_variable(2).as_positive();
+        formula.add_clause(vec![a, -b, c]);
+        formula.add_binary_clause(-a, b);
+        formula.add_unit_clause(c);
+        formula.add_clause(vec![b, c]);
+        formula.add_clause(vec![-c, a, b, -a]);
+        formula.add_clause(vec![-c, a, b]);
+
+        let index = |literal: Literal| literal.index().get();
+        assert_eq!(
+            vec![index(c)],
+            formula.units().map(index).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![[index(-a), index(b)], [index(b), index(c)]],
+            formula
+                .binaries()
+                .map(|[x, y]| [index(x), index(y)])
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(2, formula.wides().count());
+        assert_eq!(formula.clause_count(), formula.clauses().count());
+
+        let mut dimacs = Vec::new();
+        formula.write_dimacs(&mut dimacs).await.unwrap();
+        let dimacs = String::from_utf8(dimacs).unwrap();
+        let printed: Vec<_> = formula
+            .clauses()
+            .map(|clause| {
+                let mut line: Vec<_> = clause
+                    .iter()
+                    .map(|literal| literal.index().to_string())
+                    .collect();
+                line.push("0".to_string());
+                line.join(" ")
+            })
+            .collect();
+        assert_eq!(dimacs.lines().skip(1).collect::<Vec<_>>(), printed);
+    }
+
+    #[test]
+    fn dedup_clauses_keeps_one_of_each_clause() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        let c = formula.get_variable(2).as_positive();
+        formula.add_binary_clause(-a, b);
+        formula.add_binary_clause(-a, b);
+        formula.add_binary_clause(b, -a);
+        formula.add_binary_clause(a, b);
+        formula.add_clause(vec![a, b, c]);
+        formula.add_clause(vec![c, a, b]);
+        formula.add_unit_clause(c);
+        formula.add_unit_clause(c);
+
+        assert_eq!(4, formula.dedup_clauses());
+        assert_eq!(4, formula.clause_count());
+        assert_eq!(0, formula.dedup_clauses());
+    }
+
+    #[test]
+    fn clause_width_histogram_counts_each_width() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let literals: Vec<_> = (0..4)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        formula.add_clause(vec![literals[0]]);
+        formula.add_clause(literals[..3].to_vec());
+        formula.add_clause(literals[1..].to_vec());
+        formula.add_clause(literals.clone());
+
+        let histogram: Vec<_> = formula.clause_width_histogram().into_iter().collect();
+        assert_eq!(vec![(1, 1), (3, 2), (4, 1)], histogram);
+    }
+
+    #[tokio::test]
+    async fn tag_comments_match_the_tag_map() {
+        let mut formula = TaggedVariableFormulaBuilder::<String>::default();
+        let a = formula.get_variable("a".to_string()).as_positive();
+        let untagged = formula.new_variable().as_positive();
+        let b = formula.get_variable("b".to_string()).as_positive();
+        formula.add_binary_clause(a, untagged);
+        formula.add_binary_clause(-b, untagged);
+
+        let mut output = Vec::new();
+        formula.write_dimacs_with_tags(&mut output).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let tags: HashMap<_, _> = output
+            .lines()
+            .filter_map(|line| line.strip_prefix("c tag "))
+            .map(|line| {
+                let (index, tag) = line.split_once(' ').unwrap();
+                (tag.to_string(), index.parse::<i32>().unwrap())
+            })
+            .collect();
+        let expected: HashMap<_, _> = formula
+            .tagged_variables()
+            .iter()
+            .map(|(tag, variable)| (tag.clone(), variable.index().as_i32()))
+            .collect();
+        assert_eq!(expected, tags);
+        assert!(output.starts_with("c tag 1 a\nc tag 3 b\np cnf 3 2\n"));
+    }
+
+    #[tokio::test]
+    async fn wcnf_top_weight_exceeds_total_soft_weight() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let literals: Vec<_> = (0..3)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        formula.add_unit_clause(literals[0]);
+        formula.add_binary_clause(literals[0], literals[1]);
+        formula.add_clause(literals.clone());
+
+        let soft: Vec<_> = literals.iter().map(|&literal| -literal).collect();
+        let mut output = Vec::new();
+        formula.write_wcnf(&mut output, &soft).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(
+            vec![
+                "p wcnf 3 6 4",
+                "4 1 0",
+                "4 1 2 0",
+                "4 1 2 3 0",
+                "1 -1 0",
+                "1 -2 0",
+                "1 -3 0",
+            ],
+            lines,
+        );
+
+        // With nothing soft, every clause still needs a weight above zero.
+        let mut output = Vec::new();
+        formula.write_wcnf(&mut output, &[]).await.unwrap();
+        assert!(String::from_utf8(output)
+            .unwrap()
+            .starts_with("p wcnf 3 3 1\n1 1 0\n"));
+    }
+}
@@ -1,7 +1,9 @@
 use std::collections::VecDeque;
 use std::ops::Range;
 
-use crate::formula_builder::{ArithmeticFormulaBuilder, FormulaBuilder, Literal};
+use crate::formula_builder::{
+    ArithmeticFormulaBuilder, FormulaBuilder, GateFormulaBuilder, Literal,
+};
 
 #[derive(Clone, Debug)]
 pub struct BitVector {
@@ -22,6 +24,10 @@ impl BitVector {
         self.bits.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
     pub fn from_literal(literal: Literal) -> Self {
         BitVector {
             range: 0..2,
@@ -29,6 +35,15 @@ impl BitVector {
         }
     }
 
+    /// A bit vector over existing literals, least significant first, known to lie within `range`.
+    pub fn from_bits(range: Range<u32>, bits: Vec<Literal>) -> Self {
+        assert_eq!(
+            range.end.next_power_of_two().trailing_zeros() as usize,
+            bits.len()
+        );
+        BitVector { range, bits }
+    }
+
     pub fn add(formula: &mut impl FormulaBuilder, a: &Self, b: &Self) -> Self {
         // Compute the range of the resulting bit vector.
         let c_range = a.range.start + b.range.start..(a.range.end - 1) + (b.range.end - 1) + 1;
@@ -60,17 +75,17 @@ impl BitVector {
             }
 
             // Add those bits.
-            match &*bits {
+            match *bits {
                 // An empty slice will never happen because the bound imposed by `c_bits` is exact.
 
                 // One bit doesn't require addition.
-                &[x] => {
+                [x] => {
                     c_bits.push(x);
                     prev_carry = None;
                 }
 
                 // Two bits need a half adder.
-                &[x, y] => {
+                [x, y] => {
                     let sum = formula.new_variable().as_positive();
                     let carry = formula.new_variable().as_positive();
                     c_bits.push(sum);
@@ -79,7 +94,7 @@ impl BitVector {
                 }
 
                 // Three bits need a full adder.
-                &[x, y, z] => {
+                [x, y, z] => {
                     let sum = formula.new_variable().as_positive();
                     let carry = formula.new_variable().as_positive();
                     c_bits.push(sum);
@@ -95,6 +110,189 @@ impl BitVector {
         }
     }
 
+    /// Computes `a - b` by adding the two's complement of `b`: each bit of `b` inverted, with a
+    /// carry into the lowest bit. Results are unsigned, so this also requires `a >= b`; a formula
+    /// where `b` could exceed `a` forbids those assignments rather than wrapping around.
+    pub fn sub(formula: &mut impl FormulaBuilder, a: &Self, b: &Self) -> Self {
+        let c_range = a.range.start.saturating_sub(b.range.end - 1)..(a.range.end - b.range.start);
+        let c_len = c_range.end.next_power_of_two().trailing_zeros() as usize;
+
+        let one = constant(formula, true);
+
+        // Subtract over the width of the wider operand, padding the other with zeros.
+        let width = a.bits.len().max(b.bits.len());
+        let mut c_bits = Vec::with_capacity(width);
+        let mut carry = one;
+        for i in 0..width {
+            let x = a.bits.get(i).copied().unwrap_or(-one);
+            let y = -b.bits.get(i).copied().unwrap_or(-one);
+            let sum = formula.new_variable().as_positive();
+            let next_carry = formula.new_variable().as_positive();
+            formula.add_full_adder_constraint(x, y, carry, sum, next_carry);
+            c_bits.push(sum);
+            carry = next_carry;
+        }
+
+        // A final carry out means no borrow, so `a >= b`.
+        formula.add_unit_clause(carry);
+
+        // Bits beyond the range of the difference are zero.
+        for bit in c_bits.drain(c_len..) {
+            formula.add_unit_clause(-bit);
+        }
+        BitVector {
+            range: c_range,
+            bits: c_bits,
+        }
+    }
+
+    /// Requires the value to be at least `n`. This takes one clause per set bit of `n`, each ruling
+    /// out the values that first fall below `n` at that bit.
+    pub fn add_at_least_constraint(&self, formula: &mut impl FormulaBuilder, n: u32) {
+        assert!(n < self.range.end);
+        for i in (0..self.bits.len()).filter(|&i| (n >> i) & 1 == 1) {
+            let mut clause = vec![self.bits[i]];
+            for j in i + 1..self.bits.len() {
+                clause.push(if (n >> j) & 1 == 1 {
+                    -self.bits[j]
+                } else {
+                    self.bits[j]
+                });
+            }
+            formula.add_clause(clause);
+        }
+    }
+
+    /// Requires the value to be at most `n`. This takes one clause per clear bit of `n` below its
+    /// highest bit, each ruling out the values that first rise above `n` at that bit.
+    pub fn add_at_most_constraint(&self, formula: &mut impl FormulaBuilder, n: u32) {
+        if n >= self.range.end - 1 {
+            return;
+        }
+        for i in (0..self.bits.len()).filter(|&i| (n >> i) & 1 == 0) {
+            let mut clause = vec![-self.bits[i]];
+            for j in i + 1..self.bits.len() {
+                clause.push(if (n >> j) & 1 == 1 {
+                    -self.bits[j]
+                } else {
+                    self.bits[j]
+                });
+            }
+            formula.add_clause(clause);
+        }
+    }
+
+    /// Requires the value to be exactly `n`, with one unit clause per bit. Bits above the highest
+    /// set bit of `n` are forced clear.
+    pub fn add_equal_constraint(&self, formula: &mut impl FormulaBuilder, n: u32) {
+        assert!(
+            self.range.contains(&n),
+            "{} is outside the range {:?}",
+            n,
+            self.range,
+        );
+        for (i, &bit) in self.bits.iter().enumerate() {
+            formula.add_unit_clause(if (n >> i) & 1 == 1 { bit } else { -bit });
+        }
+    }
+
+    /// Requires `a` and `b` to hold the same value. Where one is wider, its extra high bits must be
+    /// zero.
+    pub fn add_equal_vectors_constraint(formula: &mut impl FormulaBuilder, a: &Self, b: &Self) {
+        for i in 0..a.bits.len().max(b.bits.len()) {
+            match (a.bits.get(i).copied(), b.bits.get(i).copied()) {
+                (Some(x), Some(y)) => formula.add_logical_equivalence_constraint(x, y),
+                (Some(x), None) | (None, Some(x)) => formula.add_unit_clause(-x),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    /// A literal that is true exactly when `a < b`, from a ripple comparator running up from the
+    /// lowest bit. When the ranges alone decide the comparison, the literal is a constant instead.
+    pub fn less_than(formula: &mut impl FormulaBuilder, a: &Self, b: &Self) -> Literal {
+        if a.range.end - 1 < b.range.start {
+            return constant(formula, true);
+        }
+        if a.range.start >= b.range.end - 1 {
+            return constant(formula, false);
+        }
+
+        // Whether the bits so far compare less, or `None` while that's known to be false. Each bit
+        // compares less when the majority of not `a`, `b`, and the lower bits do.
+        let mut less: Option<Literal> = None;
+        for i in 0..a.bits.len().max(b.bits.len()) {
+            let output = formula.new_variable().as_positive();
+            match (a.bits.get(i).copied(), b.bits.get(i).copied(), less) {
+                (Some(x), Some(y), Some(lower)) => {
+                    formula.add_logical_majority_constraint(output, -x, y, lower)
+                }
+                (Some(x), Some(y), None) => formula.add_logical_and_constraint(output, &[-x, y]),
+                (None, Some(y), Some(lower)) => {
+                    formula.add_logical_or_constraint(output, &[y, lower])
+                }
+                (None, Some(y), None) => formula.add_logical_equivalence_constraint(output, y),
+                (Some(x), None, Some(lower)) => {
+                    formula.add_logical_and_constraint(output, &[-x, lower])
+                }
+                (Some(_), None, None) => formula.add_unit_clause(-output),
+                (None, None, _) => unreachable!(),
+            }
+            less = Some(output);
+        }
+        less.unwrap()
+    }
+
+    /// A literal that is true exactly when the value is at most `k`, from a comparator chain
+    /// against the bits of `k`. When the range alone decides the comparison, the literal is a
+    /// constant instead.
+    pub fn less_than_or_equal_const(&self, formula: &mut impl FormulaBuilder, k: u32) -> Literal {
+        if self.range.end - 1 <= k {
+            return constant(formula, true);
+        }
+        if self.range.start > k {
+            return constant(formula, false);
+        }
+
+        // Whether the bits so far are at most those of `k`, or `None` while that's known to hold.
+        let mut at_most: Option<Literal> = None;
+        for (i, bit) in self.bits.iter().copied().enumerate() {
+            at_most = match ((k >> i) & 1 == 1, at_most) {
+                (true, None) => None,
+                (false, None) => Some(-bit),
+                (true, Some(lower)) => {
+                    let output = formula.new_variable().as_positive();
+                    formula.add_logical_or_constraint(output, &[-bit, lower]);
+                    Some(output)
+                }
+                (false, Some(lower)) => {
+                    let output = formula.new_variable().as_positive();
+                    formula.add_logical_and_constraint(output, &[-bit, lower]);
+                    Some(output)
+                }
+            };
+        }
+        at_most.unwrap_or_else(|| constant(formula, true))
+    }
+
+    /// The value while `enable` holds, and zero otherwise, so that a sum over several masked values
+    /// only counts the enabled ones.
+    pub fn masked(&self, formula: &mut impl FormulaBuilder, enable: Literal) -> Self {
+        let bits = self
+            .bits
+            .iter()
+            .map(|&bit| {
+                let output = formula.new_variable().as_positive();
+                formula.add_logical_and_constraint(output, &[bit, enable]);
+                output
+            })
+            .collect();
+        BitVector {
+            range: 0..self.range.end,
+            bits,
+        }
+    }
+
     pub fn add_tree(formula: &mut impl FormulaBuilder, bit_vectors: Vec<Self>) -> Self {
         let mut bit_vectors: VecDeque<_> = bit_vectors.into();
         while bit_vectors.len() > 1 {
@@ -105,3 +303,320 @@ impl BitVector {
         bit_vectors.pop_front().unwrap()
     }
 }
+
+/// A fresh literal fixed to `value`.
+fn constant(formula: &mut impl FormulaBuilder, value: bool) -> Literal {
+    let literal = formula.new_variable().as_positive();
+    formula.add_unit_clause(if value { literal } else { -literal });
+    literal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BitVector;
+    use crate::formula_builder::{FormulaBuilder, Literal, TaggedVariableFormulaBuilder};
+
+    /// Assumptions that set `bits`, least significant first, to `value`.
+    fn assume(bits: &[Literal], value: u32) -> Vec<Literal> {
+        bits.iter()
+            .enumerate()
+            .map(|(i, &bit)| if (value >> i) & 1 == 1 { bit } else { -bit })
+            .collect()
+    }
+
+    /// Counts five free literals, applies `constrain` to the count, and checks which of the 32
+    /// assignments remain satisfiable against `expected`.
+    fn check_count_constraint(
+        constrain: impl Fn(&mut TaggedVariableFormulaBuilder<usize>, &BitVector),
+        expected: impl Fn(u32) -> bool,
+        description: &str,
+    ) {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let literals: Vec<_> = (0..5)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        let count = BitVector::add_tree(
+            &mut formula,
+            literals
+                .iter()
+                .copied()
+                .map(BitVector::from_literal)
+                .collect(),
+        );
+        constrain(&mut formula, &count);
+        for bits in 0u32..1 << 5 {
+            let assumptions: Vec<_> = literals
+                .iter()
+                .enumerate()
+                .map(|(i, &literal)| {
+                    if bits & (1 << i) != 0 {
+                        literal
+                    } else {
+                        -literal
+                    }
+                })
+                .collect();
+            assert_eq!(
+                expected(bits.count_ones()),
+                formula.solve(&assumptions).is_some(),
+                "{}, bits = {:b}",
+                description,
+                bits,
+            );
+        }
+    }
+
+    #[test]
+    fn sub_small_operands() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a_bits: Vec<_> = (0..3)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        let b_bits: Vec<_> = (3..5)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        let a = BitVector::from_bits(0..8, a_bits.clone());
+        let b = BitVector::from_bits(1..4, b_bits.clone());
+        let c = BitVector::sub(&mut formula, &a, &b);
+        assert_eq!(0..7, c.range());
+
+        for a_value in 0..8 {
+            for b_value in 1..4 {
+                let mut assumptions = assume(&a_bits, a_value);
+                assumptions.extend(assume(&b_bits, b_value));
+                match formula.solve(&assumptions) {
+                    Some(solution) => {
+                        let c_value: u32 = c
+                            .bits()
+                            .iter()
+                            .enumerate()
+                            .map(|(i, bit)| {
+                                if solution[&bit.variable()] == bit.is_positive() {
+                                    1 << i
+                                } else {
+                                    0
+                                }
+                            })
+                            .sum();
+                        assert!(a_value >= b_value, "{} - {} is negative", a_value, b_value);
+                        assert_eq!(a_value - b_value, c_value, "{} - {}", a_value, b_value);
+                    }
+                    None => assert!(
+                        a_value < b_value,
+                        "{} - {} is unsatisfiable",
+                        a_value,
+                        b_value
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn at_most_constant() {
+        for n in 0..=5 {
+            check_count_constraint(
+                |formula, count| count.add_at_most_constraint(formula, n),
+                |value| value <= n,
+                &format!("at most {}", n),
+            );
+        }
+    }
+
+    #[test]
+    fn equal_bounds_pin_the_count() {
+        for n in 0..=5 {
+            check_count_constraint(
+                |formula, count| {
+                    count.add_at_least_constraint(formula, n);
+                    count.add_at_most_constraint(formula, n);
+                },
+                |value| value == n,
+                &format!("exactly {}", n),
+            );
+        }
+    }
+
+    #[test]
+    fn equal_constant() {
+        for n in 0..=5 {
+            check_count_constraint(
+                |formula, count| count.add_equal_constraint(formula, n),
+                |value| value == n,
+                &format!("equal to {}", n),
+            );
+        }
+    }
+
+    #[test]
+    fn equal_constant_across_wide_counts() {
+        // 81 literals need seven bits, so values near 64 and 81 exercise the highest ones.
+        for &n in &[63, 64, 65, 80, 81] {
+            let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+            let literals: Vec<_> = (0..81)
+                .map(|i| formula.get_variable(i).as_positive())
+                .collect();
+            let count = BitVector::add_tree(
+                &mut formula,
+                literals
+                    .iter()
+                    .copied()
+                    .map(BitVector::from_literal)
+                    .collect(),
+            );
+            assert_eq!(7, count.len());
+            count.add_equal_constraint(&mut formula, n);
+            for ones in (n - 1..=n + 1).filter(|&ones| ones <= 81) {
+                let assumptions: Vec<_> = literals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &literal)| if (i as u32) < ones { literal } else { -literal })
+                    .collect();
+                assert_eq!(
+                    ones == n,
+                    formula.solve(&assumptions).is_some(),
+                    "n = {}, ones = {}",
+                    n,
+                    ones,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn equal_vectors_of_mismatched_widths() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a_bits: Vec<_> = (0..3)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        let b_bits: Vec<_> = (3..5)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        let a = BitVector::from_bits(0..8, a_bits.clone());
+        let b = BitVector::from_bits(0..4, b_bits.clone());
+        BitVector::add_equal_vectors_constraint(&mut formula, &a, &b);
+
+        for a_value in 0..8 {
+            for b_value in 0..4 {
+                let mut assumptions = assume(&a_bits, a_value);
+                assumptions.extend(assume(&b_bits, b_value));
+                assert_eq!(
+                    a_value == b_value,
+                    formula.solve(&assumptions).is_some(),
+                    "a = {}, b = {}",
+                    a_value,
+                    b_value,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn less_than_small_operands() {
+        for (a_range, b_range) in &[(0u32..8, 0u32..4), (0..4, 0..8), (2..4, 4..8), (4..8, 0..4)] {
+            let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+            let a_bits: Vec<_> = (0..a_range.end.next_power_of_two().trailing_zeros() as usize)
+                .map(|i| formula.get_variable(i).as_positive())
+                .collect();
+            let b_bits: Vec<_> = (0..b_range.end.next_power_of_two().trailing_zeros() as usize)
+                .map(|i| formula.get_variable(100 + i).as_positive())
+                .collect();
+            let a = BitVector::from_bits(a_range.clone(), a_bits.clone());
+            let b = BitVector::from_bits(b_range.clone(), b_bits.clone());
+            let less = BitVector::less_than(&mut formula, &a, &b);
+            for a_value in a_range.clone() {
+                for b_value in b_range.clone() {
+                    let mut assumptions = assume(&a_bits, a_value);
+                    assumptions.extend(assume(&b_bits, b_value));
+                    let solution = formula.solve(&assumptions).unwrap();
+                    assert_eq!(
+                        a_value < b_value,
+                        solution[&less.variable()] == less.is_positive(),
+                        "{} < {}",
+                        a_value,
+                        b_value,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn less_than_or_equal_constant() {
+        for k in 0..10 {
+            let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+            let bits: Vec<_> = (0..3)
+                .map(|i| formula.get_variable(i).as_positive())
+                .collect();
+            let a = BitVector::from_bits(1..8, bits.clone());
+            let at_most = a.less_than_or_equal_const(&mut formula, k);
+            for value in 1..8 {
+                let solution = formula.solve(&assume(&bits, value)).unwrap();
+                assert_eq!(
+                    value <= k,
+                    solution[&at_most.variable()] == at_most.is_positive(),
+                    "{} <= {}",
+                    value,
+                    k,
+                );
+            }
+        }
+
+        // Bounds that the range settles don't need a comparator.
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let bits: Vec<_> = (0..3)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        let a = BitVector::from_bits(2..6, bits);
+        a.less_than_or_equal_const(&mut formula, 5);
+        a.less_than_or_equal_const(&mut formula, 1);
+        assert_eq!(2, formula.clause_count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn equal_constant_outside_range() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let literal = formula.get_variable(0).as_positive();
+        BitVector::from_literal(literal).add_equal_constraint(&mut formula, 2);
+    }
+
+    #[test]
+    fn at_least_constant() {
+        for n in 0..=5 {
+            let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+            let literals: Vec<_> = (0..5)
+                .map(|i| formula.get_variable(i).as_positive())
+                .collect();
+            let count = BitVector::add_tree(
+                &mut formula,
+                literals
+                    .iter()
+                    .copied()
+                    .map(BitVector::from_literal)
+                    .collect(),
+            );
+            count.add_at_least_constraint(&mut formula, n);
+            for bits in 0u32..1 << 5 {
+                let assumptions: Vec<_> = literals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &literal)| {
+                        if bits & (1 << i) != 0 {
+                            literal
+                        } else {
+                            -literal
+                        }
+                    })
+                    .collect();
+                assert_eq!(
+                    bits.count_ones() >= n,
+                    formula.solve(&assumptions).is_some(),
+                    "n = {}, bits = {:b}",
+                    n,
+                    bits,
+                );
+            }
+        }
+    }
+}
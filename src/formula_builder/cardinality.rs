@@ -8,6 +8,185 @@ pub trait CardinalityFormulaBuilder: FormulaBuilder {
             }
         }
     }
+
+    /// Requires at most one of `literals` to be true with a ladder of `n - 1` auxiliary variables,
+    /// each implied once some earlier literal is true. This takes about `3 * n` clauses, against
+    /// the `n * (n - 1) / 2` of the pairwise encoding above, and is the sequential counter for
+    /// `k = 1`.
+    fn add_at_most_one_sequential(&mut self, literals: &[Literal]) {
+        self.add_at_most_k_of_constraint(literals, 1);
+    }
+
+    /// Requires at most `k` of `literals` to be true, using Sinz's sequential counter. This takes
+    /// `O(n * k)` auxiliary variables and clauses, where the pairwise encoding above only scales
+    /// to `k = 1`.
+    fn add_at_most_k_of_constraint(&mut self, literals: &[Literal], k: usize) {
+        let n = literals.len();
+        if k >= n {
+            return;
+        }
+        if k == 0 {
+            for literal in literals.iter().copied() {
+                self.add_unit_clause(-literal);
+            }
+            return;
+        }
+
+        // counts[i][j] is implied when at least j + 1 of the first i + 1 literals are true.
+        let mut counts: Vec<Vec<Literal>> = Vec::with_capacity(n - 1);
+        for literal in literals[..n - 1].iter().copied() {
+            let row: Vec<_> = (0..k).map(|_| self.new_variable().as_positive()).collect();
+            self.add_binary_clause(-literal, row[0]);
+            if let Some(prev) = counts.last() {
+                for j in 0..k {
+                    self.add_binary_clause(-prev[j], row[j]);
+                }
+                for j in 1..k {
+                    self.add_clause(vec![-literal, -prev[j - 1], row[j]]);
+                }
+                self.add_binary_clause(-literal, -prev[k - 1]);
+            } else {
+                for count in row[1..].iter().copied() {
+                    self.add_unit_clause(-count);
+                }
+            }
+            counts.push(row);
+        }
+        self.add_binary_clause(-literals[n - 1], -counts[n - 2][k - 1]);
+    }
+
+    /// Requires at least `k` of `literals` to be true, by requiring at most `n - k` of them to be
+    /// false. At least one only takes a single clause.
+    fn add_at_least_k_of_constraint(&mut self, literals: &[Literal], k: usize) {
+        assert!(k <= literals.len());
+        if k == 1 {
+            self.add_clause(literals.to_vec());
+            return;
+        }
+        let negated: Vec<_> = literals.iter().map(|literal| -*literal).collect();
+        self.add_at_most_k_of_constraint(&negated, literals.len() - k);
+    }
+
+    /// Requires exactly `k` of `literals` to be true, with a sequential counter for each bound.
+    fn add_exactly_k_of_constraint(&mut self, literals: &[Literal], k: usize) {
+        self.add_at_most_k_of_constraint(literals, k);
+        self.add_at_least_k_of_constraint(literals, k);
+    }
 }
 
 impl<T> CardinalityFormulaBuilder for T where T: FormulaBuilder {}
+
+#[cfg(test)]
+mod tests {
+    use super::CardinalityFormulaBuilder;
+    use crate::formula_builder::{FormulaBuilder, Literal, TaggedVariableFormulaBuilder};
+
+    /// Checks a cardinality constraint against every assignment of its inputs.
+    fn check_truth_table(
+        add_constraint: impl Fn(&mut TaggedVariableFormulaBuilder<usize>, &[Literal], usize),
+        expected: impl Fn(usize, usize) -> bool,
+    ) {
+        for n in 1..=6 {
+            for k in 0..=n {
+                let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+                let literals: Vec<_> = (0..n)
+                    .map(|i| formula.get_variable(i).as_positive())
+                    .collect();
+                add_constraint(&mut formula, &literals, k);
+                for bits in 0u32..1 << n {
+                    let assumptions: Vec<_> = literals
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &literal)| {
+                            if bits & (1 << i) != 0 {
+                                literal
+                            } else {
+                                -literal
+                            }
+                        })
+                        .collect();
+                    let satisfiable = formula.solve(&assumptions).is_some();
+                    assert_eq!(
+                        expected(bits.count_ones() as usize, k),
+                        satisfiable,
+                        "n = {}, k = {}, bits = {:b}",
+                        n,
+                        k,
+                        bits,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn at_most_k() {
+        check_truth_table(
+            |formula, literals, k| formula.add_at_most_k_of_constraint(literals, k),
+            |count, k| count <= k,
+        );
+    }
+
+    #[test]
+    fn at_least_k() {
+        check_truth_table(
+            |formula, literals, k| formula.add_at_least_k_of_constraint(literals, k),
+            |count, k| count >= k,
+        );
+    }
+
+    #[test]
+    fn exactly_k() {
+        check_truth_table(
+            |formula, literals, k| formula.add_exactly_k_of_constraint(literals, k),
+            |count, k| count == k,
+        );
+    }
+
+    #[test]
+    fn sequential_at_most_one_matches_pairwise() {
+        for n in 1..=9 {
+            let mut pairwise = TaggedVariableFormulaBuilder::<usize>::default();
+            let mut sequential = TaggedVariableFormulaBuilder::<usize>::default();
+            let literals: Vec<_> = (0..n)
+                .map(|i| pairwise.get_variable(i).as_positive())
+                .collect();
+            for i in 0..n {
+                sequential.get_variable(i);
+            }
+            pairwise.add_at_most_one_of_constraint(&literals);
+            sequential.add_at_most_one_sequential(&literals);
+            for bits in 0u32..1 << n {
+                let assumptions: Vec<_> = literals
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &literal)| {
+                        if bits & (1 << i) != 0 {
+                            literal
+                        } else {
+                            -literal
+                        }
+                    })
+                    .collect();
+                assert_eq!(
+                    pairwise.solve(&assumptions).is_some(),
+                    sequential.solve(&assumptions).is_some(),
+                    "n = {}, bits = {:b}",
+                    n,
+                    bits,
+                );
+            }
+            assert!(sequential.clause_count() <= 3 * n);
+        }
+    }
+
+    #[test]
+    fn at_most_k_is_linear() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let literals: Vec<_> = (0..20)
+            .map(|i| formula.get_variable(i).as_positive())
+            .collect();
+        formula.add_at_most_k_of_constraint(&literals, 5);
+        assert!(formula.clause_count() < 2 * 20 * 5 + 20);
+    }
+}
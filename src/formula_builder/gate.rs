@@ -44,6 +44,23 @@ pub trait GateFormulaBuilder: FormulaBuilder {
         self.add_clause(vec![-a, b, output]);
         self.add_clause(vec![-a, -b, -output]);
     }
+
+    fn add_logical_majority_constraint(
+        &mut self,
+        output: Literal,
+        a: Literal,
+        b: Literal,
+        c: Literal,
+    ) {
+        // Any two inputs agreeing decide the output.
+        //  a ^  b ->  output  =>  (-a v -b v  output)
+        // -a ^ -b -> -output  =>  ( a v  b v -output)
+        // ...and likewise for (a, c) and (b, c).
+        for (x, y) in [(a, b), (a, c), (b, c)] {
+            self.add_clause(vec![-x, -y, output]);
+            self.add_clause(vec![x, y, -output]);
+        }
+    }
 }
 
 impl<T> GateFormulaBuilder for T where T: FormulaBuilder {}
@@ -0,0 +1,72 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::formula_builder::Literal;
+
+/// How the number of true literals in a linear constraint compares to its right-hand side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Relation {
+    AtLeast,
+    Equal,
+}
+
+/// A constraint on how many of its literals are true, kept whole for pseudo-Boolean output rather
+/// than broken down into clauses.
+#[derive(Clone)]
+pub struct LinearConstraint {
+    pub literals: Vec<Literal>,
+    pub relation: Relation,
+    pub rhs: u32,
+}
+
+impl LinearConstraint {
+    /// Writes this constraint as an OPB line over plain variables. A negated literal `~x` counts as
+    /// `1 - x`, so it contributes `-1 x` and lowers the right-hand side by one.
+    pub async fn write_opb_fragment<W: AsyncWrite + Unpin>(&self, w: &mut W) -> Result<()> {
+        let mut buf = String::new();
+        let mut rhs = self.rhs as i64;
+        for literal in self.literals.iter().copied() {
+            let variable = literal.variable().index().as_i32();
+            if literal.is_positive() {
+                write!(&mut buf, "+1 x{} ", variable)?;
+            } else {
+                write!(&mut buf, "-1 x{} ", variable)?;
+                rhs -= 1;
+            }
+        }
+        let relation = match self.relation {
+            Relation::AtLeast => ">=",
+            Relation::Equal => "=",
+        };
+        writeln!(&mut buf, "{} {} ;", relation, rhs)?;
+        w.write_all(buf.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Relation;
+    use crate::formula_builder::{FormulaBuilder, TaggedVariableFormulaBuilder};
+
+    #[tokio::test]
+    async fn writes_clauses_and_linear_constraints() {
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        let c = formula.get_variable(2).as_positive();
+        formula.add_binary_clause(a, -b);
+        formula.add_linear_constraint(&[a, b, -c], Relation::Equal, 2);
+
+        let mut output = Vec::new();
+        formula.write_opb(&mut output).await.unwrap();
+        assert_eq!(
+            "* #variable= 3 #constraint= 2\n\
+             +1 x1 -1 x2 >= 0 ;\n\
+             +1 x1 +1 x2 -1 x3 = 1 ;\n",
+            String::from_utf8(output).unwrap(),
+        );
+    }
+}
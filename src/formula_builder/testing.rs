@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use crate::formula_builder::{Literal, TaggedVariableFormulaBuilder, Variable};
+use crate::positive_i32::PositiveI32;
+
+impl<T> TaggedVariableFormulaBuilder<T> {
+    /// Searches for an assignment satisfying every clause and every assumption. This is a plain
+    /// DPLL search with unit propagation, so it is only suitable for small formulas or formulas
+    /// that unit propagation decides on its own, such as circuits with all of their inputs fixed.
+    pub fn solve(&self, assumptions: &[Literal]) -> Option<HashMap<Variable, bool>> {
+        let mut clauses: Vec<Vec<i32>> = self
+            .clauses()
+            .map(|clause| clause.iter().map(|l| l.index().get()).collect())
+            .collect();
+        clauses.extend(assumptions.iter().map(|l| vec![l.index().get()]));
+
+        let mut search = Search::new(
+            self.variable_counter.highest_variable_index as usize,
+            clauses,
+        );
+        if !search.run() {
+            return None;
+        }
+        Some(
+            (1..search.values.len())
+                .map(|index| {
+                    let variable =
+                        Variable::from_index(PositiveI32::from_u32(index as u32).unwrap());
+                    (variable, search.values[index] > 0)
+                })
+                .collect(),
+        )
+    }
+}
+
+struct Search {
+    clauses: Vec<Vec<i32>>,
+    /// Clause indices containing each literal, indexed by `literal_slot`.
+    occurrences: Vec<Vec<usize>>,
+    /// The value of each variable: 1 for true, -1 for false, or 0 for unassigned.
+    values: Vec<i8>,
+    trail: Vec<i32>,
+    propagated: usize,
+}
+
+fn literal_slot(literal: i32) -> usize {
+    2 * literal.unsigned_abs() as usize + (literal < 0) as usize
+}
+
+impl Search {
+    fn new(variable_count: usize, clauses: Vec<Vec<i32>>) -> Self {
+        let mut occurrences = vec![Vec::new(); 2 * variable_count + 2];
+        for (i, clause) in clauses.iter().enumerate() {
+            for literal in clause.iter().copied() {
+                occurrences[literal_slot(literal)].push(i);
+            }
+        }
+        Self {
+            clauses,
+            occurrences,
+            values: vec![0; variable_count + 1],
+            trail: Vec::new(),
+            propagated: 0,
+        }
+    }
+
+    fn value(&self, literal: i32) -> i8 {
+        let value = self.values[literal.unsigned_abs() as usize];
+        if literal < 0 {
+            -value
+        } else {
+            value
+        }
+    }
+
+    fn assign(&mut self, literal: i32) -> bool {
+        match self.value(literal) {
+            1 => true,
+            -1 => false,
+            _ => {
+                self.values[literal.unsigned_abs() as usize] = if literal < 0 { -1 } else { 1 };
+                self.trail.push(literal);
+                true
+            }
+        }
+    }
+
+    fn undo(&mut self, trail_len: usize) {
+        for literal in self.trail.drain(trail_len..) {
+            self.values[literal.unsigned_abs() as usize] = 0;
+        }
+        self.propagated = self.propagated.min(trail_len);
+    }
+
+    /// Returns false on conflict.
+    fn propagate(&mut self) -> bool {
+        while self.propagated < self.trail.len() {
+            let falsified = -self.trail[self.propagated];
+            self.propagated += 1;
+            for i in 0..self.occurrences[literal_slot(falsified)].len() {
+                let clause = self.occurrences[literal_slot(falsified)][i];
+                let mut unassigned = None;
+                let mut unassigned_count = 0;
+                let mut satisfied = false;
+                for literal in self.clauses[clause].iter().copied() {
+                    match self.value(literal) {
+                        1 => {
+                            satisfied = true;
+                            break;
+                        }
+                        0 => {
+                            unassigned = Some(literal);
+                            unassigned_count += 1;
+                        }
+                        _ => (),
+                    }
+                }
+                if satisfied {
+                    continue;
+                }
+                match unassigned_count {
+                    0 => return false,
+                    1 => {
+                        self.assign(unassigned.unwrap());
+                    }
+                    _ => (),
+                }
+            }
+        }
+        true
+    }
+
+    fn run(&mut self) -> bool {
+        for i in 0..self.clauses.len() {
+            if let [literal] = *self.clauses[i] {
+                if !self.assign(literal) {
+                    return false;
+                }
+            }
+        }
+
+        // Each decision records the trail length before it, its literal, and whether it has
+        // already been flipped.
+        let mut decisions: Vec<(usize, i32, bool)> = Vec::new();
+        loop {
+            if !self.propagate() {
+                loop {
+                    match decisions.pop() {
+                        None => return false,
+                        Some((trail_len, literal, flipped)) => {
+                            self.undo(trail_len);
+                            if !flipped {
+                                decisions.push((trail_len, -literal, true));
+                                self.assign(-literal);
+                                break;
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+            match (1..self.values.len()).find(|&index| self.values[index] == 0) {
+                None => return true,
+                Some(index) => {
+                    let literal = index as i32;
+                    decisions.push((self.trail.len(), literal, false));
+                    self.assign(literal);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::formula_builder::{FormulaBuilder, TaggedVariableFormulaBuilder};
+
+    #[test]
+    fn finds_a_model_that_satisfies_every_clause() {
+        let mut formula = TaggedVariableFormulaBuilder::<()>::new();
+        let a = formula.new_variable();
+        let b = formula.new_variable();
+        let c = formula.new_variable();
+        formula.add_clause(vec![a.as_positive(), b.as_positive()]);
+        formula.add_clause(vec![a.as_negative(), c.as_positive()]);
+        formula.add_clause(vec![b.as_negative(), c.as_positive()]);
+
+        let model = formula.solve(&[]).unwrap();
+        assert!(model[&a] || model[&b]);
+        assert!(model[&c]);
+
+        let model = formula.solve(&[a.as_negative()]).unwrap();
+        assert!(!model[&a] && model[&b] && model[&c]);
+
+        assert!(formula.solve(&[c.as_negative()]).is_none());
+    }
+
+    #[test]
+    fn backtracks_out_of_a_conflicting_decision() {
+        // The search decides variables true first, so it has to undo `a` to satisfy these.
+        let mut formula = TaggedVariableFormulaBuilder::<()>::new();
+        let a = formula.new_variable();
+        let b = formula.new_variable();
+        formula.add_clause(vec![a.as_negative(), b.as_positive()]);
+        formula.add_clause(vec![a.as_negative(), b.as_negative()]);
+
+        let model = formula.solve(&[]).unwrap();
+        assert!(!model[&a]);
+        assert!(formula.solve(&[a.as_positive()]).is_none());
+    }
+}
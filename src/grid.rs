@@ -0,0 +1,537 @@
+use std::collections::HashMap;
+use std::iter::once;
+use std::ops::Index;
+
+use anyhow::{anyhow, Result};
+
+use crate::board_size::board_size;
+use crate::formula_builder::Variable;
+use crate::puzzle_string::format_puzzle_string;
+use crate::sat_solver::Solution;
+use crate::sudoku::{Cell, Col, Digit, Row, VariableKind};
+
+/// A fully solved board.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    digits: Vec<Digit>,
+}
+
+/// Which cells of a board are givens.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GivensMask {
+    given: Vec<bool>,
+}
+
+impl Index<Cell> for Grid {
+    type Output = Digit;
+
+    fn index(&self, cell: Cell) -> &Digit {
+        &self.digits[cell.index()]
+    }
+}
+
+/// Every cell of a grid and its digit, in row-major order.
+pub struct GridCells<'a> {
+    grid: &'a Grid,
+    index: usize,
+}
+
+impl Iterator for GridCells<'_> {
+    type Item = (Cell, Digit);
+
+    fn next(&mut self) -> Option<(Cell, Digit)> {
+        let digit = *self.grid.digits.get(self.index)?;
+        let side = board_size().side() as usize;
+        let cell = Cell {
+            row: Row::new((self.index / side + 1) as u8).unwrap(),
+            col: Col::new((self.index % side + 1) as u8).unwrap(),
+        };
+        self.index += 1;
+        Some((cell, digit))
+    }
+}
+
+/// A grid yields its cells and their digits, so `for (cell, digit) in &grid` walks the board.
+/// Every cell of a solved grid has a digit, so this yields `(Cell, Digit)`; for a puzzle's
+/// `(Cell, Option<Digit>)`, with `None` in the empty cells, use `Grid::iter_puzzle`.
+///
+/// ```
+/// use sudoku_generator::grid::Grid;
+/// use sudoku_generator::sudoku::Digit;
+///
+/// let grid = Grid::from_fn(|cell| {
+///     let shifted = cell.row.index() * 3 + cell.row.index() / 3 + cell.col.index();
+///     Digit::new((shifted % 9) as u8 + 1).unwrap()
+/// });
+/// let mut count = 0;
+/// for (cell, digit) in &grid {
+///     assert_eq!(grid[cell], digit);
+///     count += 1;
+/// }
+/// assert_eq!(81, count);
+/// ```
+impl<'a> IntoIterator for &'a Grid {
+    type Item = (Cell, Digit);
+    type IntoIter = GridCells<'a>;
+
+    fn into_iter(self) -> GridCells<'a> {
+        self.iter_cells()
+    }
+}
+
+impl Grid {
+    /// Every cell and its digit, in row-major order.
+    ///
+    /// ```
+    /// use sudoku_generator::grid::Grid;
+    /// use sudoku_generator::sudoku::Digit;
+    ///
+    /// let grid = Grid::from_fn(|cell| {
+    ///     let shifted = cell.row.index() * 3 + cell.row.index() / 3 + cell.col.index();
+    ///     Digit::new((shifted % 9) as u8 + 1).unwrap()
+    /// });
+    /// let first_row: String = grid
+    ///     .iter_cells()
+    ///     .take(9)
+    ///     .map(|(_, digit)| digit.symbol())
+    ///     .collect();
+    /// assert_eq!("123456789", first_row);
+    /// ```
+    pub fn iter_cells(&self) -> GridCells<'_> {
+        GridCells {
+            grid: self,
+            index: 0,
+        }
+    }
+
+    /// Every cell of the puzzle that `mask` picks out of this grid, in row-major order, with its
+    /// digit if it's given and `None` if it's left empty.
+    ///
+    /// ```
+    /// use sudoku_generator::grid::{GivensMask, Grid};
+    /// use sudoku_generator::sudoku::Digit;
+    ///
+    /// let grid = Grid::from_fn(|cell| {
+    ///     let shifted = cell.row.index() * 3 + cell.row.index() / 3 + cell.col.index();
+    ///     Digit::new((shifted % 9) as u8 + 1).unwrap()
+    /// });
+    /// // Give the cells of the first row.
+    /// let mask = GivensMask::from_fn(|cell| cell.row.index() == 0);
+    /// for (cell, given) in grid.iter_puzzle(&mask) {
+    ///     if cell.row.index() == 0 {
+    ///         assert_eq!(Some(grid[cell]), given);
+    ///     } else {
+    ///         assert_eq!(None, given);
+    ///     }
+    /// }
+    /// ```
+    pub fn iter_puzzle<'a>(
+        &'a self,
+        mask: &'a GivensMask,
+    ) -> impl Iterator<Item = (Cell, Option<Digit>)> + 'a {
+        self.iter_cells()
+            .map(move |(cell, digit)| (cell, Some(digit).filter(|_| mask.is_given(cell))))
+    }
+
+    /// Every cell and the digit placed in it.
+    pub fn placements(&self) -> HashMap<Cell, Digit> {
+        self.iter_cells().collect()
+    }
+
+    /// The cells and digits that `mask` marks as given.
+    pub fn givens(&self, mask: &GivensMask) -> HashMap<Cell, Digit> {
+        self.iter_cells()
+            .filter(|&(cell, _)| mask.is_given(cell))
+            .collect()
+    }
+
+    /// The puzzle in the single-line format: 81 characters in row-major order from r1c1, with
+    /// digits for the givens that `mask` marks and `.` for the other cells.
+    pub fn to_puzzle_string(&self, mask: &GivensMask) -> String {
+        format_puzzle_string(&self.givens(mask))
+    }
+
+    /// Every solved digit in the single-line format, in row-major order from r1c1.
+    pub fn to_solution_string(&self) -> String {
+        format_puzzle_string(&self.placements())
+    }
+}
+
+impl Grid {
+    /// The least grid, reading cells in row-major order, among every grid equivalent to this one
+    /// under relabeling digits, permuting bands, stacks, and the rows and columns within them, and
+    /// for square boxes transposing. Equivalent grids have the same canonical form.
+    pub fn canonical(&self) -> Grid {
+        let size = board_size();
+        let side = size.side() as usize;
+        let rows: Vec<Vec<u8>> = self
+            .digits
+            .chunks(side)
+            .map(|row| row.iter().map(|digit| digit.as_u8()).collect())
+            .collect();
+        let mut orientations = vec![rows];
+        // Transposing swaps the height and width of the boxes, so only square boxes allow it.
+        if size.box_height() == size.box_width() {
+            let rows = &orientations[0];
+            let transposed = (0..side)
+                .map(|r| (0..side).map(|c| rows[c][r]).collect())
+                .collect();
+            orientations.push(transposed);
+        }
+
+        let col_orders = column_orders(side / size.box_width() as usize, size.box_width() as usize);
+        let mut best: Option<Vec<Vec<u8>>> = None;
+        for rows in &orientations {
+            // The first row can come from anywhere, and relabeling makes it read 1 upward.
+            for first in 0..side {
+                for cols in &col_orders {
+                    let candidate = arrange_rows(rows, first, cols, size.box_height() as usize);
+                    if best.as_ref().is_none_or(|best| candidate < *best) {
+                        best = Some(candidate);
+                    }
+                }
+            }
+        }
+
+        Grid {
+            digits: best
+                .unwrap()
+                .into_iter()
+                .flatten()
+                .map(|value| Digit::new(value).unwrap())
+                .collect(),
+        }
+    }
+
+    /// A hash of the canonical form, so that equivalent grids hash alike. This uses FNV-1a, which is
+    /// stable across runs and Rust releases.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        for digit in self.canonical().digits.iter() {
+            hash ^= digit.as_u8() as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+}
+
+/// Every ordering of `n` items.
+fn permutations(n: usize) -> Vec<Vec<usize>> {
+    if n == 0 {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for shorter in permutations(n - 1) {
+        for position in 0..n {
+            let mut permutation = shorter.clone();
+            permutation.insert(position, n - 1);
+            result.push(permutation);
+        }
+    }
+    result
+}
+
+/// Every column order reachable by permuting `stacks` stacks of `width` columns and the columns
+/// within each stack.
+fn column_orders(stacks: usize, width: usize) -> Vec<Vec<usize>> {
+    let within = permutations(width);
+    let mut orders = Vec::new();
+    for stack_order in permutations(stacks) {
+        let mut partial = vec![Vec::new()];
+        for stack in stack_order {
+            partial = partial
+                .into_iter()
+                .flat_map(|prefix: Vec<usize>| {
+                    within.iter().map(move |lines| {
+                        let mut order = prefix.clone();
+                        order.extend(lines.iter().map(|line| stack * width + line));
+                        order
+                    })
+                })
+                .collect();
+        }
+        orders.extend(partial);
+    }
+    orders
+}
+
+/// Rearranges `rows` with the given column order, relabeling digits so that row `first` reads 1
+/// upward and comes first, then ordering the remaining rows as low as their bands of `band_height`
+/// rows allow. No two rows of a solved grid are equal, so picking the least row at each step gives
+/// the least grid.
+fn arrange_rows(
+    rows: &[Vec<u8>],
+    first: usize,
+    cols: &[usize],
+    band_height: usize,
+) -> Vec<Vec<u8>> {
+    let mut labels = vec![0; cols.len() + 1];
+    for (i, &col) in cols.iter().enumerate() {
+        labels[rows[first][col] as usize] = i as u8 + 1;
+    }
+    let relabel = |r: usize| -> Vec<u8> {
+        cols.iter()
+            .map(|&col| labels[rows[r][col] as usize])
+            .collect()
+    };
+
+    let band = |b: usize| {
+        let mut band: Vec<_> = (band_height * b..band_height * (b + 1))
+            .map(relabel)
+            .collect();
+        band.sort_unstable();
+        band
+    };
+    let first_band = first / band_height;
+    let mut others: Vec<_> = (0..rows.len() / band_height)
+        .filter(|&b| b != first_band)
+        .map(band)
+        .collect();
+    others.sort_unstable();
+
+    let mut first_rows: Vec<_> = (band_height * first_band..band_height * (first_band + 1))
+        .filter(|&r| r != first)
+        .map(relabel)
+        .collect();
+    first_rows.sort_unstable();
+
+    once(relabel(first))
+        .chain(first_rows)
+        .chain(others.into_iter().flatten())
+        .collect()
+}
+
+impl GivensMask {
+    pub fn is_given(&self, cell: Cell) -> bool {
+        self.given[cell.index()]
+    }
+
+    pub fn count(&self) -> usize {
+        self.given.iter().filter(|&&given| given).count()
+    }
+}
+
+impl Grid {
+    /// Builds a grid from the digit in each cell, without going through a solver. The digits aren't
+    /// checked against the rules of sudoku.
+    pub fn from_fn(digit: impl Fn(Cell) -> Digit) -> Self {
+        Grid {
+            digits: Cell::values().map(digit).collect(),
+        }
+    }
+}
+
+impl GivensMask {
+    /// Builds a mask that marks the cells where `is_given` is true.
+    pub fn from_fn(is_given: impl Fn(Cell) -> bool) -> Self {
+        GivensMask {
+            given: Cell::values().map(is_given).collect(),
+        }
+    }
+}
+
+fn assignment(
+    variables: &HashMap<VariableKind, Variable>,
+    assignments: &HashMap<Variable, bool>,
+    kind: VariableKind,
+) -> Result<bool> {
+    let variable = variables
+        .get(&kind)
+        .ok_or_else(|| anyhow!("the formula has no variable for {:?}", kind))?;
+    assignments
+        .get(variable)
+        .copied()
+        .ok_or_else(|| anyhow!("the model has no assignment for {:?}", kind))
+}
+
+impl Solution {
+    fn assignments(&self) -> Result<&HashMap<Variable, bool>> {
+        match self {
+            Solution::Satisfiable { assignments } => Ok(assignments),
+            Solution::Unsatisfiable => Err(anyhow!("the formula is unsatisfiable")),
+            Solution::Unknown => Err(anyhow!("the solver stopped without deciding")),
+        }
+    }
+
+    /// Reads the digit placed in every cell.
+    pub fn to_grid(&self, variables: &HashMap<VariableKind, Variable>) -> Result<Grid> {
+        let assignments = self.assignments()?;
+        let mut digits = vec![Digit::K1; board_size().cell_count()];
+        for cell in Cell::values() {
+            let mut placed = None;
+            for digit in Digit::values() {
+                let kind = VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                };
+                if assignment(variables, assignments, kind)? {
+                    if placed.is_some() {
+                        return Err(anyhow!("more than one digit is placed in {:?}", cell));
+                    }
+                    placed = Some(digit);
+                }
+            }
+            digits[cell.index()] =
+                placed.ok_or_else(|| anyhow!("no digit is placed in {:?}", cell))?;
+        }
+        Ok(Grid { digits })
+    }
+
+    /// Reads which cells are givens.
+    pub fn givens_mask(&self, variables: &HashMap<VariableKind, Variable>) -> Result<GivensMask> {
+        let assignments = self.assignments()?;
+        let mut given = vec![false; board_size().cell_count()];
+        for cell in Cell::values() {
+            let kind = VariableKind::Given {
+                row: cell.row,
+                col: cell.col,
+            };
+            given[cell.index()] = assignment(variables, assignments, kind)?;
+        }
+        Ok(GivensMask { given })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::Grid;
+    use crate::board_size::{with_board_size, BoardSize};
+    use crate::formula_builder::{TaggedVariableFormulaBuilder, Variable};
+    use crate::puzzle_string::{format_puzzle_string, parse_puzzle_string};
+    use crate::sat_solver::Solution;
+    use crate::sudoku::{Cell, Digit, VariableKind};
+
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    fn model() -> (HashMap<VariableKind, Variable>, Solution) {
+        let digits = parse_puzzle_string(SOLUTION).unwrap();
+        let mut formula = TaggedVariableFormulaBuilder::<VariableKind>::default();
+        let mut assignments = HashMap::new();
+        for cell in Cell::values() {
+            let given = formula.get_variable(VariableKind::Given {
+                row: cell.row,
+                col: cell.col,
+            });
+            assignments.insert(given, cell.row.index() == cell.col.index());
+            for digit in Digit::values() {
+                let placed = formula.get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                });
+                assignments.insert(placed, digits[&cell] == digit);
+            }
+        }
+        (
+            formula.into_tagged_variables(),
+            Solution::Satisfiable { assignments },
+        )
+    }
+
+    #[test]
+    fn reads_grid_and_givens() {
+        let (variables, solution) = model();
+        let grid = solution.to_grid(&variables).unwrap();
+        assert_eq!(parse_puzzle_string(SOLUTION).unwrap(), grid.placements());
+
+        let mask = solution.givens_mask(&variables).unwrap();
+        assert_eq!(9, mask.count());
+        let givens = grid.givens(&mask);
+        assert!(givens
+            .keys()
+            .all(|cell| cell.row.index() == cell.col.index()));
+    }
+
+    #[test]
+    fn puzzle_strings_round_trip() {
+        let (variables, solution) = model();
+        let grid = solution.to_grid(&variables).unwrap();
+        let mask = solution.givens_mask(&variables).unwrap();
+        assert_eq!(SOLUTION, grid.to_solution_string());
+
+        let puzzle = grid.to_puzzle_string(&mask);
+        assert_eq!(81, puzzle.len());
+        assert_eq!("5........", &puzzle[..9]);
+        assert_eq!(grid.givens(&mask), parse_puzzle_string(&puzzle).unwrap());
+    }
+
+    #[test]
+    fn equivalent_grids_share_a_canonical_hash() {
+        let (variables, solution) = model();
+        let grid = solution.to_grid(&variables).unwrap();
+        let canonical = grid.canonical();
+        assert_eq!(canonical, canonical.canonical());
+        assert_eq!(
+            "123456789",
+            &format_puzzle_string(&canonical.placements())[..9]
+        );
+
+        // Relabel digits, transpose, swap two bands, and swap two columns within a stack.
+        let placements = grid.placements();
+        let mut digits = grid.digits.clone();
+        for cell in Cell::values() {
+            let row = [3, 4, 5, 0, 1, 2, 6, 7, 8][cell.row.index() as usize];
+            let col = [0, 2, 1, 3, 4, 5, 6, 7, 8][cell.col.index() as usize];
+            let source = Cell::values().nth(9 * col + row).unwrap();
+            let digit = placements[&source].as_u8() % 9 + 1;
+            digits[cell.index()] = Digit::new(digit).unwrap();
+        }
+        let variant = Grid { digits };
+        assert_ne!(grid, variant);
+        assert_eq!(canonical, variant.canonical());
+        assert_eq!(grid.canonical_hash(), variant.canonical_hash());
+    }
+
+    #[test]
+    fn six_by_six_grids_canonicalize_without_transposing() {
+        with_board_size(BoardSize::new(2, 3), || {
+            let grid = |s: &str| Grid {
+                digits: s.bytes().map(|b| Digit::new(b - b'0').unwrap()).collect(),
+            };
+            let original = grid("123456456123234561561234345612612345");
+            // Swap the first and last bands, swap the first two columns, and relabel the digits.
+            let variant = grid("546123213456435612162345324561651234");
+            assert_ne!(original, variant);
+            assert_eq!(original.canonical(), variant.canonical());
+            assert_eq!(original.canonical(), original.canonical().canonical());
+        });
+    }
+
+    #[test]
+    fn cells_iterate_in_row_major_order() {
+        let (variables, solution) = model();
+        let grid = solution.to_grid(&variables).unwrap();
+        let entries: Vec<_> = (&grid).into_iter().collect();
+        assert_eq!(81, entries.len());
+        assert!(entries.iter().map(|&(cell, _)| cell).eq(Cell::values()));
+        let digits: String = entries
+            .iter()
+            .map(|(_, digit)| digit.as_u8().to_string())
+            .collect();
+        assert_eq!(SOLUTION, digits);
+        assert!(grid.iter_cells().eq(&grid));
+    }
+
+    #[test]
+    fn incomplete_models_are_errors() {
+        let (variables, solution) = model();
+        assert!(Solution::Unsatisfiable.to_grid(&variables).is_err());
+        assert!(solution.to_grid(&HashMap::new()).is_err());
+
+        let assignments = match solution {
+            Solution::Satisfiable { mut assignments } => {
+                for value in assignments.values_mut() {
+                    *value = false;
+                }
+                assignments
+            }
+            Solution::Unsatisfiable | Solution::Unknown => unreachable!(),
+        };
+        let empty = Solution::Satisfiable { assignments };
+        assert!(empty.to_grid(&variables).is_err());
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::sudoku::{Cell, CellNameError};
+
+/// The relationship a Kropki dot requires between the digits on either side of it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DotColor {
+    /// The digits are consecutive.
+    White,
+    /// One digit is double the other.
+    Black,
+}
+
+impl DotColor {
+    pub const ALL: [DotColor; 2] = [DotColor::White, DotColor::Black];
+
+    /// Whether digits `a` and `b` satisfy a dot of this color, in either order.
+    pub fn allows(self, a: u8, b: u8) -> bool {
+        match self {
+            DotColor::White => a.abs_diff(b) == 1,
+            DotColor::Black => a == 2 * b || b == 2 * a,
+        }
+    }
+
+    fn symbol(self) -> char {
+        match self {
+            DotColor::White => 'w',
+            DotColor::Black => 'b',
+        }
+    }
+}
+
+/// A Kropki dot on the edge between two orthogonally adjacent cells.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Dot {
+    pub cells: [Cell; 2],
+    pub color: DotColor,
+}
+
+/// Formats a dot as `--kropki` takes it, like `r1c1+r1c2=w`.
+impl Display for Dot {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}+{}={}",
+            self.cells[0],
+            self.cells[1],
+            self.color.symbol()
+        )
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum DotError {
+    #[error("expected a Kropki dot like r1c1+r1c2=w or r1c1+r2c1=b, but found {0:?}")]
+    Malformed(String),
+
+    #[error("in Kropki dot {dot:?}: {source}")]
+    BadCell { dot: String, source: CellNameError },
+
+    #[error("Kropki dot {0:?} must sit between two orthogonally adjacent cells")]
+    NotAdjacent(String),
+
+    #[error("the edge between {0} and {1} has more than one Kropki dot")]
+    RepeatedEdge(Cell, Cell),
+}
+
+/// Whether `a` and `b` share an edge.
+pub fn orthogonally_adjacent(a: Cell, b: Cell) -> bool {
+    a.row.index().abs_diff(b.row.index()) + a.col.index().abs_diff(b.col.index()) == 1
+}
+
+/// Parses a semicolon-separated list of Kropki dots, each a pair of orthogonally adjacent cells
+/// with `=w` for a white dot or `=b` for a black one, like `r1c1+r1c2=w;r4c4+r5c4=b`. An edge may
+/// only have one dot.
+pub fn parse_dots(s: &str) -> Result<Vec<Dot>, DotError> {
+    let mut seen = HashSet::new();
+    let mut dots = Vec::new();
+    for text in s.split(';').map(str::trim).filter(|text| !text.is_empty()) {
+        let malformed = || DotError::Malformed(text.to_string());
+        let (cells_text, color) = text.split_once('=').ok_or_else(malformed)?;
+        let color = match color.trim() {
+            "w" => DotColor::White,
+            "b" => DotColor::Black,
+            _ => return Err(malformed()),
+        };
+        let (a, b) = cells_text.split_once('+').ok_or_else(malformed)?;
+        let parse = |name: &str| {
+            name.trim()
+                .parse::<Cell>()
+                .map_err(|source| DotError::BadCell {
+                    dot: text.to_string(),
+                    source,
+                })
+        };
+        let (a, b) = (parse(a)?, parse(b)?);
+        if !orthogonally_adjacent(a, b) {
+            return Err(DotError::NotAdjacent(text.to_string()));
+        }
+        let edge = if a.index() < b.index() {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        if !seen.insert(edge) {
+            return Err(DotError::RepeatedEdge(edge.0, edge.1));
+        }
+        dots.push(Dot {
+            cells: [a, b],
+            color,
+        });
+    }
+    Ok(dots)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_dots, DotColor, DotError};
+
+    #[test]
+    fn parses_white_and_black_dots() {
+        let dots = parse_dots("r1c1+r1c2=w; r4c4 + r5c4 = b").unwrap();
+        assert_eq!(2, dots.len());
+        assert_eq!(DotColor::White, dots[0].color);
+        assert_eq!(DotColor::Black, dots[1].color);
+        assert_eq!("r1c1+r1c2=w", dots[0].to_string());
+        assert_eq!("r4c4+r5c4=b", dots[1].to_string());
+    }
+
+    #[test]
+    fn rejects_invalid_dots() {
+        assert!(matches!(
+            parse_dots("r1c1+r1c2"),
+            Err(DotError::Malformed(_))
+        ));
+        assert!(matches!(
+            parse_dots("r1c1+r1c2=x"),
+            Err(DotError::Malformed(_))
+        ));
+        assert!(matches!(
+            parse_dots("r1c1+r1c0=w"),
+            Err(DotError::BadCell { .. })
+        ));
+        assert!(matches!(
+            parse_dots("r1c1+r2c2=w"),
+            Err(DotError::NotAdjacent(_))
+        ));
+        assert!(matches!(
+            parse_dots("r1c1+r1c2=w;r1c2+r1c1=b"),
+            Err(DotError::RepeatedEdge(..))
+        ));
+    }
+
+    #[test]
+    fn colors_relate_digits_either_way() {
+        assert!(DotColor::White.allows(4, 5));
+        assert!(DotColor::White.allows(5, 4));
+        assert!(!DotColor::White.allows(4, 6));
+        assert!(DotColor::Black.allows(3, 6));
+        assert!(DotColor::Black.allows(8, 4));
+        assert!(!DotColor::Black.allows(3, 9));
+        // 1 and 2 satisfy both.
+        assert!(DotColor::White.allows(1, 2) && DotColor::Black.allows(2, 1));
+    }
+}
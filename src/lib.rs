@@ -0,0 +1,130 @@
+//! Generates sudoku puzzles that a chosen set of inference rules can solve, by encoding the rules
+//! of sudoku and a bounded inference circuit as a SAT formula and handing it to an external solver
+//! (kissat by default).
+//!
+//! ```no_run
+//! use sudoku_generator::{generate, Parameters};
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let params = Parameters {
+//!     givens: Some(30),
+//!     ..Parameters::default()
+//! };
+//! let puzzle = generate(&params).await?;
+//! for row in &puzzle.givens {
+//!     let line: String = row
+//!         .iter()
+//!         .map(|given| given.map_or('.', |digit| digit.symbol()))
+//!         .collect();
+//!     println!("{}", line);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, Result};
+
+use crate::board_size::board_size;
+use crate::solve::solve;
+use crate::sudoku::{Cell, Digit};
+use crate::visualize_solution::solution_to_grid;
+
+pub use crate::emit_problem::{Inferences, Parameters};
+
+pub mod adjacency;
+pub mod batch;
+pub mod board_size;
+pub mod cages;
+pub mod config;
+pub mod emit_problem;
+pub mod explain_rules;
+pub mod formula_builder;
+pub mod grid;
+mod iter_singleton;
+pub mod kropki;
+pub mod parity;
+pub mod pattern;
+mod positive_i32;
+mod propagate;
+pub mod puzzle_string;
+pub mod regions;
+pub mod render_png;
+pub mod render_svg;
+pub mod rule;
+pub mod sandwich;
+pub mod sat_solver;
+pub mod solution_frames;
+pub mod solve;
+pub mod solve_dimacs;
+pub mod solve_stdin;
+mod subsets;
+pub mod sudoku;
+pub mod symmetry;
+pub mod thermo;
+pub mod uniqueness;
+pub mod var_map;
+pub mod verify_minimal;
+pub mod visualize_solution;
+
+#[derive(Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Grid,
+    Frames,
+    /// Print nine rows of tab-separated digits, for the puzzle or, if `solution` is set, its
+    /// solution.
+    Tsv {
+        solution: bool,
+    },
+    /// Print the puzzle, or if `solution` is set its solution, as 81 characters on one line.
+    Oneline {
+        solution: bool,
+    },
+    /// Print the puzzle as an SVG image, with its solution in another color if `solution` is set.
+    Svg {
+        solution: bool,
+    },
+    /// Draw the puzzle as a PNG image with `cell_px` pixels per cell, with its solution in
+    /// another color if `solution` is set.
+    Png {
+        solution: bool,
+        cell_px: u32,
+    },
+    /// Print one line of JSON per puzzle, with its solution, parameters, and frames.
+    Jsonl,
+    /// Print the formula as a pseudo-Boolean problem instead of solving it.
+    Opb,
+    /// Print a JSON summary of the formula's variables and clauses instead of solving it.
+    FormulaStats,
+}
+
+/// A generated puzzle, indexed by row and then column from r1c1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GeneratedPuzzle {
+    /// The digit in each given cell, and `None` in the cells left for the solver.
+    pub givens: Vec<Vec<Option<Digit>>>,
+    /// The digit in every cell of the unique solution.
+    pub solution: Vec<Vec<Digit>>,
+}
+
+/// Generates one puzzle meeting `params`, running the solver without a time limit. Fails if no
+/// puzzle meets them or the solver gives up. The board is the size chosen with
+/// `board_size::set_board_size`, or 9x9 if none was chosen.
+pub async fn generate(params: &Parameters) -> Result<GeneratedPuzzle> {
+    let (variables, solution) = solve(params, None).await?;
+    let (grid, mask) = solution_to_grid(&variables, &solution)?
+        .ok_or_else(|| anyhow!("no puzzle satisfies the parameters"))?;
+
+    let side = board_size().side() as usize;
+    let mut puzzle = GeneratedPuzzle {
+        givens: vec![vec![None; side]; side],
+        solution: vec![vec![Digit::K1; side]; side],
+    };
+    for (Cell { row, col }, digit) in &grid {
+        let (r, c) = (row.index() as usize, col.index() as usize);
+        puzzle.solution[r][c] = digit;
+        if mask.is_given(Cell { row, col }) {
+            puzzle.givens[r][c] = Some(digit);
+        }
+    }
+    Ok(puzzle)
+}
@@ -1,21 +1,89 @@
+use std::collections::HashMap;
+use std::io::IsTerminal;
+use std::path::Path;
+use std::process::exit;
 use std::time::Duration;
 
-use anyhow::{anyhow, Result};
-use clap::{clap_app, ArgMatches};
-use sat_solver::SatSolver;
-use tokio::io::{stdout, AsyncWriteExt, BufWriter};
-use tokio::time::timeout;
+use anyhow::{anyhow, Context, Result};
+use clap::{clap_app, Arg, ArgMatches};
+use tokio::fs::{write, File};
+use tokio::io::{sink, stdout, AsyncWriteExt, BufWriter};
 
-use crate::emit_problem::{build_formula, Inferences, Parameters};
-use crate::visualize_solution::visualize_solution;
+use sudoku_generator::adjacency::{format_adjacency, AdjacencyFormat};
+use sudoku_generator::batch::{generate_batch, BatchOutput};
+use sudoku_generator::board_size::{board_size, set_board_size, BoardSize};
+use sudoku_generator::cages::parse_cages;
+use sudoku_generator::config::{load_config, Config};
+use sudoku_generator::emit_problem::{
+    build_formula, build_maxsat_formula, build_opb_formula, build_solving_formula,
+    build_tagged_formula, formula_report, formula_stats, EmissionStats, Inferences, Parameters,
+};
+use sudoku_generator::explain_rules::{classify, explain_rules};
+use sudoku_generator::formula_builder::Variable;
+use sudoku_generator::grid::{GivensMask, Grid};
+use sudoku_generator::kropki::parse_dots;
+use sudoku_generator::parity::{parse_parity_cells, Parity};
+use sudoku_generator::pattern::parse_pattern;
+use sudoku_generator::puzzle_string::parse_puzzle_string;
+use sudoku_generator::regions::{load_regions, set_regions};
+use sudoku_generator::render_png::render_png;
+use sudoku_generator::render_svg::render_svg;
+use sudoku_generator::rule::Rule;
+use sudoku_generator::sandwich::parse_sandwiches;
+use sudoku_generator::sat_solver::{set_solver_name, solver_name, Solution, MAXSAT_SOLVER_NAME};
+use sudoku_generator::solution_frames::{
+    candidates_at_level, difficulty, print_solution_frames, solution_frames, warn_if_unsolved,
+};
+use sudoku_generator::solve::{solve_with_retries, SolveOptions};
+use sudoku_generator::solve_dimacs::solve_dimacs;
+use sudoku_generator::solve_stdin::solve_stdin;
+use sudoku_generator::sudoku::VariableKind;
+use sudoku_generator::symmetry::Symmetry;
+use sudoku_generator::thermo::parse_thermos;
+use sudoku_generator::uniqueness::check_unique;
+use sudoku_generator::var_map::format_var_map;
+use sudoku_generator::verify_minimal::verify_minimal;
+use sudoku_generator::visualize_solution::{
+    solution_to_grid, visualize_oneline, visualize_solution, visualize_tsv,
+};
+use sudoku_generator::OutputFormat;
 
-mod emit_problem;
-pub mod formula_builder;
-mod iter_singleton;
-mod positive_i32;
-mod sat_solver;
-pub mod sudoku;
-mod visualize_solution;
+fn get_format_arg(matches: &ArgMatches) -> Result<OutputFormat> {
+    match matches.value_of("format") {
+        Some("grid") | None => Ok(OutputFormat::Grid),
+        Some("frames") => Ok(OutputFormat::Frames),
+        Some("tsv") => Ok(OutputFormat::Tsv {
+            solution: matches.is_present("solution"),
+        }),
+        Some("oneline") => Ok(OutputFormat::Oneline {
+            solution: matches.is_present("solution"),
+        }),
+        Some("svg") => Ok(OutputFormat::Svg {
+            solution: matches.is_present("solution"),
+        }),
+        Some("png") => {
+            let cell_px = matches
+                .value_of("cell_px")
+                .map(|s| s.parse::<u32>())
+                .transpose()?
+                .unwrap_or(48);
+            if cell_px == 0 {
+                return Err(anyhow!("--cell_px must be at least 1"));
+            }
+            Ok(OutputFormat::Png {
+                solution: matches.is_present("solution"),
+                cell_px,
+            })
+        }
+        Some("jsonl") => Ok(OutputFormat::Jsonl),
+        Some("opb") => Ok(OutputFormat::Opb),
+        Some("formula_stats") => Ok(OutputFormat::FormulaStats),
+        Some(x) => Err(anyhow!(
+            "expected grid, frames, tsv, oneline, svg, png, jsonl, opb, or formula_stats in --format {}",
+            x
+        )),
+    }
+}
 
 fn get_bool_arg(matches: &ArgMatches, name: &str) -> Result<Option<bool>> {
     match matches.value_of(name) {
@@ -28,62 +96,745 @@ fn get_bool_arg(matches: &ArgMatches, name: &str) -> Result<Option<bool>> {
     }
 }
 
+/// Tells the user how many repeated clauses `--dedup` dropped from the formula.
+fn report_duplicates_removed(params: &Parameters, stats: &EmissionStats) {
+    if params.dedup_clauses {
+        eprintln!("Removed {} duplicate clauses.", stats.duplicates_removed);
+    }
+}
+
+/// Writes the puzzle as a PNG image to the file named by `--out`.
+async fn write_png(
+    matches: &ArgMatches<'_>,
+    grid: &Grid,
+    mask: &GivensMask,
+    include_solution: bool,
+    cell_px: u32,
+) -> Result<()> {
+    let path = matches.value_of("out").unwrap();
+    write(path, render_png(grid, mask, include_solution, cell_px)?)
+        .await
+        .with_context(|| format!("Failed to write {}", path))
+}
+
+/// Reads the grid and its givens from `solution`, or reports that the formula is unsatisfiable and
+/// exits with status 1.
+fn grid_or_exit(
+    variables: &HashMap<VariableKind, Variable>,
+    solution: &Solution,
+) -> Result<(Grid, GivensMask)> {
+    match solution_to_grid(variables, solution)? {
+        Some(grid) => Ok(grid),
+        None => {
+            println!("UNSATISFIABLE");
+            exit(1);
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    // Every inference rule gets a flag of its own name.
+    let default_inferences = Inferences::default();
+    let rule_help: Vec<_> = Rule::ALL
+        .iter()
+        .map(|&rule| {
+            format!(
+                "Allow the solution to require {} (default {})",
+                rule.description(),
+                default_inferences.is_allowed(rule),
+            )
+        })
+        .collect();
+    let rule_args: Vec<_> = Rule::ALL
+        .iter()
+        .zip(&rule_help)
+        .map(|(rule, help)| {
+            Arg::with_name(rule.name())
+                .long(rule.name())
+                .takes_value(true)
+                .help(help)
+        })
+        .collect();
+
     let matches = clap_app!(myapp =>
+        (@arg config: --config +takes_value "Read parameters from this TOML file, whose keys match these flags with the inference rules in a [rules] table; flags given here override it")
+        (@arg input: --input +takes_value conflicts_with[givens minimize_givens] "Instead of generating a puzzle, pin the givens to this one, as 81 cells in row-major order with digits for givens and . or 0 for empty cells, and check that the enabled inference rules solve it")
         (@arg givens: --givens +takes_value "Require this many givens (default 40)")
+        (@arg no_givens_count: --no_givens_count conflicts_with[givens] "Allow any number of givens, and report how many the puzzle ended up with")
+        (@arg min_givens: --min_givens +takes_value conflicts_with[givens] "Instead of an exact count, require at least this many givens, and report how many the puzzle ended up with")
+        (@arg max_givens: --max_givens +takes_value conflicts_with[givens] "Instead of an exact count, require at most this many givens, and report how many the puzzle ended up with")
+        (@arg minimize_givens: --minimize_givens conflicts_with[givens] "Find the puzzle with the fewest givens that the enabled inference rules still solve, using a MaxSAT solver (open-wbo) instead of kissat")
         (@arg max_inference_levels: --max_inference_levels +takes_value "Instantiate the inference circuit to this depth (default 25)")
-        (@arg naked_single: --naked_single +takes_value "Allow the solution to require naked single inference (default true)")
-        (@arg hidden_single: --hidden_single +takes_value "Allow the solution to require hidden single inference (default true)")
+        (@arg relax_last_level: --relax_last_level "Don't require the last inference level to solve the board; instead warn if the solve stalls or needs more levels")
+        (@arg no_at_least_one: --no_at_least_one +hidden "Diagnostic, not for real puzzles: drop the at-least-one-digit clauses from the rules of sudoku, keeping at-most-one")
+        (@arg max_clause_count: --max_clause_count +takes_value "Give up with an error if the formula grows past this many clauses")
+        (@arg sequential_at_most_one: --sequential_at_most_one +takes_value "Encode the rules of sudoku's at-most-one constraints with a linear chain of auxiliary variables instead of pairwise clauses, for a smaller formula (default false)")
+        (@arg base_only: --base_only +takes_value requires[input] "Check the --input puzzle against only the rules of sudoku, leaving out the inference circuit and ignoring the inference rules (default true when every inference rule is off)")
+        (@arg dedup: --dedup +takes_value "Drop clauses that repeat an earlier one before handing the formula to the solver, and report how many were dropped (default false)")
+        (@arg assume_unique: --assume_unique +takes_value "Allow rules that are only sound for puzzles with a unique solution; turning this off rejects them (default true)")
+        (@arg max_chain_links: --max_chain_links +takes_value "Follow chains for chain-based inference rules up to this many links (default 4)")
+        (@arg min_distinct_techniques: --min_distinct_techniques +takes_value "Require at least this many different enabled inference rules to each be needed somewhere in the solution (default 0)")
+        (@arg min_givens_per_digit: --min_givens_per_digit +takes_value "Require every digit to appear at least this many times among the givens (default 0)")
+        (@arg min_empty_cells: --min_empty_cells +takes_value "Require at least this many cells to be left empty, so the puzzle isn't just its solution (default 0)")
+        (@arg pattern: --pattern +takes_value "Require the solution to contain these placements, given as a comma-separated list like r5c5=1,r5c6=2 (default none)")
+        (@arg diagonals: --diagonals +takes_value "Generate diagonal (X) sudoku, where each digit also appears once on both long diagonals (default false)")
+        (@arg size: --size +takes_value "Board size: 4 for 2x2 boxes, 6 for 2x3 boxes, 9 for classic 3x3 boxes, or 16 for hexadoku with 4x4 boxes and digits written 0 through F (default 9)")
+        (@arg regions: --regions +takes_value "Generate jigsaw sudoku, reading the nine regions that replace the boxes from this file of 81 digits 1 through 9 in row-major order, each naming its cell's region (default classic 3x3 boxes)")
+        (@arg anti_knight: --anti_knight +takes_value "Generate anti-knight sudoku, where cells a knight's move apart can't hold the same digit (default false)")
+        (@arg anti_king: --anti_king +takes_value "Generate anti-king sudoku, where diagonally adjacent cells can't hold the same digit (default false)")
+        (@arg symmetry: --symmetry +takes_value "Require the pattern of givens to be symmetric: rotational about the center, horizontal across the middle row, vertical across the middle column, diagonal across r1c1 to r9c9, or none (default none)")
+        (@arg cages: --cages +takes_value "Killer cages the solution must respect, separated by semicolons, each a list of cells with an optional sum like r1c1+r1c2=10 (default none)")
+        (@arg sandwiches: --sandwiches +takes_value "Sandwich clues the solution must respect, separated by semicolons, each a row or column with the sum of the digits between its 1 and its 9 like r1=10 or c4=0 (default none)")
+        (@arg thermos: --thermos +takes_value "Thermometers the solution must respect, separated by semicolons, each a path of touching cells from the bulb whose digits strictly increase like r1c1+r1c2+r2c3 (default none)")
+        (@arg kropki: --kropki +takes_value "Kropki dots the solution must respect, separated by semicolons, each a pair of orthogonally adjacent cells with w for a white dot between consecutive digits or b for a black dot between digits where one is double the other like r1c1+r1c2=w (default none)")
+        (@arg kropki_negative: --kropki_negative +takes_value "Forbid consecutive digits and digits where one is double the other across every edge without a Kropki dot (default false)")
+        (@arg even: --even +takes_value "Cells the solution must fill with even digits, separated by semicolons like r1c1;r5c5 (default none)")
+        (@arg odd: --odd +takes_value "Cells the solution must fill with odd digits, separated by semicolons like r1c1;r5c5 (default none)")
+        (@arg propagate: --propagate +takes_value "When the givens are known, propagate naked and hidden singles from them before solving (default true)")
         (@arg timeout_seconds: --timeout_seconds +takes_value "Seconds to search before giving up (default unbounded)")
-        (@arg print_formula: --print_formula "Print the SAT formula to stdout and exit")
+        (@arg timeout_ms: --timeout_ms +takes_value "Milliseconds to search before giving up, instead of --timeout_seconds (default unbounded)")
+        (@arg attempts: --attempts +takes_value "Try this many times, reseeding the solver after a timeout, before giving up (default 1)")
+        (@arg seed: --seed +takes_value "Seed the solver's random choices with this, so the same parameters and seed generate the same puzzle, as long as the solver honors --seed like kissat (default the solver's own)")
+        (@arg escalate_levels: --escalate_levels requires[attempts] "With --attempts, retry an unsatisfiable attempt with twice the inference levels")
+        (@arg solver: --solver +takes_value "Solve with this executable on the PATH, which must read DIMACS and exit with 10 or 20 like kissat (default $SUDOKU_SAT_SOLVER, or kissat)")
+        (@arg solver_timeout_arg: --solver_timeout_arg "Pass the timeout to the solver as its own time limit (kissat's --time) so it stops cleanly and prints its statistics, instead of killing it")
+        (@arg verify_model: --verify_model "Check the solver's model against every clause of the formula before using it")
+        (@arg print_formula: --print_formula "Print the SAT formula to stdout and exit, as WCNF with --minimize_givens")
+        (@arg tag_comments: --tag_comments requires[print_formula] conflicts_with[minimize_givens] "With --print_formula, start with a comment line like c tag 41 Placed r3c5 d7 for every named variable")
+        (@arg print_parameters: --print_parameters "Print the fully resolved parameters, including defaults, to stderr before running")
+        (@arg dump_adjacency: --dump_adjacency +takes_value "Print which cells see each other, including through variants like --diagonals, --anti_knight, and --anti_king, as a 0/1 matrix or an edge list (matrix or edges), and exit")
+        (@arg stats: --stats "Print how many clauses the rules of sudoku and each inference level add to the formula, and exit")
+        (@arg solver_stats: --solver_stats "After generating a puzzle, print the conflicts, decisions, propagations, and CPU time the solver reported")
+        (@arg dump_var_map: --dump_var_map "Print each tagged variable and its DIMACS number, sorted by tag, and exit")
+        (@arg verify_minimal: --verify_minimal +takes_value "Instead of generating a puzzle, check that this single-line puzzle is uniquely solvable and that every given is needed")
+        (@arg solve_dimacs: --solve_dimacs +takes_value requires[var_map] "Instead of generating a puzzle, solve this DIMACS file saved by --print_formula and show the puzzle it describes")
+        (@arg var_map: --var_map +takes_value requires[solve_dimacs] "With --solve_dimacs, read the meaning of its variables from this file saved by --dump_var_map for the same parameters")
+        (@arg from_stdin: --from_stdin "Instead of generating a puzzle, solve single-line puzzles read from stdin and print their solutions")
+        (@arg show_solution: --show_solution "With --format grid, also print the whole solution, with the givens in brackets")
+        (@arg color: --color "With --format grid, print the givens in bold and any pencil marks dim, when stdout is a terminal")
+        (@arg pencil_marks: --pencil_marks +takes_value "With --format grid, fill each empty cell with the candidates that survive to this inference level")
+        (@arg show_difficulty: --show_difficulty "After generating a puzzle, report how many inference levels it took to solve, as a difficulty score")
+        (@arg verify_unique: --verify_unique "After generating a puzzle, check with the rules of sudoku and the active variants, but no inference rules, that its givens have no other solution, and fail if they do")
+        (@arg classify: --classify "After generating a puzzle, print the techniques from a minimal set of enabled inference rules that still solves it")
+        (@arg explain_rules: --explain_rules "After generating a puzzle, report a minimal set of enabled inference rules that still solves it")
+        (@arg emit_solution_clauses: --emit_solution_clauses +takes_value "After generating a puzzle, write its solution to this file as DIMACS: the rules of sudoku plus a unit clause for every cell")
+        (@arg count: --count +takes_value "Generate this many distinct puzzles, separated by a blank line when printed over several lines (default 1)")
+        (@arg distinct_solutions: --distinct_solutions "When generating several puzzles, skip any whose solution grid is equivalent under sudoku symmetry to an earlier one")
+        (@arg output_dir: --output_dir +takes_value "Write each puzzle to its own file in this directory instead of stdout")
+        (@arg overwrite: --overwrite requires[output_dir] "Allow --output_dir to name a directory that already contains files")
+        (@arg solution: --solution "With --format tsv or oneline, write the whole solution instead of the puzzle; with --format svg or png, draw it in a second color")
+        (@arg out: --out +takes_value "With --format png, write the image to this file")
+        (@arg cell_px: --cell_px +takes_value "With --format png, draw each cell this many pixels across (default 48)")
+        (@arg format: --format +takes_value "Output format: grid prints the puzzle, frames prints the forced and eliminated placements at every inference level as JSON, tsv prints the puzzle as tab-separated rows for spreadsheets, oneline prints the puzzle as 81 characters with . for empty cells, svg prints the puzzle as an SVG image, png writes the puzzle as a PNG image to --out, jsonl prints one JSON object per puzzle per line as each is found, opb prints the formula as pseudo-Boolean constraints without solving, formula_stats prints JSON counts of the formula's variables and clauses without solving (default grid)")
     )
+    .args(&rule_args)
     .get_matches();
 
-    let params = Parameters {
-        givens: matches
-            .value_of("givens")
-            .map(|s| usize::from_str_radix(s, 10))
-            .transpose()?
-            .unwrap_or(40),
+    if let Some(name) = matches.value_of("solver") {
+        set_solver_name(name);
+    }
+
+    let config = match matches.value_of("config") {
+        Some(path) => load_config(Path::new(path)).await?,
+        None => Config::default(),
+    };
+    let mut allowed_inferences = default_inferences;
+    for rule in Rule::ALL.iter().copied() {
+        if let Some(allowed) =
+            get_bool_arg(&matches, rule.name())?.or(config.rules.get(&rule).copied())
+        {
+            allowed_inferences.set(rule, allowed);
+        }
+    }
+
+    // Every row, column, digit, and box consults the size and layout, so install them before
+    // anything looks at the board.
+    if let Some(size) = matches.value_of("size").or(config.size.as_deref()) {
+        set_board_size(size.parse::<BoardSize>().context("in --size")?)?;
+    }
+    let size = board_size();
+    if let Some(path) = matches.value_of("regions").or(config.regions.as_deref()) {
+        set_regions(load_regions(Path::new(path)).await?);
+    }
+
+    let min_givens = matches
+        .value_of("min_givens")
+        .map(|s| s.parse::<usize>())
+        .transpose()?
+        .or(config.min_givens);
+    let max_givens = matches
+        .value_of("max_givens")
+        .map(|s| s.parse::<usize>())
+        .transpose()?
+        .or(config.max_givens);
+    let minimize_givens =
+        matches.is_present("minimize_givens") || config.minimize_givens.unwrap_or(false);
+    let mut params = Parameters {
+        givens: if matches.is_present("no_givens_count")
+            || matches.is_present("input")
+            || minimize_givens
+            || min_givens.is_some()
+            || max_givens.is_some()
+            || (!matches.is_present("givens") && config.no_givens_count == Some(true))
+        {
+            None
+        } else {
+            Some(
+                matches
+                    .value_of("givens")
+                    .map(|s| s.parse::<usize>())
+                    .transpose()?
+                    .or(config.givens)
+                    .unwrap_or(40),
+            )
+        },
+        min_givens,
+        max_givens,
         inference_levels: {
             let value = matches
                 .value_of("max_inference_levels")
-                .map(|s| usize::from_str_radix(s, 10))
+                .map(|s| s.parse::<usize>())
                 .transpose()?
+                .or(config.max_inference_levels)
                 .unwrap_or(25);
             if value < 1 {
                 return Err(anyhow!("--max_inference_levels must be at least 1"));
             }
             value
         },
-        allowed_inferences: Inferences {
-            naked_single: get_bool_arg(&matches, "naked_single")?.unwrap_or(true),
-            hidden_single: get_bool_arg(&matches, "hidden_single")?.unwrap_or(true),
+        relax_last_level: matches.is_present("relax_last_level")
+            || config.relax_last_level.unwrap_or(false),
+        no_at_least_one: matches.is_present("no_at_least_one"),
+        max_clause_count: matches
+            .value_of("max_clause_count")
+            .map(|s| s.parse::<usize>())
+            .transpose()?
+            .or(config.max_clause_count),
+        sequential_at_most_one: get_bool_arg(&matches, "sequential_at_most_one")?
+            .or(config.sequential_at_most_one)
+            .unwrap_or(false),
+        dedup_clauses: get_bool_arg(&matches, "dedup")?
+            .or(config.dedup)
+            .unwrap_or(false),
+        max_chain_links: {
+            let value = matches
+                .value_of("max_chain_links")
+                .map(|s| s.parse::<usize>())
+                .transpose()?
+                .or(config.max_chain_links)
+                .unwrap_or(4);
+            if value < 1 {
+                return Err(anyhow!("--max_chain_links must be at least 1"));
+            }
+            value
         },
+        min_distinct_techniques: matches
+            .value_of("min_distinct_techniques")
+            .map(|s| s.parse::<usize>())
+            .transpose()?
+            .or(config.min_distinct_techniques)
+            .unwrap_or(0),
+        min_givens_per_digit: matches
+            .value_of("min_givens_per_digit")
+            .map(|s| s.parse::<usize>())
+            .transpose()?
+            .or(config.min_givens_per_digit)
+            .unwrap_or(0),
+        min_empty_cells: matches
+            .value_of("min_empty_cells")
+            .map(|s| s.parse::<usize>())
+            .transpose()?
+            .or(config.min_empty_cells)
+            .unwrap_or(0),
+        allowed_inferences,
+        fixed_givens: matches
+            .value_of("input")
+            .map(|s| parse_puzzle_string(s).context("in --input"))
+            .transpose()?,
+        propagate: get_bool_arg(&matches, "propagate")?
+            .or(config.propagate)
+            .unwrap_or(true),
+        pattern: matches
+            .value_of("pattern")
+            .or(config.pattern.as_deref())
+            .map(parse_pattern)
+            .transpose()?
+            .unwrap_or_default(),
+        diagonals: get_bool_arg(&matches, "diagonals")?
+            .or(config.diagonals)
+            .unwrap_or(false),
+        anti_knight: get_bool_arg(&matches, "anti_knight")?
+            .or(config.anti_knight)
+            .unwrap_or(false),
+        anti_king: get_bool_arg(&matches, "anti_king")?
+            .or(config.anti_king)
+            .unwrap_or(false),
+        symmetry: matches
+            .value_of("symmetry")
+            .or(config.symmetry.as_deref())
+            .map(|s| s.parse::<Symmetry>().context("in --symmetry"))
+            .transpose()?
+            .unwrap_or_default(),
+        cages: matches
+            .value_of("cages")
+            .or(config.cages.as_deref())
+            .map(parse_cages)
+            .transpose()?
+            .unwrap_or_default(),
+        sandwiches: matches
+            .value_of("sandwiches")
+            .or(config.sandwiches.as_deref())
+            .map(parse_sandwiches)
+            .transpose()?
+            .unwrap_or_default(),
+        thermos: matches
+            .value_of("thermos")
+            .or(config.thermos.as_deref())
+            .map(parse_thermos)
+            .transpose()?
+            .unwrap_or_default(),
+        dots: matches
+            .value_of("kropki")
+            .or(config.kropki.as_deref())
+            .map(parse_dots)
+            .transpose()?
+            .unwrap_or_default(),
+        kropki_negative: get_bool_arg(&matches, "kropki_negative")?
+            .or(config.kropki_negative)
+            .unwrap_or(false),
+        even_cells: matches
+            .value_of("even")
+            .or(config.even.as_deref())
+            .map(|s| parse_parity_cells(s, Parity::Even))
+            .transpose()?
+            .unwrap_or_default(),
+        odd_cells: matches
+            .value_of("odd")
+            .or(config.odd.as_deref())
+            .map(|s| parse_parity_cells(s, Parity::Odd))
+            .transpose()?
+            .unwrap_or_default(),
+        excluded_puzzles: Vec::new(),
+        excluded_solutions: Vec::new(),
+        base_only: false,
     };
-    let timeout_duration = matches
-        .value_of("timeout_seconds")
-        .map(|s| -> Result<Duration> { Ok(Duration::from_secs(u64::from_str_radix(s, 10)?)) })
+    if let Some(cell) = params
+        .even_cells
+        .iter()
+        .find(|cell| params.odd_cells.contains(cell))
+    {
+        return Err(anyhow!("{} can't be both --even and --odd", cell));
+    }
+    params.check_given_counts()?;
+    let enabled_rule_count = params.allowed_inferences.enabled_rules().len();
+    if params.min_distinct_techniques > 0 && params.inference_levels < 2 {
+        return Err(anyhow!(
+            "--min_distinct_techniques needs --max_inference_levels of at least 2, since level 0 only holds the givens"
+        ));
+    }
+    if params.min_distinct_techniques > enabled_rule_count {
+        return Err(anyhow!(
+            "--min_distinct_techniques is {} but only {} inference rules are enabled",
+            params.min_distinct_techniques,
+            enabled_rule_count,
+        ));
+    }
+    if matches.is_present("distinct_solutions") && size == BoardSize::HEXADOKU {
+        return Err(anyhow!(
+            "--distinct_solutions searches every symmetry of each grid, which is too slow on 16x16 boards"
+        ));
+    }
+    // Without any inference rules the circuit can't solve anything, so a known puzzle is only worth
+    // checking against the rules of sudoku.
+    params.base_only = get_bool_arg(&matches, "base_only")?
+        .or(config.base_only)
+        .unwrap_or(params.fixed_givens.is_some() && enabled_rule_count == 0);
+    if params.base_only && params.fixed_givens.is_none() {
+        return Err(anyhow!(
+            "--base_only checks a known puzzle, so it needs --input"
+        ));
+    }
+    let assume_unique = get_bool_arg(&matches, "assume_unique")?
+        .or(config.assume_unique)
+        .unwrap_or(true);
+    params
+        .allowed_inferences
+        .check_assume_unique(assume_unique)?;
+    let format = get_format_arg(&matches)?;
+    if matches.is_present("solution")
+        && !matches!(
+            format,
+            OutputFormat::Tsv { .. }
+                | OutputFormat::Oneline { .. }
+                | OutputFormat::Svg { .. }
+                | OutputFormat::Png { .. }
+        )
+    {
+        return Err(anyhow!(
+            "--solution only applies to --format tsv, oneline, svg, or png"
+        ));
+    }
+    if !matches!(format, OutputFormat::Png { .. }) {
+        if let Some(flag) = ["out", "cell_px"]
+            .iter()
+            .find(|name| matches.is_present(name))
+        {
+            return Err(anyhow!("--{} only applies to --format png", flag));
+        }
+    }
+    if let OutputFormat::Png { .. } = format {
+        match (matches.is_present("out"), matches.is_present("output_dir")) {
+            (true, true) => return Err(anyhow!("--out and --output_dir cannot both be given")),
+            (false, false) => {
+                return Err(anyhow!(
+                    "--format png writes a file, so it needs --out, or --output_dir for several puzzles"
+                ))
+            }
+            _ => (),
+        }
+    }
+    if matches.is_present("show_solution") && !matches!(format, OutputFormat::Grid) {
+        return Err(anyhow!("--show_solution only applies to --format grid"));
+    }
+    let pencil_marks = matches
+        .value_of("pencil_marks")
+        .map(|s| s.parse::<usize>())
         .transpose()?;
+    if pencil_marks.is_some() && !matches!(format, OutputFormat::Grid) {
+        return Err(anyhow!("--pencil_marks only applies to --format grid"));
+    }
+    if params.base_only {
+        let needs_circuit = [
+            ("--format frames", matches!(format, OutputFormat::Frames)),
+            ("--pencil_marks", pencil_marks.is_some()),
+            ("--show_difficulty", matches.is_present("show_difficulty")),
+            ("--classify", matches.is_present("classify")),
+            ("--explain_rules", matches.is_present("explain_rules")),
+        ];
+        if let Some((flag, _)) = needs_circuit.iter().find(|(_, present)| *present) {
+            return Err(anyhow!(
+                "{} reads the inference circuit, which --base_only leaves out; pass --base_only false to keep it",
+                flag,
+            ));
+        }
+    }
+    // Escape sequences would only clutter output that's going to a file or another program.
+    let color = matches.is_present("color") && std::io::stdout().is_terminal();
+    if let OutputFormat::Opb | OutputFormat::FormulaStats = format {
+        if matches.is_present("count") || matches.is_present("output_dir") {
+            return Err(anyhow!(
+                "--format {} doesn't solve, so it can't be combined with --count or --output_dir",
+                matches.value_of("format").unwrap(),
+            ));
+        }
+    }
+    let timeout_duration = match (
+        matches.value_of("timeout_seconds"),
+        matches.value_of("timeout_ms"),
+    ) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "--timeout_seconds and --timeout_ms cannot both be given"
+            ))
+        }
+        (Some(s), None) => Some(Duration::from_secs(s.parse()?)),
+        (None, Some(s)) => Some(Duration::from_millis(s.parse()?)),
+        (None, None) => None,
+    };
+
+    if matches.is_present("solver_timeout_arg") && timeout_duration.is_none() {
+        return Err(anyhow!(
+            "--solver_timeout_arg needs --timeout_seconds or --timeout_ms"
+        ));
+    }
+    if matches.is_present("solver_timeout_arg") && minimize_givens {
+        return Err(anyhow!(
+            "--solver_timeout_arg only applies to kissat, not the MaxSAT solver used by --minimize_givens"
+        ));
+    }
+
+    if matches.is_present("seed") && minimize_givens {
+        return Err(anyhow!(
+            "--seed only applies to kissat, not the MaxSAT solver used by --minimize_givens"
+        ));
+    }
+
+    let attempts = matches
+        .value_of("attempts")
+        .map(|s| s.parse::<usize>())
+        .transpose()?
+        .unwrap_or(1);
+    if attempts < 1 {
+        return Err(anyhow!("--attempts must be at least 1"));
+    }
+    let options = SolveOptions {
+        timeout: timeout_duration,
+        attempts,
+        seed: matches
+            .value_of("seed")
+            .map(|s| s.parse::<u64>())
+            .transpose()?,
+        escalate_levels: matches.is_present("escalate_levels"),
+        verify_model: matches.is_present("verify_model"),
+        native_timeout: matches.is_present("solver_timeout_arg"),
+        minimize_givens,
+    };
+
+    if matches.is_present("print_parameters") {
+        eprintln!(
+            "solver: {}",
+            if minimize_givens {
+                MAXSAT_SOLVER_NAME
+            } else {
+                solver_name()
+            }
+        );
+        eprintln!("{:?}", options);
+        eprintln!("format: {:?}", format);
+        eprintln!("{:#?}", params);
+    }
+
+    if let Some(puzzle) = matches.value_of("verify_minimal") {
+        return verify_minimal(&params, puzzle).await;
+    }
+
+    if let Some(path) = matches.value_of("solve_dimacs") {
+        if !matches!(
+            format,
+            OutputFormat::Grid
+                | OutputFormat::Tsv { .. }
+                | OutputFormat::Oneline { .. }
+                | OutputFormat::Svg { .. }
+                | OutputFormat::Png { .. }
+        ) {
+            return Err(anyhow!(
+                "--solve_dimacs only knows the placements and givens, so it supports --format grid, tsv, oneline, svg, or png"
+            ));
+        }
+        let var_map_path = Path::new(matches.value_of("var_map").unwrap());
+        let (variables, solution) = solve_dimacs(Path::new(path), var_map_path).await?;
+        let (grid, mask) = grid_or_exit(&variables, &solution)?;
+        match format {
+            OutputFormat::Grid => visualize_solution(
+                &grid,
+                &mask,
+                None,
+                matches.is_present("show_solution"),
+                color,
+            ),
+            OutputFormat::Tsv { solution: include } => visualize_tsv(&grid, &mask, include),
+            OutputFormat::Oneline { solution: include } => visualize_oneline(&grid, &mask, include),
+            OutputFormat::Svg { solution: include } => {
+                print!("{}", render_svg(&grid, &mask, include))
+            }
+            OutputFormat::Png {
+                solution: include,
+                cell_px,
+            } => write_png(&matches, &grid, &mask, include, cell_px).await?,
+            _ => unreachable!(),
+        }
+        return Ok(());
+    }
+
+    if matches.is_present("from_stdin") {
+        return solve_stdin(&params).await;
+    }
+
+    if let Some(s) = matches.value_of("dump_adjacency") {
+        print!(
+            "{}",
+            format_adjacency(AdjacencyFormat::parse(s)?, &params.adjacency())
+        );
+        return Ok(());
+    }
+
+    if matches.is_present("stats") {
+        print!("{}", formula_stats(&params)?.format_table());
+        return Ok(());
+    }
+
+    if matches.is_present("dump_var_map") {
+        let (variables, _) = build_formula(&mut sink(), &params).await?;
+        print!("{}", format_var_map(&variables));
+        return Ok(());
+    }
+
+    if let OutputFormat::FormulaStats = format {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&formula_report(&params)?)?
+        );
+        return Ok(());
+    }
+
+    if let OutputFormat::Opb = format {
+        let mut w = BufWriter::new(stdout());
+        build_opb_formula(&mut w, &params).await?;
+        w.shutdown().await?;
+        return Ok(());
+    }
 
     if matches.is_present("print_formula") {
         let mut w = BufWriter::new(stdout());
-        build_formula(&mut w, &params).await?;
+        if minimize_givens {
+            let (_, stats) = build_maxsat_formula(&mut w, &params).await?;
+            report_duplicates_removed(&params, &stats);
+        } else if matches.is_present("tag_comments") {
+            build_tagged_formula(&mut w, &params).await?;
+        } else {
+            let (_, stats) = build_formula(&mut w, &params).await?;
+            report_duplicates_removed(&params, &stats);
+        }
         w.shutdown().await?;
         return Ok(());
     }
 
-    let mut solver = SatSolver::start().await?;
-    let variables = build_formula(solver.input(), &params).await?;
+    let count = matches
+        .value_of("count")
+        .map(|s| s.parse::<usize>())
+        .transpose()?
+        .unwrap_or(1);
+    if count < 1 {
+        return Err(anyhow!("--count must be at least 1"));
+    }
+    if count > 1 || matches.is_present("output_dir") || matches!(format, OutputFormat::Jsonl) {
+        if matches.is_present("emit_solution_clauses") {
+            return Err(anyhow!(
+                "--emit_solution_clauses writes a single solution, so it can't be combined with --count, --output_dir, or --format jsonl"
+            ));
+        }
+        if matches.is_present("verify_unique") {
+            return Err(anyhow!(
+                "--verify_unique checks a single puzzle, so it can't be combined with --count, --output_dir, or --format jsonl"
+            ));
+        }
+        if matches.is_present("show_solution") || pencil_marks.is_some() {
+            return Err(anyhow!(
+                "--show_solution and --pencil_marks print a single puzzle, so they can't be combined with --count or --output_dir"
+            ));
+        }
+        if let OutputFormat::Png { .. } = format {
+            if matches.is_present("out") {
+                return Err(anyhow!(
+                    "--out names a single image, so --format png with --count needs --output_dir instead"
+                ));
+            }
+        }
+        let output = match matches.value_of("output_dir") {
+            Some(path) => BatchOutput::Directory {
+                path: Path::new(path),
+                overwrite: matches.is_present("overwrite"),
+            },
+            None => BatchOutput::Stdout,
+        };
+        return generate_batch(
+            &params,
+            count,
+            format,
+            output,
+            options,
+            matches.is_present("distinct_solutions"),
+        )
+        .await;
+    }
+
+    let (variables, solution, solver_stats, emission_stats) =
+        solve_with_retries(&params, options).await?;
+    report_duplicates_removed(&params, &emission_stats);
+    if params.relax_last_level {
+        if let Solution::Satisfiable { assignments } = &solution {
+            warn_if_unsolved(&variables, assignments);
+        }
+    }
 
-    let solution = if let Some(duration) = timeout_duration {
-        timeout(duration, solver.solve()).await??
-    } else {
-        solver.solve().await?
-    };
+    match format {
+        OutputFormat::Grid => {
+            let (grid, mask) = grid_or_exit(&variables, &solution)?;
+            let candidates = match (pencil_marks, &solution) {
+                (Some(level), Solution::Satisfiable { assignments }) => {
+                    Some(candidates_at_level(&variables, assignments, level)?)
+                }
+                _ => None,
+            };
+            visualize_solution(
+                &grid,
+                &mask,
+                candidates.as_ref(),
+                matches.is_present("show_solution"),
+                color,
+            );
+        }
+        OutputFormat::Frames => print_solution_frames(&variables, &solution).await?,
+        OutputFormat::Tsv { solution: include } => {
+            let (grid, mask) = grid_or_exit(&variables, &solution)?;
+            visualize_tsv(&grid, &mask, include);
+        }
+        OutputFormat::Oneline { solution: include } => {
+            let (grid, mask) = grid_or_exit(&variables, &solution)?;
+            visualize_oneline(&grid, &mask, include);
+        }
+        OutputFormat::Svg { solution: include } => {
+            let (grid, mask) = grid_or_exit(&variables, &solution)?;
+            print!("{}", render_svg(&grid, &mask, include));
+        }
+        OutputFormat::Png {
+            solution: include,
+            cell_px,
+        } => {
+            let (grid, mask) = grid_or_exit(&variables, &solution)?;
+            write_png(&matches, &grid, &mask, include, cell_px).await?;
+        }
+        OutputFormat::Jsonl | OutputFormat::Opb | OutputFormat::FormulaStats => unreachable!(),
+    }
+
+    if matches.is_present("solver_stats") {
+        print!("{}", solver_stats.format_summary());
+    }
+
+    if matches.is_present("verify_unique") {
+        if let Solution::Satisfiable { .. } = solution {
+            if !check_unique(&params, &variables, &solution).await? {
+                return Err(anyhow!(
+                    "the puzzle's givens have more than one solution under the rules of sudoku and its variants"
+                ));
+            }
+            println!("Verified that the puzzle has a unique solution.");
+        }
+    }
+
+    if params.givens.is_none() {
+        if let Solution::Satisfiable { .. } = solution {
+            let mask = solution.givens_mask(&variables)?;
+            println!("Found a puzzle with {} givens.", mask.count());
+        }
+    }
+
+    if matches.is_present("show_difficulty") {
+        if let Solution::Satisfiable { assignments } = &solution {
+            match difficulty(&solution_frames(&variables, assignments)) {
+                Some(level) => println!("Difficulty: solved at inference level {}.", level),
+                None => println!("Difficulty: not solved within the inference levels."),
+            }
+        }
+    }
 
-    visualize_solution(&variables, &solution).await?;
+    if let Some(path) = matches.value_of("emit_solution_clauses") {
+        let grid = solution.to_grid(&variables)?;
+        let file = File::create(path)
+            .await
+            .with_context(|| format!("Failed to create {}", path))?;
+        let mut w = BufWriter::new(file);
+        build_solving_formula(&mut w, &params, &grid.placements(), false).await?;
+        w.shutdown().await?;
+    }
+
+    if matches.is_present("classify") {
+        classify(&params, &variables, &solution, timeout_duration).await?;
+    }
+    if matches.is_present("explain_rules") {
+        explain_rules(&params, &variables, &solution, timeout_duration).await?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,101 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::sudoku::{Cell, CellNameError, Digit};
+
+/// Whether a shaded cell must hold an even or an odd digit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parity {
+    Even,
+    Odd,
+}
+
+impl Parity {
+    /// Whether `digit` has this parity.
+    pub fn allows(self, digit: Digit) -> bool {
+        match self {
+            Parity::Even => digit.as_u8().is_multiple_of(2),
+            Parity::Odd => !digit.as_u8().is_multiple_of(2),
+        }
+    }
+}
+
+impl Display for Parity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Parity::Even => write!(f, "even"),
+            Parity::Odd => write!(f, "odd"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum ParityError {
+    #[error("in the {parity} cells: {source}")]
+    BadCell {
+        parity: Parity,
+        source: CellNameError,
+    },
+
+    #[error("{cell} appears more than once among the {parity} cells")]
+    RepeatedCell { parity: Parity, cell: Cell },
+}
+
+/// Parses a semicolon-separated list of cells that must hold digits of `parity`, like
+/// `r1c1;r5c5`.
+pub fn parse_parity_cells(s: &str, parity: Parity) -> Result<Vec<Cell>, ParityError> {
+    let mut seen = HashSet::new();
+    let mut cells = Vec::new();
+    for name in s.split(';').map(str::trim).filter(|name| !name.is_empty()) {
+        let cell = name
+            .parse::<Cell>()
+            .map_err(|source| ParityError::BadCell { parity, source })?;
+        if !seen.insert(cell) {
+            return Err(ParityError::RepeatedCell { parity, cell });
+        }
+        cells.push(cell);
+    }
+    Ok(cells)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_parity_cells, Parity, ParityError};
+    use crate::sudoku::{Cell, Digit};
+
+    #[test]
+    fn parses_cell_lists() {
+        let cells = parse_parity_cells("r1c1; r5c5", Parity::Even).unwrap();
+        assert_eq!(
+            vec!["r1c1".parse::<Cell>().unwrap(), "r5c5".parse().unwrap()],
+            cells
+        );
+        assert!(matches!(
+            parse_parity_cells("r1c1;r0c1", Parity::Odd),
+            Err(ParityError::BadCell {
+                parity: Parity::Odd,
+                ..
+            })
+        ));
+        assert!(matches!(
+            parse_parity_cells("r1c1;r1c1", Parity::Even),
+            Err(ParityError::RepeatedCell { .. })
+        ));
+    }
+
+    #[test]
+    fn parities_split_the_digits() {
+        let even: Vec<_> = Digit::values()
+            .filter(|&digit| Parity::Even.allows(digit))
+            .map(Digit::as_u8)
+            .collect();
+        let odd: Vec<_> = Digit::values()
+            .filter(|&digit| Parity::Odd.allows(digit))
+            .map(Digit::as_u8)
+            .collect();
+        assert_eq!(vec![2, 4, 6, 8], even);
+        assert_eq!(vec![1, 3, 5, 7, 9], odd);
+    }
+}
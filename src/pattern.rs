@@ -0,0 +1,119 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::sudoku::{Cell, CellNameError, Digit, OutOfRangeError};
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum PatternError {
+    #[error("expected an entry like r1c2=3, but found {0:?}")]
+    Malformed(String),
+
+    #[error("in {entry:?}: {source}")]
+    OutOfRange {
+        entry: String,
+        source: OutOfRangeError,
+    },
+
+    #[error("{cell} is given both {first} and {second}")]
+    ConflictingDigits { cell: String, first: u8, second: u8 },
+
+    #[error("{digit} appears at both {first} and {second}, which share a house")]
+    RepeatedDigit {
+        digit: u8,
+        first: String,
+        second: String,
+    },
+}
+
+/// Parses a comma-separated list of solution constraints like `r5c5=1,r5c6=2`. Rejects lists that
+/// assign two digits to one cell or repeat a digit within a house, since no solution could satisfy
+/// them.
+pub fn parse_pattern(s: &str) -> Result<HashMap<Cell, Digit>, PatternError> {
+    let mut pattern: HashMap<Cell, Digit> = HashMap::new();
+    for entry in s
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let malformed = || PatternError::Malformed(entry.to_string());
+        let out_of_range = |source| PatternError::OutOfRange {
+            entry: entry.to_string(),
+            source,
+        };
+
+        let (position, digit) = entry.split_once('=').ok_or_else(malformed)?;
+        let cell = position.parse::<Cell>().map_err(|e| match e {
+            CellNameError::Malformed(_) => malformed(),
+            CellNameError::OutOfRange(source) => out_of_range(source),
+        })?;
+        let digit = digit.parse::<Digit>().map_err(out_of_range)?;
+
+        for (&other_cell, &other_digit) in &pattern {
+            if other_cell == cell && other_digit != digit {
+                return Err(PatternError::ConflictingDigits {
+                    cell: cell.to_string(),
+                    first: other_digit.as_u8(),
+                    second: digit.as_u8(),
+                });
+            }
+            if other_digit == digit && other_cell.sees_other(cell) {
+                return Err(PatternError::RepeatedDigit {
+                    digit: digit.as_u8(),
+                    first: other_cell.to_string(),
+                    second: cell.to_string(),
+                });
+            }
+        }
+        pattern.insert(cell, digit);
+    }
+    Ok(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pattern, PatternError};
+    use crate::sudoku::{Cell, Col, Digit, Row};
+
+    #[test]
+    fn parses_entries() {
+        let pattern = parse_pattern("r5c5=1, r5c6=2,r1c1=1,r5c5=1").unwrap();
+        assert_eq!(3, pattern.len());
+        assert_eq!(
+            Some(&Digit::K2),
+            pattern.get(&Cell {
+                row: Row::K5,
+                col: Col::K6,
+            })
+        );
+        assert!(parse_pattern("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_bad_entries() {
+        assert_eq!(
+            Err(PatternError::Malformed("r1c1".to_string())),
+            parse_pattern("r1c1")
+        );
+        assert!(matches!(
+            parse_pattern("r0c1=1"),
+            Err(PatternError::OutOfRange { .. })
+        ));
+        assert!(matches!(
+            parse_pattern("r1c1=10"),
+            Err(PatternError::OutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_inconsistent_patterns() {
+        assert!(matches!(
+            parse_pattern("r1c1=1,r1c1=2"),
+            Err(PatternError::ConflictingDigits { .. })
+        ));
+        assert!(matches!(
+            parse_pattern("r1c1=1,r2c2=1"),
+            Err(PatternError::RepeatedDigit { .. })
+        ));
+    }
+}
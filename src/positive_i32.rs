@@ -68,27 +68,27 @@ impl TryFrom<u32> for PositiveI32 {
     }
 }
 
-impl Into<i32> for PositiveI32 {
-    fn into(self) -> i32 {
-        self.as_i32()
+impl From<PositiveI32> for i32 {
+    fn from(value: PositiveI32) -> i32 {
+        value.as_i32()
     }
 }
 
-impl Into<u32> for PositiveI32 {
-    fn into(self) -> u32 {
-        self.as_u32()
+impl From<PositiveI32> for u32 {
+    fn from(value: PositiveI32) -> u32 {
+        value.as_u32()
     }
 }
 
-impl Into<NonZeroI32> for PositiveI32 {
-    fn into(self) -> NonZeroI32 {
-        self.as_non_zero_i32()
+impl From<PositiveI32> for NonZeroI32 {
+    fn from(value: PositiveI32) -> NonZeroI32 {
+        value.as_non_zero_i32()
     }
 }
 
-impl Into<NonZeroU32> for PositiveI32 {
-    fn into(self) -> NonZeroU32 {
-        self.as_non_zero_u32()
+impl From<PositiveI32> for NonZeroU32 {
+    fn from(value: PositiveI32) -> NonZeroU32 {
+        value.as_non_zero_u32()
     }
 }
 
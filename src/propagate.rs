@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::board_size::board_size;
+use crate::sudoku::{houses, Cell, Col, Digit, DigitSet, Row};
+
+/// The digits still possible in every cell of a puzzle.
+#[derive(Clone, PartialEq, Eq)]
+pub struct CandidateGrid {
+    candidates: Vec<DigitSet>,
+}
+
+/// Draws each cell as a block of its candidates shaped like a box, 3x3 on a classic board, with `.`
+/// for eliminated digits, so intermediate propagation states can be read at a glance.
+impl fmt::Debug for CandidateGrid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let size = board_size();
+        let (height, width) = (size.box_height() as u32, size.box_width() as u32);
+        let stacks = (size.side() as u32 / width) as usize;
+        let segment = (width * (width + 1) + 1) as usize;
+        let border = format!("+{}", format!("{}+", "-".repeat(segment)).repeat(stacks));
+        let spacer = format!("|{}", format!("{}|", " ".repeat(segment)).repeat(stacks));
+        for row in Row::values() {
+            if row.index() % height == 0 {
+                writeln!(f, "{}", border)?;
+            }
+            for line in 0..height {
+                write!(f, "|")?;
+                for col in Col::values() {
+                    let candidates = self.candidates(Cell { row, col });
+                    write!(f, " ")?;
+                    for digit in Digit::values()
+                        .skip((width * line) as usize)
+                        .take(width as usize)
+                    {
+                        if candidates.contains(digit) {
+                            write!(f, "{}", digit.symbol())?;
+                        } else {
+                            write!(f, ".")?;
+                        }
+                    }
+                    if col.index() % width == width - 1 {
+                        write!(f, " |")?;
+                    }
+                }
+                writeln!(f)?;
+            }
+            if row.index() % height != height - 1 {
+                writeln!(f, "{}", spacer)?;
+            }
+        }
+        writeln!(f, "{}", border)
+    }
+}
+
+impl CandidateGrid {
+    /// A grid where each given cell holds only its digit and every other cell holds all digits.
+    pub fn from_givens(givens: &HashMap<Cell, Digit>) -> Self {
+        let mut grid = Self {
+            candidates: vec![DigitSet::all(); board_size().cell_count()],
+        };
+        for (&cell, &digit) in givens {
+            grid.candidates[cell.index()] = [digit].iter().copied().collect();
+        }
+        grid
+    }
+
+    pub fn candidates(&self, cell: Cell) -> DigitSet {
+        self.candidates[cell.index()]
+    }
+
+    /// Applies naked and hidden singles until neither makes progress. Stops early if some cell
+    /// runs out of candidates, which means the givens have no solution.
+    pub fn propagate(&mut self) {
+        let houses = houses();
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            // Naked singles: a cell's only candidate is removed from all of its peers.
+            for cell in Cell::values() {
+                let candidates = self.candidates(cell);
+                if candidates.is_empty() {
+                    return;
+                }
+                if candidates.len() != 1 {
+                    continue;
+                }
+                let digit = candidates.iter().next().unwrap();
+                for peer in Cell::values().filter(|&peer| cell.sees_other(peer)) {
+                    let peer_candidates = &mut self.candidates[peer.index()];
+                    if peer_candidates.contains(digit) {
+                        peer_candidates.remove(digit);
+                        changed = true;
+                    }
+                }
+            }
+
+            // Hidden singles: a digit with only one possible cell in a house is placed there.
+            for house in &houses {
+                for digit in Digit::values() {
+                    let mut cells = house
+                        .iter()
+                        .copied()
+                        .filter(|&cell| self.candidates(cell).contains(digit));
+                    if let (Some(cell), None) = (cells.next(), cells.next()) {
+                        let candidates = &mut self.candidates[cell.index()];
+                        if candidates.len() != 1 {
+                            *candidates = [digit].iter().copied().collect();
+                            changed = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CandidateGrid;
+    use crate::puzzle_string::parse_puzzle_string;
+    use crate::sudoku::Cell;
+
+    #[test]
+    fn singles_solve_an_easy_puzzle() {
+        let givens = parse_puzzle_string(
+            "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79",
+        )
+        .unwrap();
+        let solution = parse_puzzle_string(
+            "534678912672195348198342567859761423426853791713924856961537284287419635345286179",
+        )
+        .unwrap();
+
+        let mut grid = CandidateGrid::from_givens(&givens);
+        grid.propagate();
+        for cell in Cell::values() {
+            let candidates = grid.candidates(cell);
+            assert_eq!(1, candidates.len());
+            assert!(candidates.contains(solution[&cell]));
+        }
+    }
+
+    #[test]
+    fn debug_shows_candidates() {
+        let givens = parse_puzzle_string(&format!("5{}", ".".repeat(80))).unwrap();
+        let mut grid = CandidateGrid::from_givens(&givens);
+        grid.propagate();
+        let lines: Vec<_> = format!("{:?}", grid).lines().map(str::to_string).collect();
+        assert_eq!(37, lines.len());
+        assert_eq!("+-------------+-------------+-------------+", lines[0]);
+        assert_eq!("| ... 123 123 | 123 123 123 | 123 123 123 |", lines[1]);
+        assert_eq!("| .5. 4.6 4.6 | 4.6 4.6 4.6 | 4.6 4.6 4.6 |", lines[2]);
+        assert_eq!("| ... 789 789 | 789 789 789 | 789 789 789 |", lines[3]);
+        assert_eq!("|             |             |             |", lines[4]);
+    }
+
+    #[test]
+    fn contradiction_empties_a_cell() {
+        // Two 1s in the same row each eliminate the other's only candidate.
+        let givens = parse_puzzle_string(&format!("11{}", ".".repeat(79))).unwrap();
+        let mut grid = CandidateGrid::from_givens(&givens);
+        grid.propagate();
+        assert!(Cell::values().any(|cell| grid.candidates(cell).is_empty()));
+    }
+}
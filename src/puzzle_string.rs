@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::board_size::board_size;
+use crate::sudoku::{Cell, Digit};
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum PuzzleStringError {
+    #[error("expected {expected} cells, but found {found}")]
+    WrongLength { expected: usize, found: usize },
+
+    #[error("unexpected character {character:?} at cell {position}")]
+    BadCharacter { character: char, position: usize },
+}
+
+/// Parses the standard single-line puzzle format: one character per cell in row-major order, 81
+/// on a classic board, with digits for givens and `.` or `0` for empty cells. Hexadoku writes its
+/// digits `0` through `F`, so only `.` is empty there. Whitespace is ignored, so the cells may also
+/// be split across lines, one row per line.
+pub fn parse_puzzle_string(s: &str) -> Result<HashMap<Cell, Digit>, PuzzleStringError> {
+    let characters: Vec<_> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let expected = board_size().cell_count();
+    if characters.len() != expected {
+        return Err(PuzzleStringError::WrongLength {
+            expected,
+            found: characters.len(),
+        });
+    }
+
+    let mut givens = HashMap::new();
+    for (cell, (position, &character)) in Cell::values().zip(characters.iter().enumerate()) {
+        if character == '.' || (character == '0' && !board_size().uses_hex_symbols()) {
+            continue;
+        }
+        let digit = Digit::from_symbol(character).ok_or(PuzzleStringError::BadCharacter {
+            character,
+            position: position + 1,
+        })?;
+        givens.insert(cell, digit);
+    }
+    Ok(givens)
+}
+
+/// Formats placements in the single-line puzzle format, using `.` for empty cells.
+pub fn format_puzzle_string(digits: &HashMap<Cell, Digit>) -> String {
+    Cell::values()
+        .map(|cell| match digits.get(&cell) {
+            Some(digit) => digit.symbol(),
+            None => '.',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_puzzle_string, parse_puzzle_string, PuzzleStringError};
+    use crate::board_size::{with_board_size, BoardSize};
+
+    const PUZZLE: &str =
+        "53..7....6..195....98....6.8...6...34..8.3..17...2...6.6....28....419..5....8..79";
+
+    #[test]
+    fn round_trip() {
+        let givens = parse_puzzle_string(PUZZLE).unwrap();
+        assert_eq!(30, givens.len());
+        assert_eq!(PUZZLE, format_puzzle_string(&givens));
+        assert_eq!(
+            givens,
+            parse_puzzle_string(&PUZZLE.replace('.', "0")).unwrap()
+        );
+    }
+
+    #[test]
+    fn ignores_whitespace() {
+        let rows: Vec<_> = (0..9).map(|row| &PUZZLE[9 * row..9 * (row + 1)]).collect();
+        assert_eq!(
+            parse_puzzle_string(PUZZLE),
+            parse_puzzle_string(&format!("  {}\n", rows.join("\n"))),
+        );
+    }
+
+    #[test]
+    fn hexadoku_uses_hex_symbols() {
+        with_board_size(BoardSize::HEXADOKU, || {
+            let puzzle = format!("0F.a{}", ".".repeat(252));
+            let givens = parse_puzzle_string(&puzzle).unwrap();
+            assert_eq!(3, givens.len());
+            assert_eq!(1, givens[&"r1c1".parse().unwrap()].as_u8());
+            assert_eq!(16, givens[&"r1c2".parse().unwrap()].as_u8());
+            assert_eq!(11, givens[&"r1c4".parse().unwrap()].as_u8());
+            assert_eq!(puzzle.to_uppercase(), format_puzzle_string(&givens));
+            assert!(parse_puzzle_string(&format!("G{}", ".".repeat(255))).is_err());
+        });
+    }
+
+    #[test]
+    fn malformed() {
+        assert_eq!(
+            Err(PuzzleStringError::WrongLength {
+                expected: 81,
+                found: 80
+            }),
+            parse_puzzle_string(&PUZZLE[1..])
+        );
+        assert_eq!(
+            Err(PuzzleStringError::BadCharacter {
+                character: 'x',
+                position: 3,
+            }),
+            parse_puzzle_string(&PUZZLE.replacen('.', "x", 1))
+        );
+        assert_eq!(
+            Err(PuzzleStringError::WrongLength {
+                expected: 81,
+                found: 82
+            }),
+            parse_puzzle_string(&format!("{} 1", PUZZLE))
+        );
+        assert_eq!(
+            Err(PuzzleStringError::BadCharacter {
+                character: '-',
+                position: 81,
+            }),
+            parse_puzzle_string(&format!("{}\n-", &PUZZLE[..80]))
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::path::Path;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use thiserror::Error;
+use tokio::fs::read_to_string;
+
+use crate::board_size::board_size;
+use crate::sudoku::{Box, Cell, Digit};
+
+/// Which box each cell belongs to. Classic sudoku uses 3x3 boxes; jigsaw sudoku replaces them with
+/// nine arbitrary connected regions of nine cells each.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Regions(Vec<Box>);
+
+/// The classic layout of rectangular boxes for the board size in effect, like nine 3x3 boxes.
+impl Default for Regions {
+    fn default() -> Self {
+        Regions(Cell::values().map(classic_box).collect())
+    }
+}
+
+/// The rectangular box containing `cell`, numbered across and then down from 1 at r1c1.
+fn classic_box(cell: Cell) -> Box {
+    let size = board_size();
+    let boxes_across = size.side() / size.box_width();
+    let band = cell.row.index() as u8 / size.box_height();
+    let stack = cell.col.index() as u8 / size.box_width();
+    Box::new(band * boxes_across + stack + 1).unwrap()
+}
+
+impl Regions {
+    pub fn region_of(&self, cell: Cell) -> Box {
+        self.0[cell.index()]
+    }
+}
+
+/// Formats a layout on one line the way `parse_regions` reads it, one region symbol per cell.
+impl Display for Regions {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for box_ in &self.0 {
+            write!(f, "{}", Digit::new(box_.as_u8()).unwrap().symbol())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum RegionsError {
+    #[error("expected a region digit for each of the {expected} cells, but found {found}")]
+    WrongLength { expected: usize, found: usize },
+
+    #[error("expected a region digit from 1 through the board's side length, but found {0:?}")]
+    BadChar(char),
+
+    #[error("region {region} has {cells} cells instead of {expected}")]
+    WrongSize {
+        region: u8,
+        cells: usize,
+        expected: usize,
+    },
+
+    #[error("region {0} is not connected")]
+    Disconnected(u8),
+}
+
+/// Parses a region layout: one digit per cell in row-major order starting at r1c1, like 81 digits
+/// from 1 through 9 on a classic board, each naming the region of its cell in the same symbols as
+/// the board's digits. Whitespace, including newlines between rows, is ignored. Every region must
+/// hold as many cells as a row, joined by shared edges.
+pub fn parse_regions(s: &str) -> Result<Regions, RegionsError> {
+    let size = board_size();
+    let digits: Vec<_> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.len() != size.cell_count() {
+        return Err(RegionsError::WrongLength {
+            expected: size.cell_count(),
+            found: digits.len(),
+        });
+    }
+    let boxes = digits
+        .iter()
+        .map(|&c| {
+            Digit::from_symbol(c)
+                .and_then(|digit| Box::new(digit.as_u8()))
+                .ok_or(RegionsError::BadChar(c))
+        })
+        .collect::<Result<_, _>>()?;
+    let regions = Regions(boxes);
+
+    for region in Box::values() {
+        let cells: HashSet<_> = Cell::values()
+            .filter(|&cell| regions.region_of(cell) == region)
+            .collect();
+        if cells.len() != size.side() as usize {
+            return Err(RegionsError::WrongSize {
+                region: region.as_u8(),
+                cells: cells.len(),
+                expected: size.side() as usize,
+            });
+        }
+
+        // Flood fill from any one cell, stepping only between orthogonal neighbors in the region.
+        let mut reached = HashSet::new();
+        let mut pending = vec![*cells.iter().next().unwrap()];
+        while let Some(cell) = pending.pop() {
+            if !reached.insert(cell) {
+                continue;
+            }
+            pending.extend(cells.iter().copied().filter(|other| {
+                let rows = cell.row.index().abs_diff(other.row.index());
+                let cols = cell.col.index().abs_diff(other.col.index());
+                rows + cols == 1
+            }));
+        }
+        if reached.len() != cells.len() {
+            return Err(RegionsError::Disconnected(region.as_u8()));
+        }
+    }
+    Ok(regions)
+}
+
+pub async fn load_regions(path: &Path) -> anyhow::Result<Regions> {
+    let contents = read_to_string(path)
+        .await
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_regions(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+static REGIONS: OnceLock<Regions> = OnceLock::new();
+
+/// Chooses the region layout in place of the classic boxes. Only the first layout chosen takes
+/// effect, and it must be parsed for the board size in effect.
+pub fn set_regions(regions: Regions) {
+    let _ = REGIONS.set(regions);
+}
+
+/// The region layout chosen in place of the classic boxes, if any.
+pub fn custom_regions() -> Option<&'static Regions> {
+    REGIONS.get()
+}
+
+/// The box containing `cell` under the region layout in effect, which every box lookup consults.
+pub fn region_of(cell: Cell) -> Box {
+    match REGIONS.get() {
+        Some(regions) => regions.region_of(cell),
+        None => classic_box(cell),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_regions, Regions, RegionsError};
+    use crate::sudoku::Cell;
+
+    #[test]
+    fn default_layout_is_classic_boxes() {
+        let regions = Regions::default();
+        for cell in Cell::values() {
+            let box_ = regions.region_of(cell);
+            let block = |index: u32| index / 3;
+            for other in Cell::values() {
+                assert_eq!(
+                    box_ == regions.region_of(other),
+                    block(cell.row.index()) == block(other.row.index())
+                        && block(cell.col.index()) == block(other.col.index()),
+                );
+            }
+        }
+        assert_eq!(
+            Ok(regions),
+            parse_regions(
+                "111222333\n111222333\n111222333\n\
+                 444555666\n444555666\n444555666\n\
+                 777888999\n777888999\n777888999\n",
+            ),
+        );
+    }
+
+    #[test]
+    fn parses_jigsaw_layout() {
+        // Each row is its own region, which is connected but crosses three classic boxes.
+        let layout: String = (1..=9).map(|row| row.to_string().repeat(9)).collect();
+        let regions = parse_regions(&layout).unwrap();
+        assert_eq!(
+            regions.region_of("r4c1".parse().unwrap()),
+            regions.region_of("r4c9".parse().unwrap()),
+        );
+        assert_eq!(layout, regions.to_string());
+    }
+
+    #[test]
+    fn rejects_invalid_layouts() {
+        assert_eq!(
+            Err(RegionsError::WrongLength {
+                expected: 81,
+                found: 3
+            }),
+            parse_regions("123"),
+        );
+        assert_eq!(
+            Err(RegionsError::BadChar('0')),
+            parse_regions(&"0".repeat(81)),
+        );
+        assert!(matches!(
+            parse_regions(&"1".repeat(81)),
+            Err(RegionsError::WrongSize {
+                cells: 81,
+                expected: 9,
+                ..
+            })
+        ));
+
+        // Swapping the corners of boxes 1 and 9 keeps their sizes but splits both.
+        let mut layout: Vec<char> = "111222333111222333111222333\
+                                     444555666444555666444555666\
+                                     777888999777888999777888999"
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        layout.swap(0, 80);
+        let layout: String = layout.into_iter().collect();
+        assert_eq!(Err(RegionsError::Disconnected(1)), parse_regions(&layout));
+    }
+}
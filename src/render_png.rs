@@ -0,0 +1,164 @@
+use anyhow::{anyhow, Result};
+use tiny_skia::{Color, ColorU8, FillRule, LineCap, Paint, PathBuilder, Pixmap, Stroke, Transform};
+use ttf_parser::{Face, OutlineBuilder};
+
+use crate::board_size::board_size;
+use crate::grid::{GivensMask, Grid};
+use crate::render_svg::{grid_lines, origin, CELL_SIZE, MARGIN};
+
+/// DejaVu Sans Mono, bundled so every machine draws the same digits. See `fonts/LICENSE`.
+static FONT: &[u8] = include_bytes!("../fonts/DejaVuSansMono.ttf");
+/// The height of a digit's em square, in SVG user units, matching the SVG's font size.
+const FONT_SIZE: f32 = 28.0;
+/// The same colors as the SVG's.
+const GIVEN_COLOR: ColorU8 = ColorU8::from_rgba(0x00, 0x00, 0x00, 0xff);
+const SOLVED_COLOR: ColorU8 = ColorU8::from_rgba(0x30, 0x60, 0xc0, 0xff);
+
+/// Draws a puzzle as a PNG image, laid out like `render_svg` and scaled so each cell is `cell_px`
+/// pixels across. With `include_solution`, the cells that aren't given are filled in from the
+/// solution in blue.
+pub fn render_png(
+    grid: &Grid,
+    mask: &GivensMask,
+    include_solution: bool,
+    cell_px: u32,
+) -> Result<Vec<u8>> {
+    let scale = cell_px as f32 / CELL_SIZE as f32;
+    let size = board_size().side() as usize * CELL_SIZE + 2 * MARGIN;
+    let pixels = (size as f32 * scale).ceil() as u32;
+    let mut pixmap = Pixmap::new(pixels, pixels)
+        .ok_or_else(|| anyhow!("a {0}x{0} image is too large", pixels))?;
+    pixmap.fill(Color::WHITE);
+    let transform = Transform::from_scale(scale, scale);
+
+    let mut paint = Paint::default();
+    paint.set_color(Color::BLACK);
+    paint.anti_alias = true;
+    for line in grid_lines() {
+        let mut path = PathBuilder::new();
+        path.move_to(line.x1 as f32, line.y1 as f32);
+        path.line_to(line.x2 as f32, line.y2 as f32);
+        let stroke = Stroke {
+            width: line.width as f32,
+            line_cap: LineCap::Square,
+            ..Stroke::default()
+        };
+        pixmap.stroke_path(&path.finish().unwrap(), &paint, &stroke, transform, None);
+    }
+
+    let face = Face::parse(FONT, 0).expect("the bundled font parses");
+    let em = FONT_SIZE / face.units_per_em() as f32;
+    for (cell, digit) in grid {
+        let color = if mask.is_given(cell) {
+            GIVEN_COLOR
+        } else if include_solution {
+            SOLVED_COLOR
+        } else {
+            continue;
+        };
+        let glyph = face
+            .glyph_index(digit.symbol())
+            .expect("the bundled font has every digit");
+        let mut outline = GlyphOutline(PathBuilder::new());
+        let bounds = face
+            .outline_glyph(glyph, &mut outline)
+            .expect("the bundled font's digits have outlines");
+        let path = outline.0.finish().unwrap();
+
+        // Center the glyph's bounding box in the cell. Font units run up from the baseline, so the
+        // outline is flipped to run down like the image.
+        let (x, y) = origin(cell);
+        let center_x = x as f32 + CELL_SIZE as f32 / 2.0;
+        let center_y = y as f32 + CELL_SIZE as f32 / 2.0;
+        let glyph_x = (bounds.x_min as f32 + bounds.x_max as f32) / 2.0;
+        let glyph_y = (bounds.y_min as f32 + bounds.y_max as f32) / 2.0;
+        let glyph_transform = transform
+            .pre_translate(center_x, center_y)
+            .pre_scale(em, -em)
+            .pre_translate(-glyph_x, -glyph_y);
+        paint.set_color_rgba8(color.red(), color.green(), color.blue(), color.alpha());
+        pixmap.fill_path(&path, &paint, FillRule::Winding, glyph_transform, None);
+    }
+
+    pixmap
+        .encode_png()
+        .map_err(|e| anyhow!("Failed to encode the PNG: {}", e))
+}
+
+/// Collects a glyph's outline, in font units, as a `tiny_skia` path.
+struct GlyphOutline(PathBuilder);
+
+impl OutlineBuilder for GlyphOutline {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.0.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiny_skia::Pixmap;
+
+    use super::render_png;
+    use crate::grid::{GivensMask, Grid};
+    use crate::sudoku::Digit;
+
+    /// Whether the pixel at `(x, y)` is pure white.
+    fn is_white(pixmap: &Pixmap, x: u32, y: u32) -> bool {
+        let pixel = pixmap.pixel(x, y).unwrap();
+        (pixel.red(), pixel.green(), pixel.blue()) == (0xff, 0xff, 0xff)
+    }
+
+    /// How many pixels are more blue than red, as only the solution's digits are.
+    fn blue_pixels(pixmap: &Pixmap) -> usize {
+        pixmap
+            .pixels()
+            .iter()
+            .filter(|pixel| pixel.blue() > pixel.red().saturating_add(0x40))
+            .count()
+    }
+
+    #[test]
+    fn draws_the_board_and_digits_at_the_requested_scale() {
+        let grid = Grid::from_fn(|cell| {
+            Digit::new(
+                ((cell.row.index() * 3 + cell.row.index() / 3 + cell.col.index()) % 9) as u8 + 1,
+            )
+            .unwrap()
+        });
+        let mask = GivensMask::from_fn(|cell| cell.row.index() == cell.col.index());
+
+        let pixmap = Pixmap::decode_png(&render_png(&grid, &mask, false, 40).unwrap()).unwrap();
+        // Nine 40px cells plus a 2px margin on each side.
+        assert_eq!((364, 364), (pixmap.width(), pixmap.height()));
+        // The thick outer border, and the thin line between r1c1 and r1c2.
+        assert!(!is_white(&pixmap, 2, 100));
+        assert!(!is_white(&pixmap, 42, 20));
+        // r1c2 isn't given, so its middle is empty.
+        assert!(is_white(&pixmap, 62, 22));
+        // r1c1 is, so its middle has ink.
+        assert!((12..32).any(|y| (12..32).any(|x| !is_white(&pixmap, x, y))));
+        assert_eq!(0, blue_pixels(&pixmap));
+
+        let pixmap = Pixmap::decode_png(&render_png(&grid, &mask, true, 40).unwrap()).unwrap();
+        assert!(blue_pixels(&pixmap) > 0);
+
+        let pixmap = Pixmap::decode_png(&render_png(&grid, &mask, false, 80).unwrap()).unwrap();
+        assert_eq!(728, pixmap.width());
+    }
+}
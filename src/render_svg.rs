@@ -0,0 +1,152 @@
+use std::fmt::Write;
+
+use crate::board_size::board_size;
+use crate::grid::{GivensMask, Grid};
+use crate::sudoku::{Cell, Col, Row};
+
+/// The width and height of one cell, in SVG user units.
+pub(crate) const CELL_SIZE: usize = 40;
+/// Room around the board so the outer border isn't clipped.
+pub(crate) const MARGIN: usize = 2;
+const GIVEN_COLOR: &str = "#000000";
+const SOLVED_COLOR: &str = "#3060c0";
+
+/// Draws a puzzle as a standalone SVG document: thin lines between cells, thick lines around the
+/// board and between boxes, and the givens in black. With `include_solution`, the other cells are
+/// filled in from the solution in blue.
+pub fn render_svg(grid: &Grid, mask: &GivensMask, include_solution: bool) -> String {
+    let size = board_size().side() as usize * CELL_SIZE + 2 * MARGIN;
+    let mut output = String::new();
+    writeln!(
+        &mut output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{0}" viewBox="0 0 {0} {0}">"#,
+        size,
+    )
+    .unwrap();
+    writeln!(
+        &mut output,
+        r#"<rect width="{0}" height="{0}" fill="white"/>"#,
+        size
+    )
+    .unwrap();
+
+    for line in grid_lines() {
+        writeln!(
+            &mut output,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black" stroke-width="{}" stroke-linecap="square"/>"#,
+            line.x1,
+            line.y1,
+            line.x2,
+            line.y2,
+            line.width,
+        )
+        .unwrap();
+    }
+
+    for (cell, digit) in grid {
+        let color = if mask.is_given(cell) {
+            GIVEN_COLOR
+        } else if include_solution {
+            SOLVED_COLOR
+        } else {
+            continue;
+        };
+        let (x, y) = origin(cell);
+        writeln!(
+            &mut output,
+            r#"<text x="{}" y="{}" font-family="sans-serif" font-size="28" text-anchor="middle" dominant-baseline="central" fill="{}">{}</text>"#,
+            x + CELL_SIZE / 2,
+            y + CELL_SIZE / 2,
+            color,
+            digit.as_u8(),
+        )
+        .unwrap();
+    }
+    writeln!(&mut output, "</svg>").unwrap();
+    output
+}
+
+/// A line on the board, in SVG user units.
+pub(crate) struct GridLine {
+    pub x1: usize,
+    pub y1: usize,
+    pub x2: usize,
+    pub y2: usize,
+    pub width: usize,
+}
+
+/// The lines of the board. Each cell draws its right and bottom edges, thick where they divide
+/// boxes or end the board, and the board's top and left edges are drawn thick on their own.
+pub(crate) fn grid_lines() -> Vec<GridLine> {
+    let mut lines = Vec::new();
+    for cell in Cell::values() {
+        let (x, y) = origin(cell);
+        let right = Col::new(cell.col.as_u8() + 1).map(|col| Cell { col, ..cell });
+        let below = Row::new(cell.row.as_u8() + 1).map(|row| Cell { row, ..cell });
+        for (neighbor, (x1, y1, x2, y2)) in [
+            (right, (x + CELL_SIZE, y, x + CELL_SIZE, y + CELL_SIZE)),
+            (below, (x, y + CELL_SIZE, x + CELL_SIZE, y + CELL_SIZE)),
+        ] {
+            let width = match neighbor {
+                Some(neighbor) if neighbor.box_() == cell.box_() => 1,
+                _ => 3,
+            };
+            lines.push(GridLine {
+                x1,
+                y1,
+                x2,
+                y2,
+                width,
+            });
+        }
+    }
+    let far = MARGIN + board_size().side() as usize * CELL_SIZE;
+    for (x2, y2) in [(far, MARGIN), (MARGIN, far)] {
+        lines.push(GridLine {
+            x1: MARGIN,
+            y1: MARGIN,
+            x2,
+            y2,
+            width: 3,
+        });
+    }
+    lines
+}
+
+/// The top left corner of a cell.
+pub(crate) fn origin(cell: Cell) -> (usize, usize) {
+    (
+        MARGIN + cell.col.index() as usize * CELL_SIZE,
+        MARGIN + cell.row.index() as usize * CELL_SIZE,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render_svg;
+    use crate::grid::{GivensMask, Grid};
+    use crate::sudoku::Digit;
+
+    #[test]
+    fn draws_one_text_element_per_digit_shown() {
+        let grid = Grid::from_fn(|cell| {
+            Digit::new(
+                ((cell.row.index() * 3 + cell.row.index() / 3 + cell.col.index()) % 9) as u8 + 1,
+            )
+            .unwrap()
+        });
+        let mask = GivensMask::from_fn(|cell| cell.row.index() == cell.col.index());
+
+        let svg = render_svg(&grid, &mask, false);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert_eq!(9, svg.matches("<text").count());
+        assert!(!svg.contains(super::SOLVED_COLOR));
+
+        let svg = render_svg(&grid, &mask, true);
+        assert_eq!(81, svg.matches("<text").count());
+        assert_eq!(72, svg.matches(super::SOLVED_COLOR).count());
+
+        // Of the 162 right and bottom cell edges, 18 run along the board's edge and 36 divide boxes.
+        assert_eq!(162 - 18 - 36, svg.matches(r#"stroke-width="1""#).count());
+    }
+}
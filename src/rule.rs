@@ -0,0 +1,185 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+/// An inference rule the solution may require, one per field of `Inferences`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rule {
+    NakedSingle,
+    HiddenSingle,
+    Pointing,
+    Claiming,
+    NakedPair,
+    NakedTriple,
+    NakedQuad,
+    XWing,
+    #[serde(rename = "xy_wing")]
+    XYWing,
+    WWing,
+    RemotePairs,
+    Jellyfish,
+}
+
+impl Rule {
+    /// Every rule, in a stable order.
+    pub const ALL: [Rule; 12] = [
+        Rule::NakedSingle,
+        Rule::HiddenSingle,
+        Rule::Pointing,
+        Rule::Claiming,
+        Rule::NakedPair,
+        Rule::NakedTriple,
+        Rule::NakedQuad,
+        Rule::XWing,
+        Rule::XYWing,
+        Rule::WWing,
+        Rule::RemotePairs,
+        Rule::Jellyfish,
+    ];
+
+    /// The rule's name, which is also the name of its command-line flag.
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::NakedSingle => "naked_single",
+            Rule::HiddenSingle => "hidden_single",
+            Rule::Pointing => "pointing",
+            Rule::Claiming => "claiming",
+            Rule::NakedPair => "naked_pair",
+            Rule::NakedTriple => "naked_triple",
+            Rule::NakedQuad => "naked_quad",
+            Rule::XWing => "x_wing",
+            Rule::XYWing => "xy_wing",
+            Rule::WWing => "w_wing",
+            Rule::RemotePairs => "remote_pairs",
+            Rule::Jellyfish => "jellyfish",
+        }
+    }
+
+    /// What the rule's command-line flag allows the solution to require.
+    pub fn description(self) -> &'static str {
+        match self {
+            Rule::NakedSingle => "naked single inference",
+            Rule::HiddenSingle => "hidden single inference",
+            Rule::Pointing => {
+                "pointing inference, where a box confines a digit to one row or column"
+            }
+            Rule::Claiming => {
+                "claiming (box-line reduction) inference, where a row or column confines a digit to \
+                 one box"
+            }
+            Rule::NakedPair => "naked pair inference",
+            Rule::NakedTriple => "naked triple inference",
+            Rule::NakedQuad => "naked quad inference",
+            Rule::XWing => "X-wing inference",
+            Rule::XYWing => "XY-wing inference",
+            Rule::WWing => {
+                "W-wing inference, which adds about 3.6 million clauses per inference level on a \
+                 9x9 board"
+            }
+            Rule::RemotePairs => "remote pairs inference",
+            Rule::Jellyfish => "jellyfish inference",
+        }
+    }
+}
+
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("unknown inference rule {0:?}")]
+pub struct UnknownRuleError(String);
+
+impl FromStr for Rule {
+    type Err = UnknownRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL
+            .iter()
+            .copied()
+            .find(|rule| rule.name() == s)
+            .ok_or_else(|| UnknownRuleError(s.to_string()))
+    }
+}
+
+/// Reads a rule by name, as in a config file's `[rules]` table.
+impl<'de> Deserialize<'de> for Rule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rule;
+    use crate::emit_problem::Inferences;
+
+    #[test]
+    fn names_round_trip() {
+        for rule in Rule::ALL.iter().copied() {
+            assert_eq!(Ok(rule), rule.to_string().parse());
+            assert_eq!(
+                format!("\"{}\"", rule),
+                serde_json::to_string(&rule).unwrap()
+            );
+        }
+        assert!("y_wing".parse::<Rule>().is_err());
+    }
+
+    #[test]
+    fn every_inferences_field_has_a_rule() {
+        let mut inferences = Inferences {
+            naked_single: false,
+            hidden_single: false,
+            pointing: false,
+            claiming: false,
+            naked_pair: false,
+            naked_triple: false,
+            naked_quad: false,
+            x_wing: false,
+            xy_wing: false,
+            w_wing: false,
+            remote_pairs: false,
+            jellyfish: false,
+        };
+        for rule in Rule::ALL.iter().copied() {
+            inferences.set(rule, true);
+        }
+        // Listing the fields here makes this fail to compile when a new one is added.
+        let Inferences {
+            naked_single,
+            hidden_single,
+            pointing,
+            claiming,
+            naked_pair,
+            naked_triple,
+            naked_quad,
+            x_wing,
+            xy_wing,
+            w_wing,
+            remote_pairs,
+            jellyfish,
+        } = inferences;
+        assert!(
+            naked_single
+                && hidden_single
+                && pointing
+                && claiming
+                && naked_pair
+                && naked_triple
+                && naked_quad
+                && x_wing
+                && xy_wing
+                && w_wing
+                && remote_pairs
+                && jellyfish
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::board_size::board_size;
+use crate::sudoku::{Col, House, OutOfRangeError, Row};
+
+/// A sandwich clue: in its row or column, the digits strictly between the 1 and the highest digit
+/// (9 on a classic board) must add up to `sum`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Sandwich {
+    /// The row or column the clue sits beside. Never a box.
+    pub house: House,
+    pub sum: u32,
+}
+
+/// Formats a clue as `--sandwiches` takes it, like `r1=10` or `c4=0`.
+impl Display for Sandwich {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.house {
+            House::Row(row) => write!(f, "r{}={}", row.as_u8(), self.sum),
+            House::Col(col) => write!(f, "c{}={}", col.as_u8(), self.sum),
+            House::Box(_) => unreachable!("sandwich clues are only for rows and columns"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum SandwichError {
+    #[error("expected a sandwich clue like r1=10 or c4=0, but found {0:?}")]
+    Malformed(String),
+
+    #[error("in sandwich clue {clue:?}: {source}")]
+    BadLine {
+        clue: String,
+        source: OutOfRangeError,
+    },
+
+    #[error("sandwich clue {clue:?} can't be met; the digits between the 1 and {highest} sum to 0 through {max}")]
+    ImpossibleSum { clue: String, highest: u8, max: u32 },
+
+    #[error("{0} has more than one sandwich clue")]
+    RepeatedLine(String),
+}
+
+/// Parses a semicolon-separated list of sandwich clues, each a row like `r1` or a column like `c4`
+/// with the sum of the digits between its 1 and its highest digit, like `r1=10;c4=0`. A line may
+/// only have one clue, and its sum must be reachable.
+pub fn parse_sandwiches(s: &str) -> Result<Vec<Sandwich>, SandwichError> {
+    let side = board_size().side();
+    // Everything but the 1 and the highest digit can sit between them.
+    let max = side as u32 * (side as u32 + 1) / 2 - 1 - side as u32;
+
+    let mut seen = HashSet::new();
+    let mut sandwiches = Vec::new();
+    for text in s.split(';').map(str::trim).filter(|text| !text.is_empty()) {
+        let malformed = || SandwichError::Malformed(text.to_string());
+        let (line, sum) = text.split_once('=').ok_or_else(malformed)?;
+        let (line, sum) = (line.trim(), sum.trim());
+        let bad_line = |source| SandwichError::BadLine {
+            clue: text.to_string(),
+            source,
+        };
+        let house = if let Some(row) = line.strip_prefix('r') {
+            House::Row(row.parse::<Row>().map_err(bad_line)?)
+        } else if let Some(col) = line.strip_prefix('c') {
+            House::Col(col.parse::<Col>().map_err(bad_line)?)
+        } else {
+            return Err(malformed());
+        };
+        let sum = sum.parse::<u32>().map_err(|_| malformed())?;
+        if sum > max {
+            return Err(SandwichError::ImpossibleSum {
+                clue: text.to_string(),
+                highest: side,
+                max,
+            });
+        }
+        if !seen.insert(house) {
+            return Err(SandwichError::RepeatedLine(line.to_string()));
+        }
+        sandwiches.push(Sandwich { house, sum });
+    }
+    Ok(sandwiches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_sandwiches, Sandwich, SandwichError};
+    use crate::sudoku::{Col, House, Row};
+
+    #[test]
+    fn parses_row_and_column_clues() {
+        let sandwiches = parse_sandwiches("r1=10; c4 = 0").unwrap();
+        assert_eq!(
+            vec![
+                Sandwich {
+                    house: House::Row(Row::K1),
+                    sum: 10,
+                },
+                Sandwich {
+                    house: House::Col(Col::K4),
+                    sum: 0,
+                },
+            ],
+            sandwiches,
+        );
+        assert_eq!("r1=10", sandwiches[0].to_string());
+        assert_eq!("c4=0", sandwiches[1].to_string());
+    }
+
+    #[test]
+    fn rejects_invalid_clues() {
+        assert!(matches!(
+            parse_sandwiches("r1"),
+            Err(SandwichError::Malformed(_))
+        ));
+        assert!(matches!(
+            parse_sandwiches("b1=3"),
+            Err(SandwichError::Malformed(_))
+        ));
+        assert!(matches!(
+            parse_sandwiches("r10=3"),
+            Err(SandwichError::BadLine { .. })
+        ));
+        assert!(matches!(
+            parse_sandwiches("c2=36"),
+            Err(SandwichError::ImpossibleSum { max: 35, .. })
+        ));
+        assert!(matches!(
+            parse_sandwiches("r2=3;r2=4"),
+            Err(SandwichError::RepeatedLine(_))
+        ));
+    }
+}
@@ -1,23 +1,33 @@
 use std::collections::HashMap;
-use std::env::{split_paths, var_os};
+use std::env::{split_paths, var, var_os};
+use std::ffi::OsStr;
 use std::num::NonZeroI32;
 use std::path::PathBuf;
 use std::process::Stdio;
+use std::sync::OnceLock;
+use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use tokio::fs::metadata;
-use tokio::io::{stdout, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
-use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::io::{
+    stdout, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+};
+use tokio::process::{Child, ChildStdin, Command};
 use tokio::spawn;
 use tokio::task::JoinHandle;
+use tokio::time::timeout;
 
-use crate::formula_builder::{Literal, Variable};
+use crate::formula_builder::{FormulaBuilder, Literal, TaggedVariableFormulaBuilder, Variable};
 use crate::iter_singleton::IteratorExt;
 
 async fn find_file_on_path(name: &str) -> Result<PathBuf> {
     let path = var_os("PATH").ok_or_else(|| anyhow!("PATH not defined in the environment"))?;
+    find_file_in(name, &path).await
+}
 
-    for mut path in split_paths(&path) {
+/// Like `find_file_on_path`, but searches `path`, formatted like the `PATH` variable.
+async fn find_file_in(name: &str, path: &OsStr) -> Result<PathBuf> {
+    for mut path in split_paths(path) {
         path.push(name);
         if let Ok(metadata) = metadata(&path).await {
             if metadata.is_file() {
@@ -29,23 +39,30 @@ async fn find_file_on_path(name: &str) -> Result<PathBuf> {
     Err(anyhow!("{} was not found on the PATH", name))
 }
 
-async fn parse_output(child_stdout: ChildStdout) -> Result<Solution> {
+/// Parses the solver's output, collecting any statistics it reports on comment lines. The solver is
+/// an external program, so malformed output of any kind must come back as an error rather than a
+/// panic.
+async fn parse_output(
+    output: impl AsyncRead + Unpin,
+    echo: bool,
+) -> Result<(Solution, SolverStats)> {
     // TODO: Wait a few seconds before echoing messages to stdout. That will eliminiate spam for
     // quick solves while providing a stream of status updates during long solves.
     let mut stdout = stdout();
     let mut solution = None;
-    let mut lines = BufReader::new(child_stdout).lines();
+    let mut lines = BufReader::new(output).lines();
     let mut variables_done = false;
+    let mut stats = SolverStats::default();
     while let Some(line) = lines.next_line().await? {
         let mut suppress = false;
         if let Some(suffix) = line.strip_prefix('s') {
-            match &*suffix
+            let status = suffix
                 .split_ascii_whitespace()
-                .singleton()
-                .unwrap()
-                .to_lowercase()
-            {
-                "satisfiable" => {
+                .collect::<Vec<_>>()
+                .join(" ");
+            match &*status.to_lowercase() {
+                // A MaxSAT solver reports an optimal solution, whose assignments follow as usual.
+                "satisfiable" | "optimum found" => {
                     if solution.is_some() {
                         return Err(anyhow!("DIMACS parse error: multiple solution lines"));
                     }
@@ -59,6 +76,19 @@ async fn parse_output(child_stdout: ChildStdout) -> Result<Solution> {
                     }
                     solution = Some(Solution::Unsatisfiable);
                 }
+                // Some solvers say INDETERMINATE instead of UNKNOWN when they stop undecided.
+                "unknown" | "indeterminate" => {
+                    if solution.is_some() {
+                        return Err(anyhow!("DIMACS parse error: multiple solution lines"));
+                    }
+                    solution = Some(Solution::Unknown);
+                }
+                "" => {
+                    return Err(anyhow!(
+                        "DIMACS parse error: malformed solution line: {:?}",
+                        line
+                    ));
+                }
                 _ => {
                     return Err(anyhow!(
                         "DIMACS parse error: unsupported solution line: {:?}",
@@ -66,6 +96,14 @@ async fn parse_output(child_stdout: ChildStdout) -> Result<Solution> {
                     ));
                 }
             }
+        } else if let Some(suffix) = line.strip_prefix('o') {
+            // A MaxSAT solver reports the cost of each better solution it finds along the way.
+            let cost = suffix
+                .split_ascii_whitespace()
+                .singleton()
+                .map_err(|_| anyhow!("DIMACS parse error: malformed cost line: {:?}", line))?;
+            cost.parse::<u64>()
+                .with_context(|| anyhow!("DIMACS parse error: bad cost: {:?}", cost))?;
         } else if let Some(suffix) = line.strip_prefix('v') {
             suppress = true;
             if let Some(Solution::Satisfiable { assignments }) = solution.as_mut() {
@@ -75,7 +113,8 @@ async fn parse_output(child_stdout: ChildStdout) -> Result<Solution> {
                             "DIMACS parse error: variable assignments after the zero terminator",
                         ));
                     }
-                    let literal = i32::from_str_radix(part, 10)
+                    let literal = part
+                        .parse::<i32>()
                         .with_context(|| anyhow!("DIMACS parse error: bad literal: {:?}", part))?;
                     if literal == 0 {
                         variables_done = true;
@@ -95,12 +134,14 @@ async fn parse_output(child_stdout: ChildStdout) -> Result<Solution> {
                     "DIMACS parse error: variable assignments before solution line",
                 ));
             }
+        } else if let Some(suffix) = line.strip_prefix('c') {
+            stats.parse_comment(suffix);
         }
         // Ignore all other line types.
 
-        if !suppress {
+        if echo && !suppress {
             stdout.write_all(line.as_bytes()).await?;
-            stdout.write(b"\n").await?;
+            stdout.write_all(b"\n").await?;
         }
     }
 
@@ -112,29 +153,146 @@ async fn parse_output(child_stdout: ChildStdout) -> Result<Solution> {
         }
     }
 
-    Ok(solution.unwrap())
+    let solution = solution.ok_or_else(|| anyhow!("DIMACS parse error: no solution line"))?;
+    Ok((solution, stats))
+}
+
+/// Statistics the solver reported on its comment lines, as kissat prints them when it finishes.
+/// Each is `None` if the solver didn't report it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SolverStats {
+    pub conflicts: Option<u64>,
+    pub decisions: Option<u64>,
+    pub propagations: Option<u64>,
+    /// Seconds of CPU time the solver used.
+    pub process_time: Option<f64>,
+}
+
+impl SolverStats {
+    /// Records the statistic on a comment line like `c conflicts:  1234  56.78 per second`, with
+    /// the leading `c` already removed. Any other comment is ignored.
+    fn parse_comment(&mut self, comment: &str) {
+        let (name, rest) = match comment.trim_start().split_once(':') {
+            Some(parts) => parts,
+            None => return,
+        };
+        let value = match rest.split_ascii_whitespace().next() {
+            Some(value) => value,
+            None => return,
+        };
+        match name {
+            "conflicts" => self.conflicts = value.parse().ok().or(self.conflicts),
+            "decisions" => self.decisions = value.parse().ok().or(self.decisions),
+            "propagations" => self.propagations = value.parse().ok().or(self.propagations),
+            "process-time" => self.process_time = value.parse().ok().or(self.process_time),
+            _ => (),
+        }
+    }
+
+    /// A few lines summarizing whichever statistics were reported.
+    pub fn format_summary(&self) -> String {
+        let mut output = String::from("Solver statistics:\n");
+        for (name, value) in [
+            ("conflicts", self.conflicts),
+            ("decisions", self.decisions),
+            ("propagations", self.propagations),
+        ] {
+            if let Some(value) = value {
+                output += &format!("  {:<14}{}\n", name, value);
+            }
+        }
+        if let Some(seconds) = self.process_time {
+            output += &format!("  {:<14}{:.2} s\n", "process time", seconds);
+        }
+        output
+    }
 }
 
+/// The executable run to solve formulas when neither `--solver` nor `SOLVER_ENV_VAR` names one.
+pub const DEFAULT_SOLVER_NAME: &str = "kissat";
+
+/// The environment variable naming the solver executable when `--solver` isn't given.
+pub const SOLVER_ENV_VAR: &str = "SUDOKU_SAT_SOLVER";
+
+static SOLVER_NAME: OnceLock<String> = OnceLock::new();
+
+/// Chooses the executable run to solve formulas, looked up on `PATH`, in place of the environment
+/// variable or the default. Any DIMACS solver reporting kissat's exit statuses works, as long as
+/// it accepts the options in use, like `-q` and `--seed`. Only takes effect before the first solve.
+pub fn set_solver_name(name: &str) {
+    let _ = SOLVER_NAME.set(name.to_string());
+}
+
+/// The executable run to solve formulas, looked up on `PATH`.
+pub fn solver_name() -> &'static str {
+    SOLVER_NAME.get_or_init(|| match var(SOLVER_ENV_VAR) {
+        Ok(name) if !name.is_empty() => name,
+        _ => DEFAULT_SOLVER_NAME.to_string(),
+    })
+}
+
+/// The executable run to solve WCNF formulas with `--minimize_givens`, looked up on `PATH`.
+pub const MAXSAT_SOLVER_NAME: &str = "open-wbo";
+
 pub struct SatSolver {
     child: Child,
     input: BufWriter<ChildStdin>,
-    solution: JoinHandle<Result<Solution>>,
+    solution: JoinHandle<Result<(Solution, SolverStats)>>,
 }
 
 impl SatSolver {
+    /// Starts the solver, echoing its status messages to stdout as they arrive.
     pub async fn start() -> Result<Self> {
-        let executable_path = find_file_on_path("kissat").await?;
+        Self::start_with_options(true, None, None).await
+    }
+
+    /// Like `start`, but seeds the solver's random choices so that a retry can search differently.
+    pub async fn start_seeded(seed: u64) -> Result<Self> {
+        Self::start_with_options(true, Some(seed), None).await
+    }
 
-        let mut child = Command::new(executable_path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            // .arg("-q")
+    /// Like `start_seeded`, but passes `time_limit` to the solver as its own time limit. Instead of
+    /// being killed, the solver stops by itself, prints its statistics, and reports
+    /// `Solution::Unknown`.
+    pub async fn start_time_limited(seed: Option<u64>, time_limit: Duration) -> Result<Self> {
+        Self::start_with_options(true, seed, Some(time_limit)).await
+    }
+
+    /// Starts the solver without echoing anything it prints, for callers that own stdout.
+    pub async fn start_quiet() -> Result<Self> {
+        Self::start_with_options(false, None, None).await
+    }
+
+    async fn start_with_options(
+        echo: bool,
+        seed: Option<u64>,
+        time_limit: Option<Duration>,
+    ) -> Result<Self> {
+        let command = solver_command(
+            find_file_on_path(solver_name()).await?,
+            echo,
+            seed,
+            time_limit,
+        );
+        Self::spawn(command, solver_name(), echo)
+    }
+
+    /// Starts the MaxSAT solver, which reads WCNF and reports an optimal solution, echoing its
+    /// status messages to stdout as they arrive.
+    pub async fn start_maxsat() -> Result<Self> {
+        let command = Command::new(find_file_on_path(MAXSAT_SOLVER_NAME).await?);
+        Self::spawn(command, MAXSAT_SOLVER_NAME, true)
+    }
+
+    fn spawn(mut command: Command, name: &str, echo: bool) -> Result<Self> {
+        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+        let mut child = command
             .spawn()
-            .context("Failed to execute kissat")?;
+            .with_context(|| format!("Failed to execute {}", name))?;
 
         let input = BufWriter::new(child.stdin.take().unwrap());
         let output = child.stdout.take().unwrap();
-        let solution = spawn(async move { parse_output(output).await });
+        let solution = spawn(async move { parse_output(output, echo).await });
 
         Ok(Self {
             child,
@@ -148,6 +306,23 @@ impl SatSolver {
     }
 
     pub async fn solve(self) -> Result<Solution> {
+        Ok(self.solve_within(None).await?.unwrap())
+    }
+
+    /// Like `solve`, but gives up and returns `None` if the solver hasn't finished within `limit`.
+    /// The solver process is killed and reaped before this returns, so it never outlives the call.
+    pub async fn solve_within(self, limit: Option<Duration>) -> Result<Option<Solution>> {
+        Ok(self
+            .solve_within_with_stats(limit)
+            .await?
+            .map(|(solution, _)| solution))
+    }
+
+    /// Like `solve_within`, but also returns the statistics the solver reported.
+    pub async fn solve_within_with_stats(
+        self,
+        limit: Option<Duration>,
+    ) -> Result<Option<(Solution, SolverStats)>> {
         let Self {
             mut child,
             mut input,
@@ -156,20 +331,145 @@ impl SatSolver {
         input.shutdown().await?;
         drop(input);
 
-        let exit_status = child.wait().await?;
-        let solution = solution.await??;
+        let exit_status = match limit {
+            Some(limit) => match timeout(limit, child.wait()).await {
+                Ok(exit_status) => exit_status?,
+                Err(_) => {
+                    // Killing the solver closes its output, so the parser finishes too. Its error
+                    // about the missing solution line is expected and dropped.
+                    child.kill().await?;
+                    let _ = solution.await?;
+                    return Ok(None);
+                }
+            },
+            None => child.wait().await?,
+        };
+        let (solution, stats) = solution.await??;
         match (exit_status.code(), &solution) {
-            (Some(10), Solution::Satisfiable { .. }) | (Some(20), Solution::Unsatisfiable) => (),
+            (Some(10), Solution::Satisfiable { .. })
+            | (Some(20), Solution::Unsatisfiable)
+            | (Some(0), Solution::Unknown)
+            | (Some(30), Solution::Satisfiable { .. }) => (),
             _ => {
                 return Err(anyhow!(
-                    "unexpected exit status from kissat ({}) with parsed solution {:?}",
+                    "unexpected exit status from the solver ({}) with parsed solution {:?}",
                     exit_status,
                     solution,
                 ));
             }
         }
 
-        Ok(solution)
+        Ok(Some((solution, stats)))
+    }
+}
+
+/// The command line for a kissat-compatible solver at `path`. The seed is passed as `--seed=`, so
+/// runs with the same seed and formula only reproduce each other if the solver honors it.
+fn solver_command(
+    path: PathBuf,
+    echo: bool,
+    seed: Option<u64>,
+    time_limit: Option<Duration>,
+) -> Command {
+    let mut command = Command::new(path);
+    if !echo {
+        command.arg("-q");
+    }
+    if let Some(seed) = seed {
+        command.arg(format!("--seed={}", seed));
+    }
+    if let Some(time_limit) = time_limit {
+        // kissat takes whole seconds, and treats zero as no limit.
+        let seconds = time_limit.as_millis().div_ceil(1000).max(1);
+        command.arg(format!("--time={}", seconds));
+    }
+    command
+}
+
+/// A clause ruling out the values `assignments` gives `variables`, so that solving again finds a
+/// solution differing in at least one of them.
+pub fn blocking_clause(
+    assignments: &HashMap<Variable, bool>,
+    variables: &[Variable],
+) -> Vec<Literal> {
+    variables
+        .iter()
+        .filter_map(|&variable| {
+            assignments
+                .get(&variable)
+                .map(|&value| variable.as_literal(!value))
+        })
+        .collect()
+}
+
+/// Solves `formula` repeatedly, blocking each solution's values for `variables` before solving
+/// again, until `limit` solutions are found or no more remain. Every returned solution is
+/// satisfiable, and no two agree on all of `variables`. The blocking clauses are left in `formula`.
+pub async fn enumerate_solutions<T>(
+    formula: &mut TaggedVariableFormulaBuilder<T>,
+    variables: &[Variable],
+    limit: usize,
+) -> Result<Vec<Solution>> {
+    let mut solutions = Vec::new();
+    while solutions.len() < limit {
+        let mut solver = SatSolver::start_quiet().await?;
+        formula.write_dimacs(solver.input()).await?;
+        match solver.solve().await? {
+            Solution::Satisfiable { assignments } => {
+                let clause = blocking_clause(&assignments, variables);
+                solutions.push(Solution::Satisfiable { assignments });
+                if clause.is_empty() {
+                    // Nothing distinguishes one solution from another.
+                    break;
+                }
+                formula.add_clause(clause);
+            }
+            Solution::Unsatisfiable => break,
+            Solution::Unknown => return Err(anyhow!("the solver stopped without deciding")),
+        }
+    }
+    Ok(solutions)
+}
+
+/// A solver that loads a formula once and then solves it any number of times, each under its own
+/// assumptions, as IPASIR solvers do. This spares checks that differ only in a few literals from
+/// starting a process and writing the whole formula every time, which is all `SatSolver` can do.
+pub trait IncrementalSolver {
+    /// Adds a clause that holds for every later solve.
+    fn add_clause(&mut self, clause: Vec<Literal>);
+
+    /// Solves the clauses added so far with every literal in `assumptions` true. The assumptions
+    /// only hold for this solve.
+    fn solve_under_assumptions(&mut self, assumptions: &[Literal]) -> Result<Solution>;
+}
+
+/// An in-process `IncrementalSolver` that keeps the formula and searches it from scratch with the
+/// in-tree DPLL solver on every call. It only suits small formulas, or ones that unit propagation
+/// decides, but it stands in for a linked IPASIR solver behind the same interface.
+#[cfg(feature = "incremental")]
+pub struct StubIncrementalSolver<T> {
+    formula: TaggedVariableFormulaBuilder<T>,
+}
+
+#[cfg(feature = "incremental")]
+impl<T> StubIncrementalSolver<T> {
+    /// Loads `formula`, whose clauses hold for every solve.
+    pub fn new(formula: TaggedVariableFormulaBuilder<T>) -> Self {
+        Self { formula }
+    }
+}
+
+#[cfg(feature = "incremental")]
+impl<T> IncrementalSolver for StubIncrementalSolver<T> {
+    fn add_clause(&mut self, clause: Vec<Literal>) {
+        self.formula.add_clause(clause);
+    }
+
+    fn solve_under_assumptions(&mut self, assumptions: &[Literal]) -> Result<Solution> {
+        Ok(match self.formula.solve(assumptions) {
+            Some(assignments) => Solution::Satisfiable { assignments },
+            None => Solution::Unsatisfiable,
+        })
     }
 }
 
@@ -179,4 +479,340 @@ pub enum Solution {
         assignments: HashMap<Variable, bool>,
     },
     Unsatisfiable,
+    /// The solver stopped without deciding, as when it reaches its own time limit.
+    Unknown,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::{join_paths, temp_dir};
+    use std::process::id;
+
+    use tokio::fs::{create_dir_all, remove_dir_all, write};
+
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    use tokio::process::Command;
+
+    use super::{
+        blocking_clause, find_file_in, parse_output, solver_command, SatSolver, Solution,
+        SolverStats,
+    };
+    use crate::formula_builder::{FormulaBuilder, TaggedVariableFormulaBuilder};
+
+    /// A small deterministic generator, so failures reproduce without a seed in the test output.
+    struct XorShift(u64);
+
+    impl XorShift {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.next() as usize % choices.len()]
+        }
+    }
+
+    async fn parse(output: &[u8]) -> anyhow::Result<Solution> {
+        Ok(parse_output(output, false).await?.0)
+    }
+
+    /// The end of kissat's output for a small satisfiable formula, from its statistics section on.
+    const KISSAT_OUTPUT: &str = "\
+c ---- [ statistics ] --------------------------------------------------------
+c
+c chronological:                            0          0 %  conflicts
+c conflicts:                             1234     6170.00 per second
+c decisions:                             5678        4.60 per conflict
+c propagations:                        901234  4506170    per second
+c reductions:                               1       1234   conflicts per reduction
+c
+c ---- [ resources ] ---------------------------------------------------------
+c
+c maximum-resident-set-size:          8388608 bytes          8 MB
+c process-time:                                    0.20 seconds
+c
+c ---- [ shutting down ] -----------------------------------------------------
+c
+c exit 10
+s SATISFIABLE
+v 1 -2 0
+";
+
+    #[tokio::test]
+    async fn parses_kissat_statistics() {
+        let (solution, stats) = parse_output(KISSAT_OUTPUT.as_bytes(), false).await.unwrap();
+        assert!(matches!(solution, Solution::Satisfiable { .. }));
+        assert_eq!(
+            SolverStats {
+                conflicts: Some(1234),
+                decisions: Some(5678),
+                propagations: Some(901234),
+                process_time: Some(0.2),
+            },
+            stats,
+        );
+
+        let (_, stats) = parse_output(&b"s UNSATISFIABLE\n"[..], false)
+            .await
+            .unwrap();
+        assert_eq!(SolverStats::default(), stats);
+        assert_eq!("Solver statistics:\n", stats.format_summary());
+    }
+
+    #[tokio::test]
+    async fn parses_well_formed_output() {
+        match parse(b"c hello\ns SATISFIABLE\nv 1 -2\nv 3 0\n")
+            .await
+            .unwrap()
+        {
+            Solution::Satisfiable { assignments } => assert_eq!(3, assignments.len()),
+            solution => panic!("unexpected {:?}", solution),
+        }
+        assert!(matches!(
+            parse(b"s UNSATISFIABLE\n").await.unwrap(),
+            Solution::Unsatisfiable
+        ));
+        assert!(matches!(
+            parse(b"s UNKNOWN\n").await.unwrap(),
+            Solution::Unknown
+        ));
+        assert!(matches!(
+            parse(b"c interrupted\ns INDETERMINATE\n").await.unwrap(),
+            Solution::Unknown
+        ));
+        match parse(b"o 30\no 24\ns OPTIMUM FOUND\nv -1 2 0\n")
+            .await
+            .unwrap()
+        {
+            Solution::Satisfiable { assignments } => assert_eq!(2, assignments.len()),
+            solution => panic!("unexpected {:?}", solution),
+        }
+    }
+
+    #[tokio::test]
+    async fn finds_a_stub_solver_on_the_path() {
+        let empty = temp_dir().join(format!("sudoku-generator-path-empty-{}", id()));
+        let bin = temp_dir().join(format!("sudoku-generator-path-bin-{}", id()));
+        create_dir_all(&empty).await.unwrap();
+        create_dir_all(&bin).await.unwrap();
+        write(bin.join("stub-solver"), "#!/bin/sh\n").await.unwrap();
+
+        let path = join_paths([&empty, &bin]).unwrap();
+        let found = find_file_in("stub-solver", &path).await;
+        let missing = find_file_in("stub-solver", &join_paths([&empty]).unwrap()).await;
+        remove_dir_all(&empty).await.unwrap();
+        remove_dir_all(&bin).await.unwrap();
+
+        assert_eq!(bin.join("stub-solver"), found.unwrap());
+        assert!(missing.is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn same_seed_reproduces_a_seeded_stub_solver() {
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt;
+
+        use tokio::fs::set_permissions;
+
+        // A stand-in for a solver whose model depends only on its seed.
+        let dir = temp_dir().join(format!("sudoku-generator-seed-{}", id()));
+        create_dir_all(&dir).await.unwrap();
+        let stub = dir.join("seeded-solver");
+        write(
+            &stub,
+            "#!/bin/sh\n\
+             cat >/dev/null\n\
+             for arg; do case $arg in --seed=*) seed=${arg#--seed=};; esac; done\n\
+             echo 's SATISFIABLE'\n\
+             if [ $((seed % 2)) -eq 0 ]; then echo 'v 1 -2 0'; else echo 'v -1 2 0'; fi\n\
+             exit 10\n",
+        )
+        .await
+        .unwrap();
+        set_permissions(&stub, Permissions::from_mode(0o755))
+            .await
+            .unwrap();
+
+        let mut models = Vec::new();
+        for seed in [4, 4, 5] {
+            let command = solver_command(stub.clone(), false, Some(seed), None);
+            let solver = SatSolver::spawn(command, "seeded-solver", false).unwrap();
+            match solver.solve().await.unwrap() {
+                Solution::Satisfiable { assignments } => {
+                    let mut model: Vec<_> = assignments.into_iter().collect();
+                    model.sort_by_key(|(variable, _)| variable.index());
+                    models.push(model);
+                }
+                solution => panic!("unexpected {:?}", solution),
+            }
+        }
+        remove_dir_all(&dir).await.unwrap();
+
+        assert_eq!(models[0], models[1]);
+        assert_ne!(models[0], models[2]);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn solver_that_gives_up_reports_unknown() {
+        // A stand-in for a solver interrupted before deciding, which exits with status 0.
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg("cat >/dev/null; echo 'c interrupted'; echo 's UNKNOWN'; exit 0");
+        let solver = SatSolver::spawn(command, "sh", false).unwrap();
+        assert!(matches!(solver.solve().await.unwrap(), Solution::Unknown));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn solve_within_kills_a_solver_that_runs_too_long() {
+        // A stand-in for a solver that never finishes.
+        let mut command = Command::new("sleep");
+        command.arg("60");
+        let solver = SatSolver::spawn(command, "sleep", false).unwrap();
+        let pid = solver.child.id().unwrap();
+        assert!(Path::new(&format!("/proc/{}", pid)).exists());
+
+        let start = Instant::now();
+        let solution = solver
+            .solve_within(Some(Duration::from_millis(200)))
+            .await
+            .unwrap();
+        assert!(solution.is_none());
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        // The process was killed and reaped, so nothing is left of it.
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+    }
+
+    #[test]
+    fn blocking_clauses_enumerate_each_solution_once() {
+        // Exactly one of a and b holds, and c follows a, so there are exactly two solutions.
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        let c = formula.get_variable(2).as_positive();
+        formula.add_binary_clause(a, b);
+        formula.add_binary_clause(-a, -b);
+        formula.add_binary_clause(-a, c);
+        formula.add_binary_clause(a, -c);
+
+        let variables = [a.variable(), b.variable()];
+        let mut solutions = Vec::new();
+        while let Some(assignments) = formula.solve(&[]) {
+            formula.add_clause(blocking_clause(&assignments, &variables));
+            solutions.push(assignments[&a.variable()]);
+            assert!(solutions.len() <= 2, "found a solution twice");
+        }
+        solutions.sort();
+        assert_eq!(vec![false, true], solutions);
+    }
+
+    #[cfg(feature = "incremental")]
+    #[test]
+    fn incremental_solves_keep_the_formula_between_assumptions() {
+        use super::{IncrementalSolver, StubIncrementalSolver};
+
+        // Exactly one of a and b holds.
+        let mut formula = TaggedVariableFormulaBuilder::<usize>::default();
+        let a = formula.get_variable(0).as_positive();
+        let b = formula.get_variable(1).as_positive();
+        formula.add_binary_clause(a, b);
+        formula.add_binary_clause(-a, -b);
+        let mut solver = StubIncrementalSolver::new(formula);
+
+        match solver.solve_under_assumptions(&[a]).unwrap() {
+            Solution::Satisfiable { assignments } => assert!(!assignments[&b.variable()]),
+            solution => panic!("expected a solution, but found {:?}", solution),
+        }
+        assert!(matches!(
+            solver.solve_under_assumptions(&[a, b]).unwrap(),
+            Solution::Unsatisfiable
+        ));
+        // The assumptions of one solve don't carry over to the next, but added clauses do.
+        solver.add_clause(vec![-b]);
+        assert!(matches!(
+            solver.solve_under_assumptions(&[]).unwrap(),
+            Solution::Satisfiable { .. }
+        ));
+        assert!(matches!(
+            solver.solve_under_assumptions(&[-a]).unwrap(),
+            Solution::Unsatisfiable
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_output() {
+        for output in [
+            &b""[..],
+            b"s\n",
+            b"s SATISFIABLE EXTRA\n",
+            b"s OPTIMUM\n",
+            b"o\n",
+            b"o -3\n",
+            b"o 3 4\n",
+            b"s MAYBE\n",
+            b"v 1 0\ns SATISFIABLE\n",
+            b"s SATISFIABLE\nv 1 2\n",
+            b"s SATISFIABLE\nv 1 x 0\n",
+            b"s SATISFIABLE\nv -2147483648 0\n",
+            b"s SATISFIABLE\nv 1 0 2\n",
+            b"s UNSATISFIABLE\ns UNSATISFIABLE\n",
+            b"s SATISFIABLE\n\xff\n",
+        ] {
+            assert!(parse(output).await.is_err(), "{:?}", output);
+        }
+    }
+
+    #[tokio::test]
+    async fn random_output_never_panics() {
+        let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..2000 {
+            let len = rng.next() as usize % 64;
+            let bytes: Vec<u8> = (0..len).map(|_| rng.next() as u8).collect();
+            let _ = parse(&bytes).await;
+        }
+
+        // Mostly plausible lines, to reach deeper than the first line of the parser.
+        let tokens = [
+            "s",
+            "v",
+            "c",
+            "SATISFIABLE",
+            "UNSATISFIABLE",
+            "UNKNOWN",
+            "OPTIMUM",
+            "FOUND",
+            "0",
+            "1",
+            "-1",
+            "42",
+            "2147483647",
+            "-2147483648",
+            "2147483648",
+            "x",
+            "",
+            " ",
+            "\t",
+        ];
+        for _ in 0..2000 {
+            let mut output = String::new();
+            for _ in 0..rng.next() % 8 {
+                output += rng.pick(&["s", "v", "c", "o", "", "sv"]);
+                for _ in 0..rng.next() % 5 {
+                    output += " ";
+                    output += rng.pick(&tokens);
+                }
+                output += "\n";
+            }
+            let _ = parse(output.as_bytes()).await;
+        }
+    }
 }
@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::process::exit;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::board_size::board_size;
+use crate::formula_builder::Variable;
+use crate::sat_solver::Solution;
+use crate::sudoku::{Cell, Digit, DigitSet, VariableKind};
+
+/// The state of the inference circuit at one level, for playing back a solve step by step.
+#[derive(Debug, Serialize)]
+pub struct Frame {
+    pub level: usize,
+    pub forced: Vec<Placement>,
+    pub eliminated: Vec<Placement>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Placement {
+    pub row: u8,
+    pub col: u8,
+    pub digit: u8,
+}
+
+/// Reads the forced and eliminated placements at every level of the inference circuit.
+pub fn solution_frames(
+    variables: &HashMap<VariableKind, Variable>,
+    assignments: &HashMap<Variable, bool>,
+) -> Vec<Frame> {
+    let mut frames = Vec::new();
+    for level in 0.. {
+        let mut frame = Frame {
+            level,
+            forced: Vec::new(),
+            eliminated: Vec::new(),
+        };
+        for cell in Cell::values() {
+            for digit in Digit::values() {
+                let forced = variables.get(&VariableKind::Forced {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                    level,
+                });
+                let eliminated = variables.get(&VariableKind::Eliminated {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                    level,
+                });
+                let (forced, eliminated) = match (forced, eliminated) {
+                    (Some(forced), Some(eliminated)) => (forced, eliminated),
+                    // The circuit has no more levels.
+                    _ => return frames,
+                };
+                let placement = Placement {
+                    row: cell.row.as_u8(),
+                    col: cell.col.as_u8(),
+                    digit: digit.as_u8(),
+                };
+                if assignments[forced] {
+                    frame.forced.push(placement);
+                } else if assignments[eliminated] {
+                    frame.eliminated.push(placement);
+                }
+            }
+        }
+        frames.push(frame);
+    }
+    unreachable!()
+}
+
+/// The digits each cell could still hold at `level` of the inference circuit: every digit not
+/// eliminated there. Fails if the circuit has no such level.
+pub fn candidates_at_level(
+    variables: &HashMap<VariableKind, Variable>,
+    assignments: &HashMap<Variable, bool>,
+    level: usize,
+) -> Result<HashMap<Cell, DigitSet>> {
+    let mut candidates = HashMap::new();
+    for cell in Cell::values() {
+        let mut digits = DigitSet::empty();
+        for digit in Digit::values() {
+            let eliminated = variables
+                .get(&VariableKind::Eliminated {
+                    row: cell.row,
+                    col: cell.col,
+                    digit,
+                    level,
+                })
+                .ok_or_else(|| anyhow!("the inference circuit has no level {}", level))?;
+            if !assignments[eliminated] {
+                digits.insert(digit);
+            }
+        }
+        candidates.insert(cell, digits);
+    }
+    Ok(candidates)
+}
+
+/// How far the inference circuit got toward solving a puzzle by its last level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    /// Every cell is forced.
+    Solved,
+    /// The last two levels agree, so no number of further levels would place more digits.
+    Stalled,
+    /// The last level still placed or eliminated something new, so more levels might finish.
+    OutOfLevels,
+}
+
+pub fn progress(frames: &[Frame]) -> Progress {
+    let last = match frames.last() {
+        Some(last) => last,
+        None => return Progress::OutOfLevels,
+    };
+    if last.forced.len() == board_size().cell_count() {
+        return Progress::Solved;
+    }
+    match frames.len().checked_sub(2).map(|i| &frames[i]) {
+        Some(prev) if prev.forced == last.forced && prev.eliminated == last.eliminated => {
+            Progress::Stalled
+        }
+        _ => Progress::OutOfLevels,
+    }
+}
+
+/// A difficulty score: the lowest level by which every cell is forced, or `None` if the circuit
+/// never solves the puzzle. Forced placements carry over from level to level, so this is also the
+/// latest level at which any one cell is first forced.
+pub fn difficulty(frames: &[Frame]) -> Option<usize> {
+    frames
+        .iter()
+        .find(|frame| frame.forced.len() == board_size().cell_count())
+        .map(|frame| frame.level)
+}
+
+/// Warns on stderr when a puzzle generated with a relaxed last level isn't actually solved by the
+/// inference circuit.
+pub fn warn_if_unsolved(
+    variables: &HashMap<VariableKind, Variable>,
+    assignments: &HashMap<Variable, bool>,
+) {
+    let frames = solution_frames(variables, assignments);
+    match progress(&frames) {
+        Progress::Solved => (),
+        Progress::Stalled => eprintln!(
+            "warning: the enabled inference rules stall before solving this puzzle; \
+             enable more rules to finish it"
+        ),
+        Progress::OutOfLevels => eprintln!(
+            "warning: the inference circuit had not reached a fixpoint after {} levels; \
+             raise --max_inference_levels to finish solving this puzzle",
+            frames.len(),
+        ),
+    }
+}
+
+pub async fn print_solution_frames(
+    variables: &HashMap<VariableKind, Variable>,
+    solution: &Solution,
+) -> Result<()> {
+    let assignments = match solution {
+        Solution::Satisfiable { assignments } => assignments,
+        Solution::Unsatisfiable => {
+            println!("UNSATISFIABLE");
+            exit(1);
+        }
+        Solution::Unknown => return Err(anyhow!("the solver stopped without deciding")),
+    };
+
+    println!("{}", render_frames(variables, assignments)?);
+
+    Ok(())
+}
+
+/// Serializes the frames of a solved puzzle as JSON.
+pub fn render_frames(
+    variables: &HashMap<VariableKind, Variable>,
+    assignments: &HashMap<Variable, bool>,
+) -> Result<String> {
+    Ok(serde_json::to_string(&solution_frames(
+        variables,
+        assignments,
+    ))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{difficulty, progress, Frame, Placement, Progress};
+
+    fn frame(level: usize, forced: usize, eliminated: usize) -> Frame {
+        let placement = |i: usize| Placement {
+            row: (i / 9 + 1) as u8,
+            col: (i % 9 + 1) as u8,
+            digit: 1,
+        };
+        Frame {
+            level,
+            forced: (0..forced).map(placement).collect(),
+            eliminated: (0..eliminated).map(placement).collect(),
+        }
+    }
+
+    #[test]
+    fn classifies_progress_by_the_last_two_levels() {
+        assert_eq!(
+            Progress::Solved,
+            progress(&[frame(0, 30, 0), frame(1, 81, 0)])
+        );
+        assert_eq!(
+            Progress::Stalled,
+            progress(&[frame(0, 30, 5), frame(1, 40, 9), frame(2, 40, 9)])
+        );
+        assert_eq!(
+            Progress::OutOfLevels,
+            progress(&[frame(0, 30, 5), frame(1, 40, 9), frame(2, 40, 12)])
+        );
+        assert_eq!(Progress::OutOfLevels, progress(&[frame(0, 30, 5)]));
+    }
+
+    #[test]
+    fn difficulty_is_the_first_solved_level() {
+        assert_eq!(
+            Some(2),
+            difficulty(&[
+                frame(0, 30, 0),
+                frame(1, 60, 0),
+                frame(2, 81, 0),
+                frame(3, 81, 0)
+            ])
+        );
+        assert_eq!(None, difficulty(&[frame(0, 30, 5), frame(1, 40, 9)]));
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+
+use crate::emit_problem::{
+    build_formula, build_maxsat_formula, verify_model, EmissionStats, Parameters,
+};
+use crate::formula_builder::Variable;
+use crate::sat_solver::{SatSolver, Solution, SolverStats};
+use crate::sudoku::VariableKind;
+
+/// How long past its own time limit a solver may run before it's killed anyway.
+const NATIVE_TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+/// How hard to try when generating a puzzle.
+#[derive(Clone, Copy, Debug)]
+pub struct SolveOptions {
+    /// Give up on an attempt after this long, if set.
+    pub timeout: Option<Duration>,
+    /// Try this many times in all, with a different solver seed each time, before giving up.
+    pub attempts: usize,
+    /// Seed the first attempt's solver with this, and count up from it for retries. The same
+    /// parameters and seed reproduce the same puzzle only if the solver honors `--seed`.
+    pub seed: Option<u64>,
+    /// When an attempt is unsatisfiable, retry with more inference levels instead of reporting it.
+    pub escalate_levels: bool,
+    /// Check the solver's model against every emitted clause before accepting it.
+    pub verify_model: bool,
+    /// Pass `timeout` to the solver as its own time limit rather than killing it when it expires.
+    pub native_timeout: bool,
+    /// Ask a MaxSAT solver for the puzzle with the fewest givens, instead of any puzzle.
+    pub minimize_givens: bool,
+}
+
+/// Builds the formula for `params` and solves it, giving up after `timeout_duration` if set.
+pub async fn solve(
+    params: &Parameters,
+    timeout_duration: Option<Duration>,
+) -> Result<(HashMap<VariableKind, Variable>, Solution)> {
+    match solve_attempt(params, timeout_duration, None, false, false).await? {
+        None => Err(anyhow!(
+            "the solver timed out after {:?}",
+            timeout_duration.unwrap()
+        )),
+        Some((_, Solution::Unknown, ..)) => Err(anyhow!("the solver gave up (unknown)")),
+        Some((variables, solution, ..)) => Ok((variables, solution)),
+    }
+}
+
+/// Solves like `solve`, but retries attempts that time out or that the solver gives up on with a
+/// fresh seed, and with `escalate_levels` retries unsatisfiable attempts with more inference
+/// levels, up to `options.attempts` attempts in all. Also returns the solver's and the formula's
+/// statistics from the attempt that finished.
+pub async fn solve_with_retries(
+    params: &Parameters,
+    options: SolveOptions,
+) -> Result<(
+    HashMap<VariableKind, Variable>,
+    Solution,
+    SolverStats,
+    EmissionStats,
+)> {
+    let mut params = params.clone();
+    let mut gave_up = 0;
+    for attempt in 1..=options.attempts {
+        // Without a seed, the first attempt runs the solver as usual, so a single attempt behaves
+        // like `solve`.
+        let seed = match options.seed {
+            Some(seed) => Some(seed.wrapping_add(attempt as u64 - 1)),
+            None if attempt == 1 => None,
+            None => Some(attempt as u64 - 1),
+        };
+        match solve_attempt(
+            &params,
+            options.timeout,
+            seed,
+            options.native_timeout,
+            options.minimize_givens,
+        )
+        .await?
+        {
+            None => eprintln!(
+                "Attempt {} of {} timed out after {:?}.",
+                attempt,
+                options.attempts,
+                options.timeout.unwrap(),
+            ),
+            Some((_, Solution::Unknown, ..)) => {
+                eprintln!(
+                    "Attempt {} of {}: solver gave up (unknown).",
+                    attempt, options.attempts,
+                );
+                gave_up += 1;
+            }
+            Some((_, Solution::Unsatisfiable, ..))
+                if options.escalate_levels && attempt < options.attempts =>
+            {
+                eprintln!(
+                    "Attempt {} of {} was unsatisfiable with {} inference levels.",
+                    attempt, options.attempts, params.inference_levels,
+                );
+                params.inference_levels *= 2;
+            }
+            Some(result) => {
+                if let (true, Solution::Satisfiable { assignments }) =
+                    (options.verify_model, &result.1)
+                {
+                    verify_model(&params, assignments)?;
+                }
+                if attempt > 1 {
+                    eprintln!("Attempt {} of {} finished.", attempt, options.attempts);
+                }
+                return Ok(result);
+            }
+        }
+    }
+    Err(match gave_up {
+        0 => anyhow!("all {} attempts timed out", options.attempts),
+        n if n == options.attempts => {
+            anyhow!(
+                "solver gave up (unknown) on all {} attempts",
+                options.attempts
+            )
+        }
+        n => anyhow!(
+            "all {} attempts failed: {} timed out and the solver gave up (unknown) on {}",
+            options.attempts,
+            options.attempts - n,
+            n,
+        ),
+    })
+}
+
+/// Returns `None` if the solver had to be killed when the attempt ran out of time. A solver that
+/// stops itself reports `Solution::Unknown` instead.
+async fn solve_attempt(
+    params: &Parameters,
+    timeout_duration: Option<Duration>,
+    seed: Option<u64>,
+    native_timeout: bool,
+    minimize_givens: bool,
+) -> Result<
+    Option<(
+        HashMap<VariableKind, Variable>,
+        Solution,
+        SolverStats,
+        EmissionStats,
+    )>,
+> {
+    if minimize_givens {
+        // The MaxSAT solver takes no seed or time limit, so a retry just runs it again.
+        let mut solver = SatSolver::start_maxsat().await?;
+        let (variables, emission_stats) = build_maxsat_formula(solver.input(), params).await?;
+        return Ok(solver
+            .solve_within_with_stats(timeout_duration)
+            .await?
+            .map(|(solution, stats)| (variables, solution, stats, emission_stats)));
+    }
+
+    let (mut solver, kill_after) = match (seed, timeout_duration) {
+        (seed, Some(limit)) if native_timeout => (
+            SatSolver::start_time_limited(seed, limit).await?,
+            // Still kill a solver that overruns its own limit, but give it time to exit cleanly.
+            Some(limit + NATIVE_TIMEOUT_GRACE),
+        ),
+        (Some(seed), _) => (SatSolver::start_seeded(seed).await?, timeout_duration),
+        (None, _) => (SatSolver::start().await?, timeout_duration),
+    };
+    let (variables, emission_stats) = build_formula(solver.input(), params).await?;
+    Ok(solver
+        .solve_within_with_stats(kill_after)
+        .await?
+        .map(|(solution, stats)| (variables, solution, stats, emission_stats)))
+}
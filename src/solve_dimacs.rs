@@ -0,0 +1,31 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use tokio::fs::{read, read_to_string};
+use tokio::io::AsyncWriteExt;
+
+use crate::formula_builder::Variable;
+use crate::sat_solver::{SatSolver, Solution};
+use crate::sudoku::VariableKind;
+use crate::var_map::parse_var_map;
+
+/// Solves a DIMACS formula saved by `--print_formula`, reading what its variables mean from the
+/// map saved alongside it by `--dump_var_map`, so the solution can be visualized like a fresh one.
+pub async fn solve_dimacs(
+    formula_path: &Path,
+    var_map_path: &Path,
+) -> Result<(HashMap<VariableKind, Variable>, Solution)> {
+    let var_map = read_to_string(var_map_path)
+        .await
+        .with_context(|| format!("Failed to read {}", var_map_path.display()))?;
+    let variables = parse_var_map(&var_map)
+        .with_context(|| format!("Failed to parse {}", var_map_path.display()))?;
+    let formula = read(formula_path)
+        .await
+        .with_context(|| format!("Failed to read {}", formula_path.display()))?;
+
+    let mut solver = SatSolver::start().await?;
+    solver.input().write_all(&formula).await?;
+    Ok((variables, solver.solve().await?))
+}
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use tokio::io::{stdin, stdout, AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+use crate::emit_problem::{build_solving_formula, Parameters};
+use crate::puzzle_string::{format_puzzle_string, parse_puzzle_string};
+use crate::sat_solver::{SatSolver, Solution};
+
+/// Reads puzzles from stdin, one per line in the single-line format, and writes each solution to
+/// stdout on its own line. Blank lines and lines starting with `#` are skipped. A line that fails
+/// to parse or has no solution is reported on stderr and skipped without stopping the batch. Each
+/// puzzle is solved under the variants in `params`.
+pub async fn solve_stdin(params: &Parameters) -> Result<()> {
+    let mut lines = BufReader::new(stdin()).lines();
+    let mut stdout = stdout();
+    let mut line_number = 0;
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let givens = match parse_puzzle_string(line) {
+            Ok(givens) => givens,
+            Err(e) => {
+                eprintln!("line {}: {}", line_number, e);
+                continue;
+            }
+        };
+
+        let mut solver = SatSolver::start_quiet().await?;
+        let variables =
+            build_solving_formula(solver.input(), params, &givens, params.propagate).await?;
+        let solution = solver.solve().await?;
+        if let Solution::Unsatisfiable = solution {
+            eprintln!("line {}: puzzle has no solution", line_number);
+            continue;
+        }
+        let digits = solution.to_grid(&variables)?.placements();
+        stdout
+            .write_all(format!("{}\n", format_puzzle_string(&digits)).as_bytes())
+            .await?;
+        stdout.flush().await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+/// Returns every subset of `items` with exactly `k` elements. Each subset preserves the order of
+/// `items`, and the subsets are listed in lexicographic order of their positions.
+pub fn subsets<T: Copy>(items: &[T], k: usize) -> Vec<Vec<T>> {
+    let mut result = Vec::new();
+    let mut current = Vec::with_capacity(k);
+    extend_subsets(items, k, &mut current, &mut result);
+    result
+}
+
+fn extend_subsets<T: Copy>(items: &[T], k: usize, current: &mut Vec<T>, result: &mut Vec<Vec<T>>) {
+    if current.len() == k {
+        result.push(current.clone());
+        return;
+    }
+    let needed = k - current.len();
+    for i in 0..items.len() {
+        if items.len() - i < needed {
+            break;
+        }
+        current.push(items[i]);
+        extend_subsets(&items[i + 1..], k, current, result);
+        current.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::subsets;
+
+    #[test]
+    fn subset_counts() {
+        let items: Vec<_> = (0..9).collect();
+        assert_eq!(1, subsets(&items, 0).len());
+        assert_eq!(9, subsets(&items, 1).len());
+        assert_eq!(36, subsets(&items, 2).len());
+        assert_eq!(126, subsets(&items, 4).len());
+        assert_eq!(1, subsets(&items, 9).len());
+        assert_eq!(0, subsets(&items, 10).len());
+    }
+
+    #[test]
+    fn subsets_are_ordered() {
+        assert_eq!(
+            vec![vec![1, 2], vec![1, 3], vec![2, 3]],
+            subsets(&[1, 2, 3], 2)
+        );
+    }
+}
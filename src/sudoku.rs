@@ -1,3 +1,14 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::iter::FromIterator;
+use std::str::FromStr;
+
+use smallvec::SmallVec;
+use thiserror::Error;
+
+use crate::board_size::board_size;
+use crate::regions::region_of;
+
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum VariableKind {
     Placed {
@@ -21,10 +32,129 @@ pub enum VariableKind {
         digit: Digit,
         level: usize,
     },
+    Confined {
+        row: Row,
+        col: Col,
+        digits: DigitSet,
+        level: usize,
+    },
+    RemotePair {
+        origin_row: Row,
+        origin_col: Col,
+        row: Row,
+        col: Col,
+        digits: DigitSet,
+        odd: bool,
+        links: usize,
+        level: usize,
+    },
 }
 
-macro_rules! bounded_integer_1_through_9 {
-    ($name:ident) => {
+impl VariableKind {
+    /// A short snake_case name for this kind of variable, ignoring its fields.
+    pub fn name(&self) -> &'static str {
+        match self {
+            VariableKind::Placed { .. } => "placed",
+            VariableKind::Given { .. } => "given",
+            VariableKind::Forced { .. } => "forced",
+            VariableKind::Eliminated { .. } => "eliminated",
+            VariableKind::Confined { .. } => "confined",
+            VariableKind::RemotePair { .. } => "remote_pair",
+        }
+    }
+}
+
+/// A compact, stable form of the variable, like `Placed r3c5 d7` or `Forced r3c5 d7 l2`. Digit sets
+/// list their digits after the `d`, levels follow an `l`, and a remote pair's link count follows
+/// an `n`.
+impl fmt::Display for VariableKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cell = |row, col| Cell { row, col };
+        let digits = |digits: &DigitSet| -> String { digits.iter().map(Digit::symbol).collect() };
+        match self {
+            VariableKind::Placed { row, col, digit } => {
+                write!(f, "Placed {} d{}", cell(*row, *col), digit.as_u8())
+            }
+            VariableKind::Given { row, col } => write!(f, "Given {}", cell(*row, *col)),
+            VariableKind::Forced {
+                row,
+                col,
+                digit,
+                level,
+            } => write!(
+                f,
+                "Forced {} d{} l{}",
+                cell(*row, *col),
+                digit.as_u8(),
+                level
+            ),
+            VariableKind::Eliminated {
+                row,
+                col,
+                digit,
+                level,
+            } => write!(
+                f,
+                "Eliminated {} d{} l{}",
+                cell(*row, *col),
+                digit.as_u8(),
+                level
+            ),
+            VariableKind::Confined {
+                row,
+                col,
+                digits: set,
+                level,
+            } => write!(
+                f,
+                "Confined {} d{} l{}",
+                cell(*row, *col),
+                digits(set),
+                level
+            ),
+            VariableKind::RemotePair {
+                origin_row,
+                origin_col,
+                row,
+                col,
+                digits: set,
+                odd,
+                links,
+                level,
+            } => write!(
+                f,
+                "RemotePair {} {} d{} {} n{} l{}",
+                cell(*origin_row, *origin_col),
+                cell(*row, *col),
+                digits(set),
+                if *odd { "odd" } else { "even" },
+                links,
+                level
+            ),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("expected a {kind} from 1 through {max}, but found {value:?}")]
+pub struct OutOfRangeError {
+    kind: &'static str,
+    max: u8,
+    value: String,
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum CellNameError {
+    #[error("expected a cell like r1c2, but found {0:?}")]
+    Malformed(String),
+
+    #[error(transparent)]
+    OutOfRange(#[from] OutOfRangeError),
+}
+
+/// Defines a type for the integers from 1 through the side length of the board in effect.
+macro_rules! bounded_integer_1_through_side {
+    ($name:ident, $kind:literal) => {
         #[derive(::std::clone::Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
         pub struct $name(u8);
 
@@ -40,11 +170,11 @@ macro_rules! bounded_integer_1_through_9 {
             pub const K9: Self = Self(9);
 
             pub fn values() -> impl Iterator<Item = Self> {
-                (1..=9).into_iter().map(|x| Self(x))
+                (1..=board_size().side()).map(|x| Self(x))
             }
 
-            pub const fn new(value: u8) -> Option<Self> {
-                if value >= 1 && value <= 9 {
+            pub fn new(value: u8) -> Option<Self> {
+                if value >= 1 && value <= board_size().side() {
                     Some(Self(value))
                 } else {
                     None
@@ -59,12 +189,63 @@ macro_rules! bounded_integer_1_through_9 {
                 self.0 as u32 - 1
             }
         }
+
+        impl TryFrom<u8> for $name {
+            type Error = OutOfRangeError;
+
+            fn try_from(value: u8) -> Result<Self, OutOfRangeError> {
+                Self::new(value).ok_or_else(|| OutOfRangeError {
+                    kind: $kind,
+                    max: board_size().side(),
+                    value: value.to_string(),
+                })
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = OutOfRangeError;
+
+            fn from_str(s: &str) -> Result<Self, OutOfRangeError> {
+                s.parse::<u8>()
+                    .ok()
+                    .and_then(Self::new)
+                    .ok_or_else(|| OutOfRangeError {
+                        kind: $kind,
+                        max: board_size().side(),
+                        value: s.to_string(),
+                    })
+            }
+        }
     };
 }
 
-bounded_integer_1_through_9!(Row);
-bounded_integer_1_through_9!(Col);
-bounded_integer_1_through_9!(Digit);
+bounded_integer_1_through_side!(Row, "row");
+bounded_integer_1_through_side!(Col, "column");
+bounded_integer_1_through_side!(Digit, "digit");
+
+impl Digit {
+    /// The character for this digit in puzzle strings: `1` through `9`, or on hexadoku `0` through
+    /// `F` for 1 through 16.
+    pub fn symbol(self) -> char {
+        if board_size().uses_hex_symbols() {
+            char::from_digit(self.0 as u32 - 1, 16)
+                .unwrap()
+                .to_ascii_uppercase()
+        } else {
+            char::from(b'0' + self.0)
+        }
+    }
+
+    /// The digit written as `c`, the inverse of `symbol`. Hexadecimal letters may be either case.
+    pub fn from_symbol(c: char) -> Option<Self> {
+        let value = if board_size().uses_hex_symbols() {
+            c.to_digit(16)? + 1
+        } else {
+            c.to_digit(10)?
+        };
+        Self::new(value as u8)
+    }
+}
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Cell {
@@ -72,19 +253,138 @@ pub struct Cell {
     pub col: Col,
 }
 
+/// Formats a cell by row and column, like `r1c2`.
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "r{}c{}", self.row.as_u8(), self.col.as_u8())
+    }
+}
+
+/// Parses a cell formatted like `r1c2`.
+impl FromStr for Cell {
+    type Err = CellNameError;
+
+    fn from_str(s: &str) -> Result<Self, CellNameError> {
+        let malformed = || CellNameError::Malformed(s.to_string());
+        let (row, col) = s
+            .strip_prefix('r')
+            .and_then(|s| s.split_once('c'))
+            .ok_or_else(malformed)?;
+        Ok(Cell {
+            row: row.parse()?,
+            col: col.parse()?,
+        })
+    }
+}
+
 impl Cell {
     pub fn values() -> impl Iterator<Item = Self> {
         Row::values().flat_map(|row| Col::values().map(move |col| Cell { row, col }))
     }
 
-    pub const fn box_(self) -> Box {
-        Box((self.row.0 - 1) / 3 * 3 + (self.col.0 - 1) / 3 + 1)
+    /// This cell's position in row-major order, from 0 up to the number of cells on the board.
+    pub fn index(self) -> usize {
+        self.row.index() as usize * board_size().side() as usize + self.col.index() as usize
     }
 
+    /// The box containing this cell, from the region layout in effect.
+    pub fn box_(self) -> Box {
+        region_of(self)
+    }
+
+    /// Whether this cell lies on the diagonal from r1c1 to the opposite corner.
+    pub fn on_main_diagonal(self) -> bool {
+        self.row.index() == self.col.index()
+    }
+
+    /// Whether this cell lies on the diagonal from the top right to the bottom left corner.
+    pub fn on_anti_diagonal(self) -> bool {
+        self.row.index() + self.col.index() == board_size().side() as u32 - 1
+    }
+
+    /// The other cells sharing a diagonal with this one, which diagonal sudoku forbids from holding
+    /// the same digit. Cells off both diagonals have none, and the center cell is on both.
+    pub fn diagonal_peers(self) -> Vec<Cell> {
+        Cell::values()
+            .filter(|&other| {
+                other != self
+                    && ((self.on_main_diagonal() && other.on_main_diagonal())
+                        || (self.on_anti_diagonal() && other.on_anti_diagonal()))
+            })
+            .collect()
+    }
+
+    /// The cells a chess knight's move away from this one, which anti-knight sudoku forbids from
+    /// holding the same digit. Moves that would leave the board are skipped.
+    pub fn knight_moves(self) -> impl Iterator<Item = Cell> {
+        const MOVES: [(i8, i8); 8] = [
+            (-2, -1),
+            (-2, 1),
+            (-1, -2),
+            (-1, 2),
+            (1, -2),
+            (1, 2),
+            (2, -1),
+            (2, 1),
+        ];
+        MOVES.iter().filter_map(move |&(dr, dc)| {
+            Some(Cell {
+                row: Row::new(u8::try_from(self.row.as_u8() as i8 + dr).ok()?)?,
+                col: Col::new(u8::try_from(self.col.as_u8() as i8 + dc).ok()?)?,
+            })
+        })
+    }
+
+    /// The cells a chess king's move away from this one. The diagonal ones are what anti-king
+    /// sudoku forbids from holding the same digit; the orthogonal ones already share a row or
+    /// column. Moves that would leave the board are skipped.
+    pub fn king_moves(self) -> impl Iterator<Item = Cell> {
+        const MOVES: [(i8, i8); 8] = [
+            (-1, -1),
+            (-1, 0),
+            (-1, 1),
+            (0, -1),
+            (0, 1),
+            (1, -1),
+            (1, 0),
+            (1, 1),
+        ];
+        MOVES.iter().filter_map(move |&(dr, dc)| {
+            Some(Cell {
+                row: Row::new(u8::try_from(self.row.as_u8() as i8 + dr).ok()?)?,
+                col: Col::new(u8::try_from(self.col.as_u8() as i8 + dc).ok()?)?,
+            })
+        })
+    }
+
+    /// The row, column, and box containing this cell.
+    pub fn houses(self) -> [House; 3] {
+        [
+            House::Row(self.row),
+            House::Col(self.col),
+            House::Box(self.box_()),
+        ]
+    }
+
+    /// The houses containing both this cell and the other cell. Two distinct cells share at most a
+    /// line and a box. A cell shares all three of its houses with itself.
+    pub fn shared_houses(self, rhs: Self) -> SmallVec<[House; 2]> {
+        let mut houses = SmallVec::new();
+        if self.row == rhs.row {
+            houses.push(House::Row(self.row));
+        }
+        if self.col == rhs.col {
+            houses.push(House::Col(self.col));
+        }
+        if self.box_() == rhs.box_() {
+            houses.push(House::Box(self.box_()));
+        }
+        houses
+    }
+
+    /// How many houses the two cells share, from 0 through 3.
     pub fn common_houses(self, rhs: Self) -> usize {
-        (if self.row == rhs.row { 1 } else { 0 }
-            + if self.col == rhs.col { 1 } else { 0 }
-            + if self.box_() == rhs.box_() { 1 } else { 0 })
+        self.shared_houses(rhs).len()
     }
 
     /// Whether this cell sees the other cell and is distinct from it. True if the cells share a
@@ -106,39 +406,355 @@ impl Cell {
     }
 }
 
-bounded_integer_1_through_9!(Box);
+bounded_integer_1_through_side!(Box, "box");
+
+/// A set of digits stored as a bitmask indexed by `Digit::index`.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DigitSet(u16);
+
+impl DigitSet {
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Every digit of the board in effect.
+    pub fn all() -> Self {
+        Self(((1u32 << board_size().side()) - 1) as u16)
+    }
+
+    pub const fn contains(self, digit: Digit) -> bool {
+        self.0 & (1 << digit.index()) != 0
+    }
+
+    pub fn insert(&mut self, digit: Digit) {
+        self.0 |= 1 << digit.index();
+    }
+
+    pub fn remove(&mut self, digit: Digit) {
+        self.0 &= !(1 << digit.index());
+    }
+
+    pub const fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(self) -> impl Iterator<Item = Digit> {
+        Digit::values().filter(move |digit| self.contains(*digit))
+    }
+}
+
+impl FromIterator<Digit> for DigitSet {
+    fn from_iter<I: IntoIterator<Item = Digit>>(iter: I) -> Self {
+        let mut set = Self::empty();
+        for digit in iter {
+            set.insert(digit);
+        }
+        set
+    }
+}
+
+/// A row, column, or box: a group of nine cells that holds each digit once.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub enum House {
+    Row(Row),
+    Col(Col),
+    Box(Box),
+}
+
+impl House {
+    /// Every row, then every column, then every box.
+    pub fn values() -> impl Iterator<Item = Self> {
+        Row::values()
+            .map(House::Row)
+            .chain(Col::values().map(House::Col))
+            .chain(Box::values().map(House::Box))
+    }
+
+    pub fn cells(self) -> Vec<Cell> {
+        match self {
+            House::Row(row) => Col::values().map(|col| Cell { row, col }).collect(),
+            House::Col(col) => Row::values().map(|row| Cell { row, col }).collect(),
+            House::Box(box_) => box_.cells().collect(),
+        }
+    }
+}
+
+/// A house yields its cells, so `for cell in house` walks a row, column, or box.
+///
+/// ```
+/// use sudoku_generator::sudoku::{House, Row};
+///
+/// let row = House::Row(Row::new(4).unwrap());
+/// let mut count = 0;
+/// for cell in row {
+///     assert_eq!(4, cell.row.as_u8());
+///     count += 1;
+/// }
+/// assert_eq!(9, count);
+/// ```
+impl IntoIterator for House {
+    type Item = Cell;
+    type IntoIter = std::vec::IntoIter<Cell>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.cells().into_iter()
+    }
+}
+
+/// The cells of every row, column, and box, in the order of `House::values`.
+pub fn houses() -> Vec<Vec<Cell>> {
+    House::values().map(House::cells).collect()
+}
 
 impl Box {
+    /// The rows crossing this box: three for a classic box, and up to nine for a jigsaw region.
     pub fn rows(self) -> impl Iterator<Item = Row> {
-        let base_row = (self.0 - 1) / 3 * 3 + 1;
-        (base_row..base_row + 3).into_iter().map(|x| Row(x))
+        Row::values().filter(move |&row| self.cells().any(|cell| cell.row == row))
     }
 
+    /// The columns crossing this box: three for a classic box, and up to nine for a jigsaw region.
     pub fn cols(self) -> impl Iterator<Item = Col> {
-        let base_col = (self.0 - 1) % 3 * 3 + 1;
-        (base_col..base_col + 3).into_iter().map(|x| Col(x))
+        Col::values().filter(move |&col| self.cells().any(|cell| cell.col == col))
     }
 
     pub fn cells(self) -> impl Iterator<Item = Cell> {
-        self.rows()
-            .flat_map(move |row| self.cols().map(move |col| Cell { row, col }))
+        Cell::values().filter(move |cell| cell.box_() == self)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Cell;
+    use std::convert::TryFrom;
+
+    use super::{Box, Cell, Col, Digit, DigitSet, House, OutOfRangeError, Row, VariableKind};
+    use crate::board_size::{with_board_size, BoardSize};
+
+    #[test]
+    fn variable_kinds_display_compactly() {
+        let digits: DigitSet = [Digit::K1, Digit::K2].iter().copied().collect();
+        for (expected, kind) in [
+            (
+                "Placed r3c5 d7",
+                VariableKind::Placed {
+                    row: Row::K3,
+                    col: Col::K5,
+                    digit: Digit::K7,
+                },
+            ),
+            (
+                "Given r9c1",
+                VariableKind::Given {
+                    row: Row::K9,
+                    col: Col::K1,
+                },
+            ),
+            (
+                "Eliminated r1c2 d3 l4",
+                VariableKind::Eliminated {
+                    row: Row::K1,
+                    col: Col::K2,
+                    digit: Digit::K3,
+                    level: 4,
+                },
+            ),
+            (
+                "Confined r1c2 d12 l0",
+                VariableKind::Confined {
+                    row: Row::K1,
+                    col: Col::K2,
+                    digits,
+                    level: 0,
+                },
+            ),
+            (
+                "RemotePair r1c1 r2c4 d12 odd n3 l5",
+                VariableKind::RemotePair {
+                    origin_row: Row::K1,
+                    origin_col: Col::K1,
+                    row: Row::K2,
+                    col: Col::K4,
+                    digits,
+                    odd: true,
+                    links: 3,
+                    level: 5,
+                },
+            ),
+        ] {
+            assert_eq!(expected, kind.to_string());
+        }
+    }
 
     #[test]
     fn box_consistency() {
-        for cell in Cell::values() {
-            let box_ = cell.box_();
-            assert_eq!(3, box_.rows().count());
-            assert_eq!(3, box_.cols().count());
-            assert_eq!(9, box_.cells().count());
-            assert_eq!(1, box_.rows().filter(|r| *r == cell.row).count());
-            assert_eq!(1, box_.cols().filter(|c| *c == cell.col).count());
-            assert_eq!(1, box_.cells().filter(|c| *c == cell).count());
+        for size in BoardSize::ALL {
+            with_board_size(size, || {
+                let side = size.side() as usize;
+                assert_eq!(side, Box::values().count());
+                assert_eq!(size.cell_count(), Cell::values().count());
+                for cell in Cell::values() {
+                    let box_ = cell.box_();
+                    assert_eq!(size.box_height() as usize, box_.rows().count());
+                    assert_eq!(size.box_width() as usize, box_.cols().count());
+                    assert_eq!(side, box_.cells().count());
+                    assert_eq!(1, box_.rows().filter(|r| *r == cell.row).count());
+                    assert_eq!(1, box_.cols().filter(|c| *c == cell.col).count());
+                    assert_eq!(1, box_.cells().filter(|c| *c == cell).count());
+                    assert!(cell.index() < size.cell_count());
+                }
+                assert_eq!(side, DigitSet::all().len());
+            });
+        }
+    }
+
+    #[test]
+    fn houses_cover_the_grid() {
+        assert_eq!(27, House::values().count());
+        let mut counts = [[0; 3]; 81];
+        for house in House::values() {
+            let kind = match house {
+                House::Row(_) => 0,
+                House::Col(_) => 1,
+                House::Box(_) => 2,
+            };
+            assert_eq!(9, house.into_iter().count());
+            for cell in house {
+                assert!(cell.houses().contains(&house));
+                counts[cell.index()][kind] += 1;
+            }
+        }
+        // Each kind of house partitions the grid.
+        assert!(counts.iter().all(|&count| count == [1, 1, 1]));
+    }
+
+    #[test]
+    fn diagonal_membership() {
+        let cell = |row, col| Cell {
+            row: Row::new(row).unwrap(),
+            col: Col::new(col).unwrap(),
+        };
+        assert!(cell(1, 1).on_main_diagonal() && !cell(1, 1).on_anti_diagonal());
+        assert!(cell(9, 9).on_main_diagonal() && !cell(9, 9).on_anti_diagonal());
+        assert!(!cell(1, 9).on_main_diagonal() && cell(1, 9).on_anti_diagonal());
+        assert!(!cell(9, 1).on_main_diagonal() && cell(9, 1).on_anti_diagonal());
+        assert!(cell(5, 5).on_main_diagonal() && cell(5, 5).on_anti_diagonal());
+
+        assert_eq!(8, cell(1, 1).diagonal_peers().len());
+        assert!(cell(1, 1).diagonal_peers().contains(&cell(9, 9)));
+        assert!(!cell(1, 1).diagonal_peers().contains(&cell(9, 1)));
+        assert_eq!(16, cell(5, 5).diagonal_peers().len());
+        assert!(cell(1, 2).diagonal_peers().is_empty());
+    }
+
+    #[test]
+    fn knight_moves_stay_on_the_board() {
+        let cell = |row, col| Cell {
+            row: Row::new(row).unwrap(),
+            col: Col::new(col).unwrap(),
+        };
+        let mut center: Vec<_> = cell(5, 5).knight_moves().collect();
+        center.sort();
+        assert_eq!(
+            vec![
+                cell(3, 4),
+                cell(3, 6),
+                cell(4, 3),
+                cell(4, 7),
+                cell(6, 3),
+                cell(6, 7),
+                cell(7, 4),
+                cell(7, 6),
+            ],
+            center,
+        );
+        let mut corner: Vec<_> = cell(1, 1).knight_moves().collect();
+        corner.sort();
+        assert_eq!(vec![cell(2, 3), cell(3, 2)], corner);
+        assert_eq!(3, cell(1, 2).knight_moves().count());
+    }
+
+    #[test]
+    fn king_moves_stay_on_the_board() {
+        let cell = |row, col| Cell {
+            row: Row::new(row).unwrap(),
+            col: Col::new(col).unwrap(),
+        };
+        assert_eq!(8, cell(5, 5).king_moves().count());
+        let mut edge: Vec<_> = cell(1, 5).king_moves().collect();
+        edge.sort();
+        assert_eq!(
+            vec![cell(1, 4), cell(1, 6), cell(2, 4), cell(2, 5), cell(2, 6)],
+            edge,
+        );
+        let mut corner: Vec<_> = cell(9, 9).king_moves().collect();
+        corner.sort();
+        assert_eq!(vec![cell(8, 8), cell(8, 9), cell(9, 8)], corner);
+    }
+
+    #[test]
+    fn shared_houses() {
+        let cell = |row, col| Cell {
+            row: Row::new(row).unwrap(),
+            col: Col::new(col).unwrap(),
+        };
+        assert_eq!(
+            &[House::Row(Row::K1), House::Box(Box::K1)][..],
+            &cell(1, 1).shared_houses(cell(1, 3))[..],
+        );
+        assert_eq!(
+            &[House::Col(Col::K5)][..],
+            &cell(1, 5).shared_houses(cell(9, 5))[..],
+        );
+        assert!(cell(1, 1).shared_houses(cell(5, 5)).is_empty());
+        assert_eq!(3, cell(4, 4).common_houses(cell(4, 4)));
+        assert_eq!(2, cell(1, 1).common_houses(cell(1, 3)));
+        assert_eq!(1, cell(1, 1).common_houses(cell(1, 9)));
+        assert_eq!(0, cell(1, 1).common_houses(cell(5, 5)));
+        for a in Cell::values() {
+            for b in Cell::values() {
+                for house in a.shared_houses(b) {
+                    assert!(house.cells().contains(&a) && house.cells().contains(&b));
+                }
+                assert_eq!(a.sees_other(b), a != b && a.common_houses(b) > 0);
+            }
         }
     }
+
+    macro_rules! test_conversions {
+        ($test:ident, $name:ident, $kind:literal) => {
+            #[test]
+            fn $test() {
+                for value in $name::values() {
+                    assert_eq!(Ok(value), $name::try_from(value.as_u8()));
+                    assert_eq!(Ok(value), value.as_u8().to_string().parse());
+                }
+                for bad in &["0", "10", "", " 1", "x"] {
+                    assert_eq!(
+                        Err(OutOfRangeError {
+                            kind: $kind,
+                            max: 9,
+                            value: bad.to_string(),
+                        }),
+                        bad.parse::<$name>(),
+                    );
+                }
+                assert!($name::try_from(0).is_err());
+                assert!($name::try_from(10).is_err());
+
+                // Smaller boards shrink the range.
+                with_board_size(BoardSize::new(2, 2), || {
+                    assert_eq!(4, $name::values().count());
+                    assert!($name::try_from(5).is_err());
+                });
+            }
+        };
+    }
+
+    test_conversions!(row_conversions, Row, "row");
+    test_conversions!(col_conversions, Col, "column");
+    test_conversions!(digit_conversions, Digit, "digit");
+    test_conversions!(box_conversions, Box, "box");
 }
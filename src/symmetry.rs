@@ -0,0 +1,131 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::board_size::board_size;
+use crate::sudoku::{Cell, Col, Row};
+
+/// A symmetry the pattern of givens must have, as in most published sudoku.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Symmetry {
+    #[default]
+    None,
+    /// Unchanged by a half turn about the center cell.
+    Rotational,
+    /// Unchanged by reflection across the middle row, so the top and bottom halves match.
+    Horizontal,
+    /// Unchanged by reflection across the middle column, so the left and right halves match.
+    Vertical,
+    /// Unchanged by reflection across the diagonal from r1c1 to r9c9.
+    Diagonal,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 5] = [
+        Symmetry::None,
+        Symmetry::Rotational,
+        Symmetry::Horizontal,
+        Symmetry::Vertical,
+        Symmetry::Diagonal,
+    ];
+
+    /// The symmetry's name, as given to `--symmetry`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Symmetry::None => "none",
+            Symmetry::Rotational => "rotational",
+            Symmetry::Horizontal => "horizontal",
+            Symmetry::Vertical => "vertical",
+            Symmetry::Diagonal => "diagonal",
+        }
+    }
+
+    /// The cell that `cell` maps to, which must be given exactly when `cell` is. Cells on the axis
+    /// of a reflection, and the center under rotation, map to themselves. Returns `None` without
+    /// a symmetry.
+    pub fn mirror(self, cell: Cell) -> Option<Cell> {
+        let side = board_size().side();
+        let flip_row = Row::new(side + 1 - cell.row.as_u8()).unwrap();
+        let flip_col = Col::new(side + 1 - cell.col.as_u8()).unwrap();
+        match self {
+            Symmetry::None => None,
+            Symmetry::Rotational => Some(Cell {
+                row: flip_row,
+                col: flip_col,
+            }),
+            Symmetry::Horizontal => Some(Cell {
+                row: flip_row,
+                ..cell
+            }),
+            Symmetry::Vertical => Some(Cell {
+                col: flip_col,
+                ..cell
+            }),
+            Symmetry::Diagonal => Some(Cell {
+                row: Row::new(cell.col.as_u8()).unwrap(),
+                col: Col::new(cell.row.as_u8()).unwrap(),
+            }),
+        }
+    }
+}
+
+impl Display for Symmetry {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+#[error("expected none, rotational, horizontal, vertical, or diagonal symmetry, but found {0:?}")]
+pub struct UnknownSymmetryError(String);
+
+impl FromStr for Symmetry {
+    type Err = UnknownSymmetryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Symmetry::ALL
+            .iter()
+            .copied()
+            .find(|symmetry| symmetry.name() == s)
+            .ok_or_else(|| UnknownSymmetryError(s.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symmetry;
+    use crate::sudoku::Cell;
+
+    #[test]
+    fn mirrors_are_involutions() {
+        for symmetry in Symmetry::ALL {
+            assert_eq!(Ok(symmetry), symmetry.name().parse());
+            for cell in Cell::values() {
+                if let Some(mirror) = symmetry.mirror(cell) {
+                    assert_eq!(Some(cell), symmetry.mirror(mirror));
+                }
+            }
+        }
+        assert!("radial".parse::<Symmetry>().is_err());
+
+        let cell = |s: &str| s.parse::<Cell>().unwrap();
+        assert_eq!(
+            Some(cell("r9c8")),
+            Symmetry::Rotational.mirror(cell("r1c2"))
+        );
+        assert_eq!(
+            Some(cell("r5c5")),
+            Symmetry::Rotational.mirror(cell("r5c5"))
+        );
+        assert_eq!(
+            Some(cell("r9c2")),
+            Symmetry::Horizontal.mirror(cell("r1c2"))
+        );
+        assert_eq!(Some(cell("r1c8")), Symmetry::Vertical.mirror(cell("r1c2")));
+        assert_eq!(Some(cell("r2c1")), Symmetry::Diagonal.mirror(cell("r1c2")));
+        assert_eq!(None, Symmetry::None.mirror(cell("r1c2")));
+    }
+}
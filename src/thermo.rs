@@ -0,0 +1,142 @@
+use std::fmt::{self, Display, Formatter};
+
+use thiserror::Error;
+
+use crate::board_size::board_size;
+use crate::sudoku::{Cell, CellNameError};
+
+/// A thermometer: a path of touching cells whose digits must strictly increase from the bulb, its
+/// first cell, to its tip.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Thermo {
+    pub cells: Vec<Cell>,
+}
+
+/// Formats a thermometer as `--thermos` takes it, bulb first, like `r1c1+r1c2+r2c3`.
+impl Display for Thermo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let cells: Vec<_> = self.cells.iter().map(ToString::to_string).collect();
+        write!(f, "{}", cells.join("+"))
+    }
+}
+
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum ThermoError {
+    #[error("in thermometer {thermo:?}: {source}")]
+    BadCell {
+        thermo: String,
+        source: CellNameError,
+    },
+
+    #[error("thermometer {0:?} needs at least two cells")]
+    TooShort(String),
+
+    #[error(
+        "thermometer {thermo:?} has {cells} cells, but its digits can only increase {max} times"
+    )]
+    TooLong {
+        thermo: String,
+        cells: usize,
+        max: usize,
+    },
+
+    #[error("in thermometer {thermo:?}: {cell} appears more than once")]
+    RepeatedCell { thermo: String, cell: Cell },
+
+    #[error("in thermometer {thermo:?}: {from} doesn't touch {to}")]
+    NotTouching {
+        thermo: String,
+        from: Cell,
+        to: Cell,
+    },
+}
+
+/// Parses a semicolon-separated list of thermometers, each a `+`-separated path of cells from the
+/// bulb to the tip, like `r1c1+r1c2+r2c3;r9c9+r8c8`. Each step must move to a cell touching the
+/// last, orthogonally or diagonally. Different thermometers may share cells, as branching ones do.
+pub fn parse_thermos(s: &str) -> Result<Vec<Thermo>, ThermoError> {
+    let mut thermos = Vec::new();
+    for text in s.split(';').map(str::trim).filter(|text| !text.is_empty()) {
+        let mut cells: Vec<Cell> = Vec::new();
+        for name in text.split('+').map(str::trim) {
+            let cell = name
+                .parse::<Cell>()
+                .map_err(|source| ThermoError::BadCell {
+                    thermo: text.to_string(),
+                    source,
+                })?;
+            if cells.contains(&cell) {
+                return Err(ThermoError::RepeatedCell {
+                    thermo: text.to_string(),
+                    cell,
+                });
+            }
+            if let Some(&last) = cells.last() {
+                if last.row.index().abs_diff(cell.row.index()) > 1
+                    || last.col.index().abs_diff(cell.col.index()) > 1
+                {
+                    return Err(ThermoError::NotTouching {
+                        thermo: text.to_string(),
+                        from: last,
+                        to: cell,
+                    });
+                }
+            }
+            cells.push(cell);
+        }
+        if cells.len() < 2 {
+            return Err(ThermoError::TooShort(text.to_string()));
+        }
+        let side = board_size().side() as usize;
+        if cells.len() > side {
+            return Err(ThermoError::TooLong {
+                thermo: text.to_string(),
+                cells: cells.len(),
+                max: side,
+            });
+        }
+        thermos.push(Thermo { cells });
+    }
+    Ok(thermos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_thermos, ThermoError};
+
+    #[test]
+    fn parses_thermos_bulb_first() {
+        let thermos = parse_thermos("r1c1+r1c2+r2c3; r9c9 + r8c8").unwrap();
+        assert_eq!(2, thermos.len());
+        assert_eq!("r1c1", thermos[0].cells[0].to_string());
+        assert_eq!("r1c1+r1c2+r2c3", thermos[0].to_string());
+        assert_eq!("r9c9+r8c8", thermos[1].to_string());
+
+        // Branches share their bulb.
+        assert_eq!(2, parse_thermos("r5c5+r5c6;r5c5+r6c5").unwrap().len());
+    }
+
+    #[test]
+    fn rejects_invalid_thermos() {
+        assert!(matches!(
+            parse_thermos("r1c1+x"),
+            Err(ThermoError::BadCell { .. })
+        ));
+        assert!(matches!(
+            parse_thermos("r1c1"),
+            Err(ThermoError::TooShort(_))
+        ));
+        assert!(matches!(
+            parse_thermos("r1c1+r1c2+r1c3+r1c4+r1c5+r1c6+r1c7+r1c8+r1c9+r2c9"),
+            Err(ThermoError::TooLong { max: 9, .. })
+        ));
+        assert!(matches!(
+            parse_thermos("r1c1+r1c2+r1c1"),
+            Err(ThermoError::RepeatedCell { .. })
+        ));
+        assert!(matches!(
+            parse_thermos("r1c1+r1c3"),
+            Err(ThermoError::NotTouching { .. })
+        ));
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::emit_problem::{solving_formula, Parameters};
+use crate::formula_builder::Variable;
+use crate::sat_solver::{enumerate_solutions, Solution};
+use crate::sudoku::{Cell, Digit, VariableKind};
+
+/// How many solutions a puzzle has, counting no further than two.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolutionCount {
+    None,
+    Unique,
+    Multiple,
+}
+
+/// Counts the solutions of the puzzle with the given placements, under the variants in `params`, by
+/// solving it once, then solving again with the first solution's placements blocked.
+pub async fn count_solutions(
+    params: &Parameters,
+    givens: &HashMap<Cell, Digit>,
+) -> Result<SolutionCount> {
+    let mut formula = solving_formula(params, givens, true);
+    let placed: Vec<_> = formula
+        .tagged_variables()
+        .iter()
+        .filter(|(kind, _)| matches!(kind, VariableKind::Placed { .. }))
+        .map(|(_, &variable)| variable)
+        .collect();
+    Ok(
+        match enumerate_solutions(&mut formula, &placed, 2).await?.len() {
+            0 => SolutionCount::None,
+            1 => SolutionCount::Unique,
+            _ => SolutionCount::Multiple,
+        },
+    )
+}
+
+/// Checks that a generated puzzle's givens, with the rules of sudoku and of the variants it was
+/// generated with but none of the inference circuit, admit no completion other than the one the
+/// solver found.
+pub async fn check_unique(
+    params: &Parameters,
+    variables: &HashMap<VariableKind, Variable>,
+    solution: &Solution,
+) -> Result<bool> {
+    let givens = solution
+        .to_grid(variables)?
+        .givens(&solution.givens_mask(variables)?);
+    Ok(count_solutions(params, &givens).await? == SolutionCount::Unique)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::emit_problem::{solving_formula, Parameters};
+    use crate::formula_builder::FormulaBuilder;
+    use crate::puzzle_string::parse_puzzle_string;
+    use crate::sat_solver::blocking_clause;
+    use crate::sudoku::{Cell, Digit, VariableKind};
+    use crate::thermo::parse_thermos;
+
+    const SOLUTION: &str =
+        "534678912672195348198342567859761423426853791713924856961537284287419635345286179";
+
+    /// Counts completions of `givens` up to `limit` the way `count_solutions` does, but with the
+    /// in-crate solver, since tests can't count on kissat being installed.
+    fn count_completions(
+        params: &Parameters,
+        givens: &HashMap<Cell, Digit>,
+        limit: usize,
+    ) -> usize {
+        let mut formula = solving_formula(params, givens, false);
+        let placed: Vec<_> = formula
+            .tagged_variables()
+            .iter()
+            .filter(|(kind, _)| matches!(kind, VariableKind::Placed { .. }))
+            .map(|(_, &variable)| variable)
+            .collect();
+        let mut count = 0;
+        while count < limit {
+            match formula.solve(&[]) {
+                Some(assignments) => {
+                    formula.add_clause(blocking_clause(&assignments, &placed));
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn blocking_the_first_completion_finds_a_second() {
+        let solution = parse_puzzle_string(SOLUTION).unwrap();
+
+        // Without one cell, the rest of the grid still pins it down.
+        let mut givens = solution.clone();
+        givens.remove(&"r5c5".parse().unwrap());
+        assert_eq!(1, count_completions(&Parameters::default(), &givens, 2));
+
+        // Rows 1 and 2 share a band, so without them both orders of the pair complete the grid.
+        let givens: HashMap<_, _> = solution
+            .into_iter()
+            .filter(|(cell, _)| cell.row.as_u8() > 2)
+            .collect();
+        assert_eq!(2, count_completions(&Parameters::default(), &givens, 2));
+    }
+
+    #[test]
+    fn variant_rules_count_toward_uniqueness() {
+        let givens: HashMap<_, _> = parse_puzzle_string(SOLUTION)
+            .unwrap()
+            .into_iter()
+            .filter(|(cell, _)| cell.row.as_u8() > 2)
+            .collect();
+        assert_eq!(4, count_completions(&Parameters::default(), &givens, 5));
+
+        // The four completions only differ in rows 1 and 2, and two thermometers pointing down the
+        // first columns rule out all but the original grid.
+        let params = Parameters {
+            thermos: parse_thermos("r1c1+r2c1;r1c2+r2c2").unwrap(),
+            ..Parameters::default()
+        };
+        assert_eq!(1, count_completions(&params, &givens, 5));
+    }
+}
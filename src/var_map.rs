@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::formula_builder::Variable;
+use crate::positive_i32::PositiveI32;
+use crate::sudoku::{Col, Digit, Row, VariableKind};
+
+/// Lists every tagged variable and its DIMACS number, one per line, sorted by tag so the output
+/// only changes when the numbering does.
+pub fn format_var_map(variables: &HashMap<VariableKind, Variable>) -> String {
+    let mut entries: Vec<_> = variables.iter().collect();
+    entries.sort();
+    let mut output = String::new();
+    for (kind, variable) in entries {
+        writeln!(&mut output, "{:?} {}", kind, variable.index().as_i32()).unwrap();
+    }
+    output
+}
+
+/// Reads back the placement and given variables from a map written by `format_var_map`, which is
+/// all it takes to visualize a solution. Lines for other kinds of variables are skipped.
+pub fn parse_var_map(s: &str) -> Result<HashMap<VariableKind, Variable>> {
+    let mut variables = HashMap::new();
+    for (i, line) in s.lines().enumerate() {
+        let parse_line = || -> Result<Option<(VariableKind, Variable)>> {
+            let (kind, index) = line
+                .rsplit_once(' ')
+                .ok_or_else(|| anyhow!("expected a variable and its number"))?;
+            let index = index
+                .parse::<u32>()
+                .ok()
+                .and_then(PositiveI32::from_u32)
+                .ok_or_else(|| anyhow!("bad variable number {:?}", index))?;
+            let row = || {
+                field(kind, "row: Row(")
+                    .and_then(|row| Row::new(row).ok_or_else(|| anyhow!("bad row")))
+            };
+            let col = || {
+                field(kind, "col: Col(")
+                    .and_then(|col| Col::new(col).ok_or_else(|| anyhow!("bad column")))
+            };
+            let kind = if kind.starts_with("Placed {") {
+                VariableKind::Placed {
+                    row: row()?,
+                    col: col()?,
+                    digit: field(kind, "digit: Digit(")
+                        .and_then(|digit| Digit::new(digit).ok_or_else(|| anyhow!("bad digit")))?,
+                }
+            } else if kind.starts_with("Given {") {
+                VariableKind::Given {
+                    row: row()?,
+                    col: col()?,
+                }
+            } else {
+                return Ok(None);
+            };
+            Ok(Some((kind, Variable::from_index(index))))
+        };
+        if let Some((kind, variable)) =
+            parse_line().with_context(|| format!("var map line {}: {:?}", i + 1, line))?
+        {
+            variables.insert(kind, variable);
+        }
+    }
+    Ok(variables)
+}
+
+/// The number following `prefix` in `kind` and running to the next closing parenthesis.
+fn field(kind: &str, prefix: &str) -> Result<u8> {
+    let start = kind
+        .find(prefix)
+        .ok_or_else(|| anyhow!("missing {:?}", prefix))?
+        + prefix.len();
+    let len = kind[start..]
+        .find(')')
+        .ok_or_else(|| anyhow!("unterminated {:?}", prefix))?;
+    Ok(kind[start..start + len].parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::io::sink;
+
+    use super::{format_var_map, parse_var_map};
+    use crate::formula_builder::{TaggedVariableFormulaBuilder, Variable};
+    use crate::positive_i32::PositiveI32;
+    use crate::sudoku::{Col, Digit, Row, VariableKind};
+
+    /// A var map is what `--dump_var_map` prints, and what `--solve_dimacs` reads from its
+    /// `--var_map` sidecar: one variable per line, its tag in Rust debug syntax followed by a space
+    /// and its DIMACS number.
+    const VAR_MAP: &str = "\
+Placed { row: Row(1), col: Col(2), digit: Digit(3) } 7
+Given { row: Row(9), col: Col(8) } 12
+Forced { row: Row(1), col: Col(1), digit: Digit(1), level: 0 } 40
+";
+
+    #[test]
+    fn parses_placements_and_givens() {
+        let variables = parse_var_map(VAR_MAP).unwrap();
+        let variable = |index| Variable::from_index(PositiveI32::from_u32(index).unwrap());
+        assert_eq!(2, variables.len());
+        assert_eq!(
+            Some(&variable(7)),
+            variables.get(&VariableKind::Placed {
+                row: Row::new(1).unwrap(),
+                col: Col::new(2).unwrap(),
+                digit: Digit::new(3).unwrap(),
+            })
+        );
+        assert_eq!(
+            Some(&variable(12)),
+            variables.get(&VariableKind::Given {
+                row: Row::new(9).unwrap(),
+                col: Col::new(8).unwrap(),
+            })
+        );
+
+        assert!(parse_var_map("Given { row: Row(9), col: Col(8) }\n").is_err());
+        assert!(parse_var_map("Given { row: Row(10), col: Col(8) } 12\n").is_err());
+        assert!(parse_var_map("Placed { row: Row(1), col: Col(2) } 7\n").is_err());
+    }
+
+    #[test]
+    fn round_trips_placements_and_givens() {
+        let mut formula = TaggedVariableFormulaBuilder::default();
+        let mut expected = HashMap::new();
+        for kind in [
+            VariableKind::Placed {
+                row: Row::K4,
+                col: Col::K5,
+                digit: Digit::K6,
+            },
+            VariableKind::Given {
+                row: Row::K2,
+                col: Col::K3,
+            },
+        ] {
+            expected.insert(kind, formula.get_variable(kind));
+        }
+        let variables = formula.into_tagged_variables();
+        assert_eq!(
+            expected,
+            parse_var_map(&format_var_map(&variables)).unwrap()
+        );
+    }
+    use crate::emit_problem::{build_formula, Inferences, Parameters};
+
+    /// FNV-1a, which unlike `DefaultHasher` is guaranteed stable across Rust releases.
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        for &byte in bytes {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+        hash
+    }
+
+    /// Guards the variable numbering of a small formula. If a change renumbers variables on
+    /// purpose, update the expected digest to the one this test reports.
+    #[tokio::test]
+    async fn var_map_is_unchanged() {
+        let params = Parameters {
+            givens: Some(30),
+            min_givens: None,
+            max_givens: None,
+            inference_levels: 2,
+            relax_last_level: false,
+            max_clause_count: None,
+            sequential_at_most_one: false,
+            dedup_clauses: false,
+            no_at_least_one: false,
+            max_chain_links: 4,
+            min_distinct_techniques: 0,
+            min_givens_per_digit: 0,
+            min_empty_cells: 0,
+            allowed_inferences: Inferences {
+                naked_single: true,
+                hidden_single: true,
+                pointing: false,
+                claiming: false,
+                naked_pair: false,
+                naked_triple: false,
+                naked_quad: false,
+                x_wing: false,
+                xy_wing: false,
+                w_wing: false,
+                remote_pairs: false,
+                jellyfish: false,
+            },
+            fixed_givens: None,
+            propagate: true,
+            pattern: Default::default(),
+            diagonals: false,
+            anti_knight: false,
+            anti_king: false,
+            symmetry: Default::default(),
+            cages: Vec::new(),
+            sandwiches: Vec::new(),
+            thermos: Vec::new(),
+            dots: Vec::new(),
+            kropki_negative: false,
+            even_cells: Vec::new(),
+            odd_cells: Vec::new(),
+            excluded_puzzles: Vec::new(),
+            excluded_solutions: Vec::new(),
+            base_only: false,
+        };
+        let (variables, _) = build_formula(&mut sink(), &params).await.unwrap();
+        let var_map = format_var_map(&variables);
+        assert_eq!(
+            0x0637_edc0_06fa_d7ec,
+            fnv1a(var_map.as_bytes()),
+            "the variable numbering changed; the new map starts with:\n{}",
+            var_map.lines().take(5).collect::<Vec<_>>().join("\n"),
+        );
+    }
+}
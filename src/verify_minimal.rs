@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+
+use crate::emit_problem::Parameters;
+use crate::puzzle_string::parse_puzzle_string;
+use crate::uniqueness::{count_solutions, SolutionCount};
+
+/// Checks that a puzzle has a unique solution and that removing any one of its givens would admit
+/// another solution, under the variants in `params`. Reports each given the puzzle could do
+/// without.
+pub async fn verify_minimal(params: &Parameters, puzzle: &str) -> Result<()> {
+    let givens = parse_puzzle_string(puzzle)?;
+    match count_solutions(params, &givens).await? {
+        SolutionCount::Unique => (),
+        SolutionCount::None => return Err(anyhow!("the puzzle has no solution")),
+        SolutionCount::Multiple => return Err(anyhow!("the puzzle has more than one solution")),
+    }
+
+    let mut cells: Vec<_> = givens.keys().copied().collect();
+    cells.sort();
+    let mut removable = Vec::new();
+    for cell in cells {
+        let mut reduced = givens.clone();
+        let digit = reduced.remove(&cell).unwrap();
+        if count_solutions(params, &reduced).await? == SolutionCount::Unique {
+            println!(
+                "{}={} can be removed and the solution stays unique",
+                cell,
+                digit.as_u8()
+            );
+            removable.push(cell);
+        }
+    }
+
+    if removable.is_empty() {
+        println!("Minimal: all {} givens are needed.", givens.len());
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "not minimal: {} of {} givens can be removed",
+            removable.len(),
+            givens.len(),
+        ))
+    }
+}
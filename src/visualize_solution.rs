@@ -1,74 +1,345 @@
 use std::collections::HashMap;
 use std::fmt::Write;
-use std::process::exit;
 
 use anyhow::Result;
 
+use crate::board_size::board_size;
 use crate::formula_builder::Variable;
+use crate::grid::{GivensMask, Grid};
 use crate::sat_solver::Solution;
-use crate::sudoku::{Cell, Col, Digit, Row, VariableKind};
+use crate::sudoku::{Cell, Col, Digit, DigitSet, Row, VariableKind};
 
-pub async fn visualize_solution(
+/// Reads the solved grid and which of its cells are givens, or `None` if the formula was
+/// unsatisfiable. A solver that stopped without deciding is an error.
+pub fn solution_to_grid(
     variables: &HashMap<VariableKind, Variable>,
     solution: &Solution,
-) -> Result<()> {
-    let assignments = match solution {
-        Solution::Satisfiable { assignments } => assignments,
-        Solution::Unsatisfiable => {
-            println!("UNSATISFIABLE");
-            exit(1);
-        }
-    };
+) -> Result<Option<(Grid, GivensMask)>> {
+    if let Solution::Unsatisfiable = solution {
+        return Ok(None);
+    }
+    Ok(Some((
+        solution.to_grid(variables)?,
+        solution.givens_mask(variables)?,
+    )))
+}
 
-    let mut digits: HashMap<Cell, Digit> = Default::default();
-    for cell in Cell::values() {
-        for digit in Digit::values() {
-            if assignments[&variables[&VariableKind::Placed {
-                row: cell.row,
-                col: cell.col,
-                digit,
-            }]] {
-                digits.insert(cell, digit);
-            }
-        }
+/// Prints the givens of a puzzle as a bordered grid, with the other cells' `candidates` penciled in
+/// if given, followed by the whole solution if `show_solution` is set. With `color`, the givens are
+/// bold and the candidates dim.
+pub fn visualize_solution(
+    grid: &Grid,
+    mask: &GivensMask,
+    candidates: Option<&HashMap<Cell, DigitSet>>,
+    show_solution: bool,
+    color: bool,
+) {
+    match candidates {
+        Some(candidates) => print!("{}", render_pencil_marks(grid, mask, candidates, color)),
+        None => print!("{}", render_grid(grid, mask, color)),
+    }
+    if show_solution {
+        println!();
+        print!("{}", render_solution_grid(grid, mask));
     }
+}
 
-    let mut given: HashMap<Cell, bool> = Default::default();
-    for cell in Cell::values() {
-        given.insert(
-            cell,
-            assignments[&variables[&VariableKind::Given {
-                row: cell.row,
-                col: cell.col,
-            }]],
-        );
+/// Prints a puzzle, or its whole solution if `include_solution` is set, as tab-separated values.
+pub fn visualize_tsv(grid: &Grid, mask: &GivensMask, include_solution: bool) {
+    print!("{}", render_tsv(grid, mask, include_solution));
+}
+
+/// Prints a puzzle, or its whole solution if `include_solution` is set, as one line with a
+/// character per cell.
+pub fn visualize_oneline(grid: &Grid, mask: &GivensMask, include_solution: bool) {
+    println!("{}", render_oneline(grid, mask, include_solution));
+}
+
+/// Writes the puzzle, or its whole solution if `include_solution` is set, in the single-line
+/// format.
+pub fn render_oneline(grid: &Grid, mask: &GivensMask, include_solution: bool) -> String {
+    if include_solution {
+        grid.to_solution_string()
+    } else {
+        grid.to_puzzle_string(mask)
+    }
+}
+
+/// Writes tab-separated rows of digits for pasting into a spreadsheet. Cells that aren't
+/// givens are left as empty fields unless `include_solution` is set.
+pub fn render_tsv(grid: &Grid, mask: &GivensMask, include_solution: bool) -> String {
+    let mut output = String::new();
+    for row in Row::values() {
+        let fields: Vec<_> = Col::values()
+            .map(|col| {
+                let cell = Cell { row, col };
+                if include_solution || mask.is_given(cell) {
+                    grid[cell].as_u8().to_string()
+                } else {
+                    String::new()
+                }
+            })
+            .collect();
+        writeln!(&mut output, "{}", fields.join("\t")).unwrap();
     }
+    output
+}
 
-    const BORDER: &str = "+-------+-------+-------+";
+/// Draws the givens of a puzzle as a bordered grid, leaving the other cells blank. With `color`,
+/// the givens are bold.
+pub fn render_grid(grid: &Grid, mask: &GivensMask, color: bool) -> String {
+    let (height, width) = box_shape();
+    let border = border(width as usize * (cell_width() + 1) + 1);
+    let mut output = String::new();
     for row in Row::values() {
-        if row.index() % 3 == 0 {
-            println!("{}", BORDER);
+        if row.index() % height == 0 {
+            writeln!(&mut output, "{}", border).unwrap();
         }
         let mut line = "| ".to_string();
         for col in Col::values() {
             let cell = Cell { row, col };
             if col.index() > 0 {
-                if col.index() % 3 == 0 {
+                if col.index() % width == 0 {
                     line += " | ";
                 } else {
                     line += " ";
                 }
             }
-            if given[&cell] {
-                write!(&mut line, "{}", digits[&cell].as_u8())?;
+            if mask.is_given(cell) {
+                line += &styled(BOLD, grid[cell], color);
             } else {
-                line += " ";
+                line += &blank();
             }
         }
         line += " |";
-        println!("{}", line);
+        writeln!(&mut output, "{}", line).unwrap();
     }
-    println!("{}", BORDER);
+    writeln!(&mut output, "{}", border).unwrap();
+    output
+}
 
-    Ok(())
+/// The height and width of the boxes on the board in effect.
+fn box_shape() -> (u32, u32) {
+    let size = board_size();
+    (size.box_height() as u32, size.box_width() as u32)
+}
+
+/// How many characters the widest digit takes, so that every cell can be padded to match.
+fn cell_width() -> usize {
+    board_size().side().to_string().len()
+}
+
+/// An empty cell, as wide as a digit.
+fn blank() -> String {
+    " ".repeat(cell_width())
+}
+
+/// A horizontal border like `+---+---+`, with one segment of `segment` dashes per stack of boxes.
+fn border(segment: usize) -> String {
+    let size = board_size();
+    let stacks = (size.side() / size.box_width()) as usize;
+    format!("+{}", format!("{}+", "-".repeat(segment)).repeat(stacks))
+}
+
+/// Draws a puzzle with each cell as a block shaped like a box, three-by-three on a classic board: a
+/// given in the middle of its block, or the `candidates` still open for the other cells, each digit
+/// in its own position. With `color`, the givens are bold and the candidates dim.
+pub fn render_pencil_marks(
+    grid: &Grid,
+    mask: &GivensMask,
+    candidates: &HashMap<Cell, DigitSet>,
+    color: bool,
+) -> String {
+    let (height, width) = box_shape();
+    let border = border(width as usize * (width as usize * cell_width() + 1) + 1);
+    let mut output = String::new();
+    for row in Row::values() {
+        if row.index() % height == 0 {
+            writeln!(&mut output, "{}", border).unwrap();
+        }
+        for mark_row in 0..height {
+            let mut line = "| ".to_string();
+            for col in Col::values() {
+                let cell = Cell { row, col };
+                if col.index() > 0 {
+                    if col.index() % width == 0 {
+                        line += " | ";
+                    } else {
+                        line += " ";
+                    }
+                }
+                for mark_col in 0..width {
+                    let digit = Digit::new((mark_row * width + mark_col + 1) as u8).unwrap();
+                    if mask.is_given(cell) {
+                        if mark_row == height / 2 && mark_col == width / 2 {
+                            line += &styled(BOLD, grid[cell], color);
+                        } else {
+                            line += &blank();
+                        }
+                    } else if candidates[&cell].contains(digit) {
+                        line += &styled(DIM, digit, color);
+                    } else {
+                        line += &blank();
+                    }
+                }
+            }
+            line += " |";
+            writeln!(&mut output, "{}", line).unwrap();
+        }
+    }
+    writeln!(&mut output, "{}", border).unwrap();
+    output
+}
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RESET: &str = "\x1b[0m";
+
+/// A digit padded to the cell width and wrapped in an ANSI escape sequence, or bare without
+/// `color`.
+fn styled(style: &str, digit: Digit, color: bool) -> String {
+    let digit = format!("{:>width$}", digit.as_u8(), width = cell_width());
+    if color {
+        format!("{}{}{}", style, digit, RESET)
+    } else {
+        digit
+    }
+}
+
+/// Draws every digit of a solution as a bordered grid, with the givens in brackets so they stand
+/// apart from the cells the solver filled in.
+pub fn render_solution_grid(grid: &Grid, mask: &GivensMask) -> String {
+    let (height, width) = box_shape();
+    let border = border(width as usize * (cell_width() + 2));
+    let mut output = String::new();
+    for row in Row::values() {
+        if row.index() % height == 0 {
+            writeln!(&mut output, "{}", border).unwrap();
+        }
+        let mut line = String::new();
+        for col in Col::values() {
+            let cell = Cell { row, col };
+            if col.index() % width == 0 {
+                line += "|";
+            }
+            let digit = format!("{:>width$}", grid[cell].as_u8(), width = cell_width());
+            if mask.is_given(cell) {
+                write!(&mut line, "[{}]", digit).unwrap();
+            } else {
+                write!(&mut line, " {} ", digit).unwrap();
+            }
+        }
+        line += "|";
+        writeln!(&mut output, "{}", line).unwrap();
+    }
+    writeln!(&mut output, "{}", border).unwrap();
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{render_grid, render_pencil_marks, render_solution_grid, solution_to_grid};
+    use crate::board_size::{with_board_size, BoardSize};
+    use crate::formula_builder::TaggedVariableFormulaBuilder;
+    use crate::grid::{GivensMask, Grid};
+    use crate::sat_solver::Solution;
+    use crate::sudoku::DigitSet;
+    use crate::sudoku::{Cell, Digit, VariableKind};
+
+    #[test]
+    fn reads_grid_from_solution() {
+        // A shifted Latin square: r1 reads 123456789, and each later row starts one digit higher.
+        let digit = |cell: Cell| {
+            Digit::new(
+                ((cell.row.index() * 3 + cell.row.index() / 3 + cell.col.index()) % 9) as u8 + 1,
+            )
+            .unwrap()
+        };
+        let mut formula = TaggedVariableFormulaBuilder::<VariableKind>::default();
+        let mut assignments = HashMap::new();
+        for cell in Cell::values() {
+            let given = formula.get_variable(VariableKind::Given {
+                row: cell.row,
+                col: cell.col,
+            });
+            assignments.insert(given, cell.col.index() == 0);
+            for d in Digit::values() {
+                let placed = formula.get_variable(VariableKind::Placed {
+                    row: cell.row,
+                    col: cell.col,
+                    digit: d,
+                });
+                assignments.insert(placed, digit(cell) == d);
+            }
+        }
+        let variables = formula.into_tagged_variables();
+
+        let (grid, mask) = solution_to_grid(&variables, &Solution::Satisfiable { assignments })
+            .unwrap()
+            .unwrap();
+        for cell in Cell::values() {
+            assert_eq!(digit(cell), grid[cell]);
+            assert_eq!(cell.col.index() == 0, mask.is_given(cell));
+        }
+        assert_eq!(9, mask.count());
+
+        assert!(solution_to_grid(&variables, &Solution::Unsatisfiable)
+            .unwrap()
+            .is_none());
+        assert!(solution_to_grid(&variables, &Solution::Unknown).is_err());
+
+        let rendered = render_solution_grid(&grid, &mask);
+        let lines: Vec<_> = rendered.lines().collect();
+        assert_eq!(13, lines.len());
+        assert!(lines.iter().all(|line| line.len() == 31));
+        assert_eq!("|[1] 2  3 | 4  5  6 | 7  8  9 |", lines[1]);
+        assert_eq!("|[4] 5  6 | 7  8  9 | 1  2  3 |", lines[2]);
+        assert_eq!(9, rendered.matches('[').count());
+    }
+
+    #[test]
+    fn color_codes_only_with_color() {
+        let grid = Grid::from_fn(|cell| Digit::new(cell.col.as_u8()).unwrap());
+        let mask = GivensMask::from_fn(|cell| cell.row.index() == 0);
+        let candidates = Cell::values()
+            .map(|cell| (cell, DigitSet::all()))
+            .collect::<HashMap<_, _>>();
+
+        assert!(!render_grid(&grid, &mask, false).contains('\x1b'));
+        assert!(!render_pencil_marks(&grid, &mask, &candidates, false).contains('\x1b'));
+        assert_eq!(
+            9,
+            render_grid(&grid, &mask, true).matches("\x1b[1m").count()
+        );
+        let pencil_marks = render_pencil_marks(&grid, &mask, &candidates, true);
+        assert_eq!(9, pencil_marks.matches("\x1b[1m").count());
+        assert_eq!(72 * 9, pencil_marks.matches("\x1b[2m").count());
+    }
+
+    #[test]
+    fn hexadoku_pads_cells_to_two_characters() {
+        with_board_size(BoardSize::HEXADOKU, || {
+            let grid = Grid::from_fn(|cell| Digit::new(cell.col.as_u8()).unwrap());
+            let mask = GivensMask::from_fn(|cell| cell.row.index() == 0);
+            let candidates = Cell::values()
+                .map(|cell| (cell, DigitSet::all()))
+                .collect::<HashMap<_, _>>();
+
+            for rendered in [
+                render_grid(&grid, &mask, false),
+                render_pencil_marks(&grid, &mask, &candidates, false),
+                render_solution_grid(&grid, &mask),
+            ] {
+                let lines: Vec<_> = rendered.lines().collect();
+                assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+            }
+            let rendered = render_grid(&grid, &mask, false);
+            assert_eq!(
+                "|  1  2  3  4 |  5  6  7  8 |  9 10 11 12 | 13 14 15 16 |",
+                rendered.lines().nth(1).unwrap(),
+            );
+        });
+    }
 }